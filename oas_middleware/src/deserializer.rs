@@ -0,0 +1,633 @@
+use openapiv3::*;
+use serde_json::{Map, Value};
+
+use crate::error::E;
+use crate::request::{self, Params, RequestParts};
+use crate::spec_utils;
+
+/// Percent-decoding of the location of the parameter.
+/// Values are split on their delimiters before decoding, so encoded delimiters are kept.
+type Decode = fn(&str) -> String;
+
+/// Characters which change how a query string is split when they are not percent-encoded.
+/// RFC 3986 allows the other reserved characters, like `:` or `/`, in query values.
+/// Commas are the delimiters of the values which are lists.
+const RESERVED: &[char] = &['&', '=', '#'];
+
+/// How a schema expects the parameter to be serialized.
+enum Shape {
+    Primitive,
+    Array,
+    Object,
+}
+
+/// Builds the value of a parameter from the request following its `style` and `explode`.
+/// Scalars are converted to the type the schema expects when possible, so they can be
+/// checked by the schema validator.
+/// Returns None when the parameter is not in the request.
+///
+/// # Examples
+///
+///
+/// `?ids=1&ids=2` with `explode: true` becomes `[1, 2]`
+/// `?filter[name]=x` with `style: deepObject` becomes `{"name": "x"}`
+/// `/items/;id=5` with `style: matrix` becomes `5`
+///
+pub fn parameter_value(
+    parameter: &Parameter,
+    request_parts: &RequestParts,
+) -> Result<Option<Value>, E> {
+    let data = parameter.parameter_data_ref();
+    let name = data.name.as_str();
+
+    let schema = match &data.format {
+        ParameterSchemaOrContent::Schema(schema) => schema.as_item(),
+        ParameterSchemaOrContent::Content(content) => {
+            let value = match parameter {
+                Parameter::Query { .. } => {
+                    find(&request_parts.query_variables, name, false).map(request::decode_query)
+                }
+                Parameter::Path { .. } => {
+                    find(&request_parts.path_variables, name, false).map(request::decode_path)
+                }
+                Parameter::Header { .. } => {
+                    find(&request_parts.header_variables, name, true).map(str::to_string)
+                }
+                Parameter::Cookie { .. } => {
+                    find(&request_parts.cookie_variables, name, false).map(str::to_string)
+                }
+            };
+            return Ok(value.map(|value| content_value(content, &value)));
+        }
+    };
+
+    match parameter {
+        Parameter::Query {
+            style,
+            allow_reserved,
+            allow_empty_value,
+            ..
+        } => {
+            let variables = &request_parts.query_variables;
+            let explode = data.explode.unwrap_or(*style == QueryStyle::Form);
+            // A flag like `?verbose` turns a boolean on.
+            let flag = variables
+                .iter()
+                .any(|variable| variable.name == name && variable.raw_value.is_empty());
+            if flag && is_boolean(schema) {
+                return Ok(Some(Value::Bool(true)));
+            }
+            for variable in variables
+                .iter()
+                .filter(|variable| is_query_variable_of(parameter, &variable.name))
+            {
+                if variable.raw_value.is_empty() && !allow_empty_value.unwrap_or(false) {
+                    return Err(E::EmptyValueError(variable.name.to_string()));
+                }
+                if !allow_reserved && has_reserved(&variable.raw_value) {
+                    return Err(E::ReservedError(variable.name.to_string()));
+                }
+            }
+            Ok(query_value(name, style, explode, schema, variables))
+        }
+        Parameter::Path { style, .. } => {
+            let explode = data.explode.unwrap_or(false);
+            match find(&request_parts.path_variables, name, false) {
+                Some(raw) => path_value(name, style, explode, schema, raw).map(Some),
+                None => Ok(None),
+            }
+        }
+        Parameter::Header { .. } => {
+            let explode = data.explode.unwrap_or(false);
+            let raw = find(&request_parts.header_variables, name, true);
+            Ok(raw.map(|raw| simple_value(raw, explode, schema, str::to_string)))
+        }
+        Parameter::Cookie { .. } => {
+            let raw = find(&request_parts.cookie_variables, name, false);
+            Ok(raw.map(|raw| form_value(raw, schema)))
+        }
+    }
+}
+
+/// Builds the value of a response header, headers always use the simple style.
+pub fn header_value(header: &Header, raw: &str) -> Value {
+    match &header.format {
+        ParameterSchemaOrContent::Schema(schema) => {
+            simple_value(raw, false, schema.as_item(), str::to_string)
+        }
+        ParameterSchemaOrContent::Content(content) => content_value(content, raw),
+    }
+}
+
+/// True if the query variable with this name is part of the parameter.
+/// Objects can be spread over several query variables.
+pub fn is_query_variable_of(parameter: &Parameter, variable_name: &str) -> bool {
+    let (data, style) = match parameter {
+        Parameter::Query {
+            parameter_data,
+            style,
+            ..
+        } => (parameter_data, style),
+        _ => return false,
+    };
+    if data.name == variable_name {
+        return true;
+    }
+
+    let schema = match &data.format {
+        ParameterSchemaOrContent::Schema(schema) => schema.as_item(),
+        ParameterSchemaOrContent::Content(_) => None,
+    };
+    let explode = data.explode.unwrap_or(*style == QueryStyle::Form);
+    match (style, shape(schema)) {
+        (QueryStyle::DeepObject, _) => deep_object_property(&data.name, variable_name).is_some(),
+        (QueryStyle::Form, Shape::Object) if explode => {
+            property_schema(schema, variable_name).is_some()
+        }
+        _ => false,
+    }
+}
+
+fn query_value(
+    name: &str,
+    style: &QueryStyle,
+    explode: bool,
+    schema: Option<&Schema>,
+    variables: &Params,
+) -> Option<Value> {
+    let decode: Decode = request::decode_query;
+    let values: Vec<&str> = variables
+        .iter()
+        .filter(|variable| variable.name == name)
+        .map(|variable| variable.raw_value.as_str())
+        .collect();
+
+    match (style, shape(schema)) {
+        (QueryStyle::DeepObject, _) => {
+            let map: Map<String, Value> = variables
+                .iter()
+                .filter_map(|variable| {
+                    deep_object_property(name, &variable.name)
+                        .map(|property| (property, decode(&variable.raw_value)))
+                })
+                .map(|(property, value)| {
+                    let coerced = coerce(property_schema(schema, property), &value);
+                    (property.to_string(), coerced)
+                })
+                .collect();
+            Some(map).filter(|map| !map.is_empty()).map(Value::Object)
+        }
+        (_, Shape::Array) if !values.is_empty() => {
+            let items: Vec<&str> = if explode {
+                values
+            } else {
+                let separator = match style {
+                    QueryStyle::SpaceDelimited => ' ',
+                    QueryStyle::PipeDelimited => '|',
+                    _ => ',',
+                };
+                values
+                    .iter()
+                    .flat_map(|value| value.split(separator))
+                    .collect()
+            };
+            Some(array_value(&items, schema, decode))
+        }
+        (QueryStyle::Form, Shape::Object) if explode => {
+            // Each property is a query variable of its own.
+            let map: Map<String, Value> = variables
+                .iter()
+                .filter_map(|variable| {
+                    property_schema(schema, &variable.name).map(|property| {
+                        let coerced = coerce(Some(property), &decode(&variable.raw_value));
+                        (variable.name.clone(), coerced)
+                    })
+                })
+                .collect();
+            Some(map).filter(|map| !map.is_empty()).map(Value::Object)
+        }
+        (_, Shape::Object) => values
+            .first()
+            .map(|value| object_from_list(&value.split(',').collect::<Vec<_>>(), schema, decode)),
+        (_, _) => values.first().map(|value| coerce(schema, &decode(value))),
+    }
+}
+
+fn path_value(
+    name: &str,
+    style: &PathStyle,
+    explode: bool,
+    schema: Option<&Schema>,
+    raw: &str,
+) -> Result<Value, E> {
+    let decode: Decode = request::decode_path;
+    match style {
+        PathStyle::Simple => Ok(simple_value(raw, explode, schema, decode)),
+        PathStyle::Label => {
+            let content = raw
+                .strip_prefix('.')
+                .ok_or_else(|| style_error(name, "label"))?;
+            let separator = if explode { '.' } else { ',' };
+            let items: Vec<&str> = content.split(separator).collect();
+            Ok(match shape(schema) {
+                Shape::Primitive => coerce(schema, &decode(content)),
+                Shape::Array => array_value(&items, schema, decode),
+                Shape::Object if explode => object_from_pairs(&items, schema, decode),
+                Shape::Object => object_from_list(&items, schema, decode),
+            })
+        }
+        PathStyle::Matrix => {
+            let content = raw
+                .strip_prefix(';')
+                .ok_or_else(|| style_error(name, "matrix"))?;
+            let prefix = format!("{}=", name);
+            match shape(schema) {
+                Shape::Object if explode => {
+                    let pairs: Vec<&str> = content.split(';').collect();
+                    Ok(object_from_pairs(&pairs, schema, decode))
+                }
+                Shape::Array if explode => {
+                    let items = content
+                        .split(';')
+                        .map(|item| item.strip_prefix(&prefix).unwrap_or(""))
+                        .collect::<Vec<_>>();
+                    Ok(array_value(&items, schema, decode))
+                }
+                shape => {
+                    let value = match content.strip_prefix(&prefix) {
+                        Some(value) => value,
+                        None if content == name => "",
+                        None => return Err(style_error(name, "matrix")),
+                    };
+                    let items: Vec<&str> = value.split(',').collect();
+                    Ok(match shape {
+                        Shape::Primitive => coerce(schema, &decode(value)),
+                        Shape::Array => array_value(&items, schema, decode),
+                        Shape::Object => object_from_list(&items, schema, decode),
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// `simple` style used by path parameters and headers, `1,2,3` or `role=admin,name=x`.
+fn simple_value(raw: &str, explode: bool, schema: Option<&Schema>, decode: Decode) -> Value {
+    let items: Vec<&str> = raw.split(',').collect();
+    match shape(schema) {
+        Shape::Primitive => coerce(schema, &decode(raw)),
+        Shape::Array => array_value(&items, schema, decode),
+        Shape::Object if explode => object_from_pairs(&items, schema, decode),
+        Shape::Object => object_from_list(&items, schema, decode),
+    }
+}
+
+/// `form` style used by cookies, only the non exploded form can be represented.
+fn form_value(raw: &str, schema: Option<&Schema>) -> Value {
+    let items: Vec<&str> = raw.split(',').collect();
+    match shape(schema) {
+        Shape::Primitive => coerce(schema, raw),
+        Shape::Array => array_value(&items, schema, str::to_string),
+        Shape::Object => object_from_list(&items, schema, str::to_string),
+    }
+}
+
+/// Parameters described with a media type, JSON ones are parsed.
+fn content_value(content: &Content, raw: &str) -> Value {
+    let is_json = content
+        .keys()
+        .any(|media_type| spec_utils::is_json(media_type));
+    match serde_json::from_str(raw) {
+        Ok(value) if is_json => value,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+fn array_value(items: &[&str], schema: Option<&Schema>, decode: Decode) -> Value {
+    let items_schema = items_schema(schema);
+    Value::Array(
+        items
+            .iter()
+            .map(|item| coerce(items_schema, &decode(item)))
+            .collect(),
+    )
+}
+
+/// Objects serialized as `key=value` items.
+fn object_from_pairs(pairs: &[&str], schema: Option<&Schema>, decode: Decode) -> Value {
+    let map = pairs
+        .iter()
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(idx) => (&pair[..idx], &pair[idx + 1..]),
+            None => (*pair, ""),
+        })
+        .map(|(key, value)| {
+            let key = decode(key);
+            let value = coerce(property_schema(schema, &key), &decode(value));
+            (key, value)
+        })
+        .collect();
+    Value::Object(map)
+}
+
+/// Objects serialized as a `key,value,key,value` list.
+fn object_from_list(items: &[&str], schema: Option<&Schema>, decode: Decode) -> Value {
+    let map = items
+        .chunks(2)
+        .map(|chunk| {
+            let key = decode(chunk[0]);
+            let value = decode(chunk.get(1).copied().unwrap_or(""));
+            let value = coerce(property_schema(schema, &key), &value);
+            (key, value)
+        })
+        .collect();
+    Value::Object(map)
+}
+
+/// Converts a textual value to the JSON type the schema expects.
+/// Values which can't be converted stay as strings and the schema validator reports them.
+/// Without a type, numbers and booleans are guessed.
+fn coerce(schema: Option<&Schema>, raw: &str) -> Value {
+    let the_type = match schema.map(|schema| &schema.schema_kind) {
+        Some(SchemaKind::Type(the_type)) => the_type,
+        Some(SchemaKind::Any(AnySchema { typ: Some(typ), .. })) if typ == "string" => {
+            return Value::String(raw.to_string())
+        }
+        _ => return guess(raw),
+    };
+    match the_type {
+        Type::Integer(_) => raw.parse::<i64>().map(Value::from).ok(),
+        Type::Number(_) => raw
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+        Type::Boolean(_) => raw.parse::<bool>().map(Value::Bool).ok(),
+        _ => None,
+    }
+    .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+fn guess(raw: &str) -> Value {
+    if let Ok(integer) = raw.parse::<i64>() {
+        Value::from(integer)
+    } else if let Some(number) = raw
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+    {
+        Value::Number(number)
+    } else if let Ok(boolean) = raw.parse::<bool>() {
+        Value::Bool(boolean)
+    } else {
+        Value::String(raw.to_string())
+    }
+}
+
+fn shape(schema: Option<&Schema>) -> Shape {
+    match schema.map(|schema| &schema.schema_kind) {
+        Some(SchemaKind::Type(Type::Array(_))) => Shape::Array,
+        Some(SchemaKind::Type(Type::Object(_))) => Shape::Object,
+        Some(SchemaKind::Any(any)) => match any.typ.as_deref() {
+            Some("array") => Shape::Array,
+            Some("object") => Shape::Object,
+            _ if any.items.is_some() => Shape::Array,
+            _ if !any.properties.is_empty() => Shape::Object,
+            _ => Shape::Primitive,
+        },
+        _ => Shape::Primitive,
+    }
+}
+
+fn is_boolean(schema: Option<&Schema>) -> bool {
+    match schema.map(|schema| &schema.schema_kind) {
+        Some(SchemaKind::Type(Type::Boolean(_))) => true,
+        Some(SchemaKind::Any(any)) => any.typ.as_deref() == Some("boolean"),
+        _ => false,
+    }
+}
+
+fn items_schema(schema: Option<&Schema>) -> Option<&Schema> {
+    let items = match &schema?.schema_kind {
+        SchemaKind::Type(Type::Array(array)) => array.items.as_ref(),
+        SchemaKind::Any(any) => any.items.as_ref(),
+        _ => None,
+    };
+    items
+        .and_then(|items| items.as_item())
+        .map(|items| &**items)
+}
+
+fn property_schema<'a>(schema: Option<&'a Schema>, name: &str) -> Option<&'a Schema> {
+    let (properties, additional) = match &schema?.schema_kind {
+        SchemaKind::Type(Type::Object(object)) => {
+            (&object.properties, object.additional_properties.as_ref())
+        }
+        SchemaKind::Any(any) => (&any.properties, any.additional_properties.as_ref()),
+        _ => return None,
+    };
+    match properties.get(name) {
+        Some(property) => property.as_item().map(|property| &**property),
+        None => match additional {
+            Some(AdditionalProperties::Schema(schema)) => schema.as_item(),
+            _ => None,
+        },
+    }
+}
+
+/// True if the value has characters which should have been percent-encoded.
+fn has_reserved(raw_value: &str) -> bool {
+    raw_value.chars().any(|c| RESERVED.contains(&c))
+}
+
+/// `filter[name]` is the property `name` of the `filter` deep object.
+fn deep_object_property<'a>(name: &str, variable_name: &'a str) -> Option<&'a str> {
+    variable_name
+        .strip_prefix(name)?
+        .strip_prefix('[')?
+        .strip_suffix(']')
+}
+
+/// Returns the value as it was received, before decoding.
+fn find<'a>(variables: &'a Params, name: &str, case_insensitive: bool) -> Option<&'a str> {
+    variables
+        .iter()
+        .find(|variable| {
+            if case_insensitive {
+                variable.name.eq_ignore_ascii_case(name)
+            } else {
+                variable.name == name
+            }
+        })
+        .map(|variable| variable.raw_value.as_str())
+}
+
+fn style_error(name: &str, style: &str) -> E {
+    E::StyleError {
+        param_name: name.to_string(),
+        style: style.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Attribute;
+    use serde_json::json;
+
+    fn query_parameter(parameter: Value) -> Parameter {
+        serde_json::from_value(parameter).unwrap()
+    }
+
+    /// The request parts of a query string which is already split.
+    fn query(pairs: &[(&str, &str)]) -> RequestParts {
+        RequestParts {
+            path_variables: Vec::new(),
+            query_variables: pairs.iter().map(|(name, value)| Attribute::new(name, value)).collect(),
+            header_variables: Vec::new(),
+            cookie_variables: Vec::new(),
+            body: None,
+        }
+    }
+
+    #[test]
+    fn reserved_characters_allowed_in_queries_are_accepted() {
+        let parameter = query_parameter(json!({
+            "name": "since", "in": "query", "schema": {"type": "string"}
+        }));
+        let value = parameter_value(&parameter, &query(&[("since", "2020-01-01T10:00:00Z")]));
+        assert_eq!(value.unwrap(), Some(json!("2020-01-01T10:00:00Z")));
+
+        let value = parameter_value(&parameter, &query(&[("since", "/a/b?c@d")]));
+        assert_eq!(value.unwrap(), Some(json!("/a/b?c@d")));
+    }
+
+    #[test]
+    fn unencoded_equals_signs_are_rejected() {
+        let parameter = query_parameter(json!({
+            "name": "filter", "in": "query", "schema": {"type": "string"}
+        }));
+        let value = parameter_value(&parameter, &query(&[("filter", "a=b")]));
+        assert!(matches!(value, Err(E::ReservedError(name)) if name == "filter"));
+    }
+
+    #[test]
+    fn allow_reserved_accepts_equals_signs() {
+        let parameter = query_parameter(json!({
+            "name": "filter", "in": "query", "allowReserved": true, "schema": {"type": "string"}
+        }));
+        let value = parameter_value(&parameter, &query(&[("filter", "a=b")]));
+        assert_eq!(value.unwrap(), Some(json!("a=b")));
+    }
+
+    #[test]
+    fn flags_turn_booleans_on() {
+        let parameter = query_parameter(json!({
+            "name": "verbose", "in": "query", "schema": {"type": "boolean"}
+        }));
+        let value = parameter_value(&parameter, &query(&[("verbose", "")]));
+        assert_eq!(value.unwrap(), Some(json!(true)));
+
+        let value = parameter_value(&parameter, &query(&[("verbose", "false")]));
+        assert_eq!(value.unwrap(), Some(json!(false)));
+    }
+
+    #[test]
+    fn empty_values_of_other_types_are_rejected() {
+        let parameter = query_parameter(json!({
+            "name": "limit", "in": "query", "schema": {"type": "integer"}
+        }));
+        let value = parameter_value(&parameter, &query(&[("limit", "")]));
+        assert!(matches!(value, Err(E::EmptyValueError(name)) if name == "limit"));
+    }
+
+    #[test]
+    fn commas_split_lists() {
+        let parameter = query_parameter(json!({
+            "name": "ids", "in": "query", "explode": false,
+            "schema": {"type": "array", "items": {"type": "integer"}}
+        }));
+        let value = parameter_value(&parameter, &query(&[("ids", "1,2,3")]));
+        assert_eq!(value.unwrap(), Some(json!([1, 2, 3])));
+    }
+
+    fn schema(schema: Value) -> Schema {
+        serde_json::from_value(schema).unwrap()
+    }
+
+    fn path(name: &str, value: &str) -> RequestParts {
+        RequestParts {
+            path_variables: vec![Attribute::new(name, value)],
+            ..query(&[])
+        }
+    }
+
+    #[test]
+    fn values_are_coerced_to_the_type_of_the_schema() {
+        assert_eq!(coerce(Some(&schema(json!({"type": "integer"}))), "42"), json!(42));
+        assert_eq!(coerce(Some(&schema(json!({"type": "number"}))), "4.5"), json!(4.5));
+        assert_eq!(coerce(Some(&schema(json!({"type": "boolean"}))), "true"), json!(true));
+        assert_eq!(coerce(Some(&schema(json!({"type": "string"}))), "007"), json!("007"));
+    }
+
+    #[test]
+    fn values_which_can_not_be_coerced_stay_strings() {
+        assert_eq!(coerce(Some(&schema(json!({"type": "integer"}))), "4.5"), json!("4.5"));
+        assert_eq!(coerce(Some(&schema(json!({"type": "boolean"}))), "yes"), json!("yes"));
+    }
+
+    #[test]
+    fn values_without_a_type_are_guessed() {
+        assert_eq!(coerce(None, "42"), json!(42));
+        assert_eq!(coerce(None, "-1.5"), json!(-1.5));
+        assert_eq!(coerce(None, "false"), json!(false));
+        assert_eq!(coerce(None, "rex"), json!("rex"));
+    }
+
+    #[test]
+    fn exploded_queries_repeat_the_name() {
+        let parameter = query_parameter(json!({
+            "name": "ids", "in": "query", "schema": {"type": "array", "items": {"type": "integer"}}
+        }));
+        let value = parameter_value(&parameter, &query(&[("ids", "1"), ("ids", "2")]));
+        assert_eq!(value.unwrap(), Some(json!([1, 2])));
+    }
+
+    #[test]
+    fn deep_objects_use_brackets() {
+        let parameter = query_parameter(json!({
+            "name": "filter", "in": "query", "style": "deepObject", "explode": true,
+            "schema": {"type": "object", "properties": {"age": {"type": "integer"}}}
+        }));
+        let request = query(&[("filter[name]", "rex"), ("filter[age]", "3")]);
+        let value = parameter_value(&parameter, &request);
+        assert_eq!(value.unwrap(), Some(json!({"name": "rex", "age": 3})));
+    }
+
+    #[test]
+    fn path_styles_are_deserialized() {
+        let label = query_parameter(json!({
+            "name": "ids", "in": "path", "required": true, "style": "label",
+            "schema": {"type": "array", "items": {"type": "integer"}}
+        }));
+        assert_eq!(parameter_value(&label, &path("ids", ".1,2")).unwrap(), Some(json!([1, 2])));
+
+        let matrix = query_parameter(json!({
+            "name": "id", "in": "path", "required": true, "style": "matrix",
+            "schema": {"type": "integer"}
+        }));
+        assert_eq!(parameter_value(&matrix, &path("id", ";id=5")).unwrap(), Some(json!(5)));
+        let value = parameter_value(&matrix, &path("id", "5"));
+        assert!(matches!(value, Err(E::StyleError { .. })));
+    }
+
+    #[test]
+    fn header_objects_are_lists_of_keys_and_values() {
+        let header: Header = serde_json::from_value(json!({
+            "schema": {"type": "object", "properties": {"limit": {"type": "integer"}}}
+        }))
+        .unwrap();
+        assert_eq!(header_value(&header, "limit,10,unit,s"), json!({"limit": 10, "unit": "s"}));
+    }
+}
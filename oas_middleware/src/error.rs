@@ -1,6 +1,10 @@
-use crate::request::Attribute;
+use crate::schema_validator::Violation;
+use std::fmt;
+use std::path::Path;
 use thiserror::Error;
 
+use openapi_utils::DerefError;
+
 #[derive(Error, Debug)]
 pub enum E {
     #[error("The path `{0}` is not described in the OpenAPI file.")]
@@ -9,58 +13,244 @@ pub enum E {
     #[error("The method `{0}` is not described in the OpenAPI file.")]
     MethodError(String),
 
+    #[error("The status code `{0}` is not described in the OpenAPI file.")]
+    StatusCodeError(u16),
+
     #[error("The parameter `{0}` is not described in the OpenAPI file.")]
     ParamError(String),
 
-    #[error("The contract specifies `{param_name}` as {type_name} but got `{param_value}`.")]
-    TypeError {
-        type_name: String,
+    #[error("The contract requires a request body but none was sent.")]
+    BodyMissing,
+
+    #[error("The media type `{0}` is not described in the OpenAPI file.")]
+    MediaTypeError(String),
+
+    #[error("The body could not be parsed as `{media_type}`: {reason}.")]
+    BodyParseError { media_type: String, reason: String },
+
+    #[error("The body does not agree with its schema: {}.", join_violations(.0))]
+    SchemaError(Vec<Violation>),
+
+    #[error("The parameter `{param_name}` does not agree with its schema: {}.", join_violations(.violations))]
+    ParamSchemaError {
         param_name: String,
-        param_value: String,
+        violations: Vec<Violation>,
     },
 
-    #[error("Type {0} not supported by the proxy. Fix me! ")]
-    TypeNotsupported(String),
+    #[error("The parameter `{param_name}` is not serialized with the `{style}` style.")]
+    StyleError { param_name: String, style: String },
 
-    #[error("{0}. Fix me! ")]
-    FunctionalityNotsupported(String),
+    #[error("The parameter `{0}` has an empty value but the contract does not allow empty values.")]
+    EmptyValueError(String),
 
-    #[error("The contract specifies `{param_name}` to have a {limit_name} of {limit_value} but got {param_value}.")]
-    ValueLimit {
-        param_name: String,
-        limit_name: String,
-        limit_value: String,
-        param_value: String,
-    }, // #[error("unknown data store error")]
-       // Unknown
+    #[error("The parameter `{0}` has reserved characters which are not percent-encoded.")]
+    ReservedError(String),
+
+    #[error("The contract specifies `{0}` as required but it is missing.")]
+    RequiredError(String),
+
+    #[error("The contract can't be used: {0}.")]
+    ReferenceError(DerefError),
+    // #[error("unknown data store error")]
+    // Unknown
+}
+
+impl E {
+    /// Stable identifier of the kind of error, used as the problem `type`.
+    pub fn type_uri(&self) -> &'static str {
+        match self {
+            E::PathError(_) => "urn:oas-proxy:path-not-described",
+            E::MethodError(_) => "urn:oas-proxy:method-not-described",
+            E::StatusCodeError(_) => "urn:oas-proxy:status-not-described",
+            E::ParamError(_) => "urn:oas-proxy:parameter-not-described",
+            E::BodyMissing => "urn:oas-proxy:body-missing",
+            E::MediaTypeError(_) => "urn:oas-proxy:media-type-not-described",
+            E::BodyParseError { .. } => "urn:oas-proxy:body-not-parseable",
+            E::SchemaError(_) => "urn:oas-proxy:body-schema",
+            E::ParamSchemaError { .. } => "urn:oas-proxy:parameter-schema",
+            E::StyleError { .. } => "urn:oas-proxy:parameter-style",
+            E::EmptyValueError(_) => "urn:oas-proxy:parameter-empty",
+            E::ReservedError(_) => "urn:oas-proxy:parameter-reserved",
+            E::RequiredError(_) => "urn:oas-proxy:required-missing",
+            E::ReferenceError(_) => "urn:oas-proxy:reference-not-resolved",
+        }
+    }
+
+    /// The schema violations behind the error, if any.
+    pub fn violations(&self) -> &[Violation] {
+        match self {
+            E::SchemaError(violations) | E::ParamSchemaError { violations, .. } => violations,
+            _ => &[],
+        }
+    }
+}
+
+fn join_violations(violations: &[Violation]) -> String {
+    let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    messages.join("; ")
+}
+
+/// A failure found in one location of a request or a response.
+#[derive(Debug)]
+pub struct Failure {
+    /// `path`, `query`, `header`, `cookie`, `body` or `status`.
+    pub location: String,
+    /// The parameter or header which failed, None for the body and the status.
+    pub name: Option<String>,
+    pub error: E,
+}
+
+impl Failure {
+    pub fn new(location: &str, name: Option<&str>, error: E) -> Self {
+        Failure {
+            location: location.to_string(),
+            name: name.map(|name| name.to_string()),
+            error,
+        }
+    }
+
+    /// Short description for headers, `query:limit urn:oas-proxy:parameter-schema`.
+    pub fn summary(&self) -> String {
+        let place = match &self.name {
+            Some(name) => format!("{}:{}", self.location, name),
+            None => self.location.clone(),
+        };
+        format!("{} {}", place, self.error.type_uri())
+            .chars()
+            .filter(|c| c.is_ascii_graphic() || *c == ' ')
+            .collect()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let noun = match self.location.as_str() {
+            "path" => "path variable",
+            "query" => "query parameter",
+            "status" => "status code",
+            location => location,
+        };
+        match &self.name {
+            Some(name) => write!(f, "Failure in the {} `{}`: {}", noun, name, self.error),
+            None => write!(f, "Failure in the {}: {}", noun, self.error),
+        }
+    }
+}
+
+/// All the failures found validating a request or a response.
+#[derive(Error, Debug)]
+#[error("Found {} failures.", .0.len())]
+pub struct Failures(pub Vec<Failure>);
+
+impl Failures {
+    /// Ok when nothing failed.
+    pub fn check(failures: Vec<Failure>) -> Result<(), Failures> {
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Failures(failures))
+        }
+    }
+}
+
+/// Formats an OpenAPI document can be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
 }
 
-pub fn unsupported(text: &str) -> E {
-    E::FunctionalityNotsupported(text.to_string())
+impl Format {
+    /// Uses the extension of the file, or the first character of the content when the
+    /// extension is not known.
+    pub fn detect(filename: &Path, data: &str) -> Format {
+        match filename.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ if data.trim_start().starts_with('{') => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
 }
 
-pub fn type_error(type_name: &str, param: &Attribute) -> E {
-    E::TypeError {
-        type_name: type_name.to_string(),
-        param_name: param.name.to_string(),
-        param_value: param.value.to_string(),
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Json => write!(f, "JSON"),
+            Format::Yaml => write!(f, "YAML"),
+        }
     }
 }
 
-pub fn minimum_error(limit_value: &str, param: &Attribute) -> E {
-    E::ValueLimit {
-        limit_name: "minimum".to_string(),
-        limit_value: limit_value.to_string(),
-        param_name: param.name.to_string(),
-        param_value: param.value.to_string(),
+/// Problems loading an OpenAPI document.
+#[derive(Error, Debug)]
+pub enum SpecError {
+    #[error("The OpenAPI file `{filename}` could not be read: {source}.")]
+    Io {
+        filename: String,
+        source: std::io::Error,
+    },
+
+    #[error("The OpenAPI description `{filename}` has references which can't be resolved:{}", list(.errors))]
+    Reference {
+        filename: String,
+        errors: Vec<DerefError>,
+    },
+
+    #[error("The settings for the operations {} don't match any operationId of the OpenAPI files.", .operation_ids.join(", "))]
+    UnknownOperations { operation_ids: Vec<String> },
+
+    #[error("The OpenAPI document `{url}` could not be downloaded: {reason}.")]
+    Download { url: String, reason: String },
+
+    #[error("The OpenAPI {format} in `{filename}` could not be read at {}`{path}`: {reason}.", position(*.line, *.column))]
+    Parse {
+        filename: String,
+        format: Format,
+        line: Option<usize>,
+        column: Option<usize>,
+        /// Path to the element with the problem, `paths./users.get.responses`.
+        path: String,
+        reason: String,
+    },
+}
+
+impl SpecError {
+    pub fn parse(
+        format: Format,
+        position: Option<(usize, usize)>,
+        path: &serde_path_to_error::Path,
+        reason: &dyn std::error::Error,
+    ) -> Self {
+        SpecError::Parse {
+            filename: String::new(),
+            format,
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Adds the name of the file the document was read from.
+    pub fn in_file(mut self, name: &Path) -> Self {
+        if let SpecError::Parse { filename, .. } = &mut self {
+            *filename = name.display().to_string();
+        }
+        self
     }
 }
 
-pub fn maximum_error(limit_value: &str, param: &Attribute) -> E {
-    E::ValueLimit {
-        limit_name: "maximum".to_string(),
-        limit_value: limit_value.to_string(),
-        param_name: param.name.to_string(),
-        param_value: param.value.to_string(),
+fn list(errors: &[DerefError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("\n  - {}.", error))
+        .collect()
+}
+
+fn position(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!("line {}, column {}, ", line, column),
+        _ => String::new(),
     }
 }
@@ -1,11 +1,19 @@
-mod check_type;
+mod deserializer;
 mod error;
 mod middleware;
+mod mock;
+mod options;
 mod parts;
 mod path_finder;
+mod problem;
+mod reload;
 mod request;
+mod response;
+mod schema_validator;
 mod spec_utils;
 mod usage_report;
 mod validator;
 
+pub use error::SpecError;
 pub use middleware::OASMiddleware;
+pub use options::{Api, OperationOptions, Options};
@@ -1,35 +1,244 @@
-use hyper::header::HeaderValue;
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::{HeaderValue, ALLOW, AUTHORIZATION, CONTENT_TYPE, HOST, WWW_AUTHENTICATE};
+use hyper::{Body, Method, Request, Response, StatusCode};
 
 use simple_proxy::proxy::error::MiddlewareError;
 use simple_proxy::proxy::middleware::MiddlewareResult::{Next, RespondWith};
 use simple_proxy::proxy::middleware::{Middleware, MiddlewareResult};
-use simple_proxy::proxy::service::{ServiceContext, State};
+use simple_proxy::proxy::service::{BodyTooLarge, ServiceContext, State};
 
 use anyhow::Error;
 use http::uri::Uri;
-use log::{debug, info};
-use serde_json::json;
-use std::path::Path;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use openapi_utils::SpecExt;
+use openapi_utils::OperationExt;
+use openapiv3::Components;
 
+use crate::error::{Failure, Failures, SpecError, E};
+use crate::mock;
+use crate::options::{Api, Options};
 use crate::path_finder::PathFinder;
+use crate::problem::{Problem, PROBLEM_JSON};
+use crate::reload::{self, Watcher};
 use crate::request;
+use crate::response;
 use crate::spec_utils;
-use crate::usage_report;
+use crate::usage_report::{self, ViolationCounts};
 use crate::validator;
 
 pub struct OASMiddleware {
+    /// Sorted by the length of their prefix, the longest prefix that matches wins.
+    apis: Vec<LoadedApi>,
+    options: Options,
+    violations: ViolationCounts,
+}
+
+struct LoadedApi {
+    api: Api,
+    spec: Spec,
+    /// Reloads the spec, see `Options::watch` and `Options::reload_path`.
+    watcher: Option<Mutex<Watcher>>,
+}
+
+/// The parts of the OpenAPI file the middleware works with.
+pub(crate) struct Spec {
     path_finder: PathFinder,
+    components: Components,
+    /// The files the spec was read from, a reload is needed when one of them changes.
+    pub(crate) files: Vec<PathBuf>,
 }
-impl OASMiddleware {
-    pub fn new<P: AsRef<Path>>(filename: P) -> Self {
-        let spec = spec_utils::read(filename).deref_all();
-        let path_finder = PathFinder::new(spec);
+
+impl Spec {
+    pub(crate) fn load(api: &Api, match_host: bool) -> Result<Self, SpecError> {
+        let (mut spec, uris) = spec_utils::read(&api.spec)?;
+        let components = spec.components.take().unwrap_or_default();
+        let path_finder = PathFinder::new(spec, &api.prefix, match_host);
         debug!("{:?}", path_finder);
+        let files = uris
+            .into_iter()
+            .filter(|uri| !spec_utils::is_url(uri))
+            .map(PathBuf::from)
+            .collect();
+        Ok(Spec {
+            path_finder,
+            components,
+            files,
+        })
+    }
+}
+
+impl OASMiddleware {
+    pub fn new<P: AsRef<Path>>(filename: P, options: Options) -> Result<Self, SpecError> {
+        Self::with_apis(vec![Api::new(filename.as_ref())], options)
+    }
+
+    /// Validates the requests to several APIs, each one is proxied to its backend.
+    pub fn with_apis(apis: Vec<Api>, options: Options) -> Result<Self, SpecError> {
+        let mut loaded = Vec::new();
+        for api in apis {
+            let spec = Spec::load(&api, options.match_host)?;
+            let watcher = if options.watch || options.reloads_on_request() {
+                Some(Mutex::new(reload::watch(&api, &spec.files, options.match_host, options.watch)))
+            } else {
+                None
+            };
+            if let (true, Some(reload_path)) = (options.reloads_on_request(), &options.reload_path) {
+                if spec.path_finder.matches(reload_path, None) {
+                    warn!(
+                        "The reload path {} is described in {}, POST requests to it reload the specs",
+                        reload_path,
+                        api.spec.display()
+                    );
+                }
+            }
+            loaded.push(LoadedApi { api, spec, watcher });
+        }
+        loaded.sort_by_key(|loaded| std::cmp::Reverse(loaded.api.prefix.len()));
+        if options.reload_path.is_some() && options.reload_token.is_none() {
+            warn!("The reload path needs a reload token, requests to it don't reload the specs");
+        }
+
+        let operation_ids: Vec<&str> = loaded
+            .iter()
+            .flat_map(|loaded| &loaded.spec.path_finder.items)
+            .flat_map(spec_utils::operation_list)
+            .filter_map(|(_, operation)| operation.operation_id.as_deref())
+            .collect();
+        let mut unknown: Vec<String> = options
+            .operations
+            .keys()
+            .filter(|operation_id| !operation_ids.contains(&operation_id.as_str()))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(SpecError::UnknownOperations {
+                operation_ids: unknown,
+            });
+        }
+
+        Ok(OASMiddleware {
+            apis: loaded,
+            options,
+            violations: ViolationCounts::default(),
+        })
+    }
+
+    /// Swaps in the latest specs loaded by the watcher, between requests.
+    fn apply_reloads(&mut self) {
+        for loaded in &mut self.apis {
+            let latest = match &loaded.watcher {
+                Some(watcher) => watcher.lock().ok().and_then(|watcher| watcher.latest()),
+                None => None,
+            };
+            if let Some(spec) = latest {
+                info!("Using the reloaded spec {}", loaded.api.spec.display());
+                loaded.spec = spec;
+            }
+        }
+    }
+
+    /// True for the requests to reload the specs, see `Options::reload_path`.
+    fn is_reload(&self, req: &Request<Body>) -> bool {
+        req.method() == Method::POST
+            && self.options.reloads_on_request()
+            && self.options.reload_path.as_deref() == Some(req.uri().path())
+    }
+
+    /// Asks the watchers to reload the specs, they are swapped in once loaded.
+    fn reload(&self, req: &Request<Body>) -> Response<Body> {
+        let authorization = req.headers().get(AUTHORIZATION).map(HeaderValue::as_bytes);
+        let authorized = match (&self.options.reload_token, authorization) {
+            (Some(token), Some(authorization)) => {
+                same_secret(authorization, format!("Bearer {}", token).as_bytes())
+            }
+            _ => false,
+        };
+        if !authorized {
+            info!("Reload request without the reload token, not reloading");
+            let status = StatusCode::UNAUTHORIZED;
+            let problem = Problem::new(
+                "urn:oas-proxy:reload-not-authorized",
+                "The request does not have the reload token. Not reloading.",
+                status,
+                &Error::msg("The Authorization header must be `Bearer` and the reload token"),
+                req.uri(),
+            );
+            let mut response = Response::new(Body::from(problem.to_json()));
+            *response.status_mut() = status;
+            let headers = response.headers_mut();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+            headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return response;
+        }
+        for loaded in &self.apis {
+            if let Some(watcher) = loaded.watcher.as_ref().and_then(|watcher| watcher.lock().ok()) {
+                watcher.request();
+            }
+        }
+        let mut response = Response::new(Body::from("Reloading"));
+        *response.status_mut() = StatusCode::ACCEPTED;
+        response
+    }
+
+    /// Answers requests to paths, or methods of a path, which the contract does not
+    /// describe with 404 or 405, or proxies them in observe mode to the backend of the
+    /// API whose prefix they start with. Requests which start with no prefix are never
+    /// proxied.
+    fn not_described(
+        &mut self,
+        req: &mut Request<Body>,
+        error: E,
+        api: Option<usize>,
+        allow: Option<String>,
+    ) -> Result<MiddlewareResult, MiddlewareError> {
+        let (location, status) = match allow {
+            Some(_) => ("method", StatusCode::METHOD_NOT_ALLOWED),
+            None => ("path", StatusCode::NOT_FOUND),
+        };
+        if let (true, Some(api)) = (self.options.observe, api) {
+            warn!("{} Proxying anyway", error);
+            self.violations
+                .record_request(&Failures(vec![Failure::new(location, None, error)]));
+            forward(req, &self.apis[api].api)?;
+            return Ok(Next);
+        }
+
+        info!("{} Not proxying", error);
+        let problem = Problem::new(
+            error.type_uri(),
+            "The request is not described in the API contract. Not proxying.",
+            status,
+            &Error::new(error),
+            req.uri(),
+        );
+        let mut response = Response::new(Body::from(problem.to_json()));
+        *response.status_mut() = status;
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        if let Some(allow) = allow {
+            headers.insert(ALLOW, HeaderValue::from_str(&allow)?);
+        }
+        Ok(RespondWith(response))
+    }
 
-        OASMiddleware { path_finder }
+    /// The API whose prefix the path starts with, the longest one. None when the path
+    /// starts with none of them, which only happens when every API has a prefix.
+    fn api_by_prefix(&self, path: &str) -> Option<usize> {
+        self.apis.iter().position(|loaded| {
+            let prefix = &loaded.api.prefix;
+            path.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// The API with a path matching the request.
+    fn api_index(&self, path: &str, host: Option<&str>) -> Option<usize> {
+        self.apis
+            .iter()
+            .position(|loaded| loaded.spec.path_finder.matches(path, host))
     }
 }
 
@@ -38,16 +247,72 @@ impl Middleware for OASMiddleware {
         String::from("OpenAPI Validator Middleware")
     }
 
+    /// Only the bodies of operations which validate them are buffered, the rest are streamed.
+    fn reads_request_body(&mut self, req: &Request<Body>, _context: &ServiceContext, _state: &State) -> bool {
+        self.apply_reloads();
+        if self.is_reload(req) || self.options.is_ignored(req.uri().path()) {
+            return false;
+        }
+        let host = request_host(req);
+        let index = match self.api_index(req.uri().path(), host.as_deref()) {
+            Some(index) => index,
+            None => return false,
+        };
+        let mut path = match self.apis[index].spec.path_finder.find(req.uri().path(), host.as_deref()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        match path.operation(req.method()) {
+            Ok(operation) => {
+                operation.request_body.is_some()
+                    && self.options.for_operation(operation.operation_id.as_deref()).validate
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Only responses to proxied requests which the contract describes with content are buffered.
+    fn reads_response_body(&mut self, res: &Response<Body>, context: &ServiceContext, state: &State) -> bool {
+        let matched = match self.get_state(context.req_id, state) {
+            Ok(Some(matched)) => matched,
+            _ => return false,
+        };
+        let matched = match serde_json::from_str::<MatchedRequest>(&matched) {
+            Ok(matched) => matched,
+            Err(_) => return false,
+        };
+        let (uri, method) = match (matched.uri.parse::<Uri>(), matched.method.parse::<Method>()) {
+            (Ok(uri), Ok(method)) => (uri, method),
+            _ => return false,
+        };
+        let mut path = match self.apis[matched.api].spec.path_finder.find(uri.path(), matched.host.as_deref()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        path.operation(&method)
+            .ok()
+            .and_then(|operation| operation.response(res.status().as_u16()))
+            .is_some_and(|response| !response.content.is_empty())
+    }
+
     fn before_request(
         &mut self,
         req: &mut Request<Body>,
-        _context: &ServiceContext,
-        _state: &State,
+        context: &ServiceContext,
+        state: &State,
     ) -> Result<MiddlewareResult, MiddlewareError> {
         info!("New request to {}", req.uri());
+        let error_status = self.options.error_status;
+        self.apply_reloads();
+
+        if self.is_reload(req) {
+            return Ok(RespondWith(self.reload(req)));
+        }
 
         if req.uri().path() == "/report" {
-            let usage_report = usage_report::render_report(&self.path_finder);
+            let path_finders: Vec<&PathFinder> =
+                self.apis.iter().map(|loaded| &loaded.spec.path_finder).collect();
+            let usage_report = usage_report::render_report(&path_finders, &self.violations);
             let mut response: Response<Body> = Response::new(Body::from(usage_report));
             response.headers_mut().insert(
                 "Content-Type",
@@ -56,58 +321,563 @@ impl Middleware for OASMiddleware {
             return Ok(RespondWith(response));
         }
 
-        let path = self.path_finder.find(req.uri().path()).unwrap(); //TODO: anyhow error here
-                                                                     //   .map_err(|error| middleware_error(error, req.uri()))?;
+        if self.options.is_ignored(req.uri().path()) {
+            info!("Ignored path, proxying without checks");
+            return Ok(Next);
+        }
 
-        let request_parts = request::RequestParts::new(&path.regex, &req);
-        let mut openapi_parts = crate::parts::OpenAPIParts::new(&mut path.path, &req)
-            .map_err(|error| middleware_error(error, req.uri()))?;
+        let host = request_host(req);
+        let index = match self.api_index(req.uri().path(), host.as_deref()) {
+            Some(index) => index,
+            None => {
+                let api = self.api_by_prefix(req.uri().path());
+                if let (true, Some(api)) = (self.options.proxy_undocumented, api) {
+                    info!("The path is not described in the contract, proxying without checks");
+                    forward(req, &self.apis[api].api)?;
+                    return Ok(Next);
+                }
+                let error = E::PathError(req.uri().path().to_string());
+                return self.not_described(req, error, api, None);
+            }
+        };
+        let loaded = &mut self.apis[index];
+        let mut path = loaded
+            .spec
+            .path_finder
+            .find(req.uri().path(), host.as_deref())
+            .expect("The API was chosen because one of its paths matches.");
+        let allow = path.allowed_methods();
+        let regex = path.regex;
+        let variables = path.variables;
+        let operation = match path.operation(req.method()) {
+            Ok(operation) => operation,
+            Err(error) => return self.not_described(req, error, Some(index), Some(allow)),
+        };
+
+        let request_parts = request::RequestParts::new(regex, variables, req);
+        let mut openapi_parts = crate::parts::OpenAPIParts::new(operation, &loaded.spec.components);
 
         //let (openapi_parts, request_parts) = parts::get_parts(&req).map_err(|error| middleware_error(error, req.uri()))?;
 
-        match validator::validate(&mut openapi_parts, &request_parts) {
-            Ok(()) => {
-                info!("Proxying");
-                let headers = req.headers_mut();
-                headers.insert("OAS-Proxied", HeaderValue::from_str("true").unwrap());
-                Ok(Next)
+        let options = self
+            .options
+            .for_operation(openapi_parts.operation.operation_id.as_deref());
+        if !options.validate {
+            info!("Validation disabled for the operation, proxying without checks");
+            forward(req, &loaded.api)?;
+            return Ok(Next);
+        }
+
+        let violations = match validator::validate(&mut openapi_parts, &request_parts, &options) {
+            Ok(()) => Vec::new(),
+            Err(failures) if options.observe => {
+                warn!("The request to {} does not agree with the contract, proxying anyway", req.uri());
+                warn!("{:?}", failures);
+                self.violations.record_request(&failures);
+                failures.0.iter().map(|failure| failure.summary()).collect()
             }
-            Err(error) => {
-                let e = error.context("Failed validation of request variables.");
-                Err(middleware_error(e, req.uri()))
+            Err(failures) => {
+                let e = Error::new(failures).context("Failed validation of request variables.");
+                return Err(middleware_error(e, req.uri(), error_status));
             }
+        };
+
+        if options.mock {
+            info!("Mocking");
+            let prefer = req
+                .headers()
+                .get("Prefer")
+                .and_then(|value| value.to_str().ok());
+            let response = mock::mock_response(openapi_parts.operation, &loaded.spec.components, prefer)
+                .map_err(|error| middleware_error(error.into(), req.uri(), error_status))?;
+            return Ok(RespondWith(response));
         }
+
+        info!("Proxying");
+        let matched = MatchedRequest {
+            api: index,
+            host,
+            method: req.method().to_string(),
+            uri: req.uri().to_string(),
+            violations,
+        };
+        self.set_state(context.req_id, state, serde_json::to_string(&matched)?)?;
+        forward(req, &self.apis[index].api)?;
+        let headers = req.headers_mut();
+        headers.insert("OAS-Proxied", HeaderValue::from_str("true").unwrap());
+        Ok(Next)
     }
 
     fn after_request(
         &mut self,
-        _res: Option<&mut Response<Body>>,
-        _context: &ServiceContext,
-        _state: &State,
+        res: Option<&mut Response<Body>>,
+        context: &ServiceContext,
+        state: &State,
     ) -> Result<MiddlewareResult, MiddlewareError> {
+        // Only responses to requests we validated and proxied are checked.
+        let (res, matched) = match (res, self.get_state(context.req_id, state)?) {
+            (Some(res), Some(matched)) => (res, serde_json::from_str::<MatchedRequest>(&matched)?),
+            _ => return Ok(Next),
+        };
+        if res.extensions().get::<BodyTooLarge>().is_some() {
+            warn!("The response to {} is too large to be checked", matched.uri);
+            return Ok(Next);
+        }
+        let uri: Uri = matched.uri.parse()?;
+        let error_status = self.options.error_status;
+        let method: Method = matched.method.parse()?;
+
+        let loaded = &mut self.apis[matched.api];
+        let mut path = loaded
+            .spec
+            .path_finder
+            .find(uri.path(), matched.host.as_deref())
+            .map_err(|error| middleware_error(error.into(), &uri, error_status))?;
+        let operation = path
+            .operation(&method)
+            .map_err(|error| middleware_error(error.into(), &uri, error_status))?;
+
+        let options = self.options.for_operation(operation.operation_id.as_deref());
+
+        let blocks = !options.observe
+            && self
+                .options
+                .blocks_responses(&loaded.api, operation.operation_id.as_deref());
+
+        let response_parts = response::ResponseParts::new(res);
+        let mut violations = matched.violations;
+        match validator::validate_response(operation, &loaded.spec.components, &response_parts) {
+            Ok(()) => {}
+            Err(failures) if !blocks => {
+                warn!("The response to {} does not agree with the contract", uri);
+                warn!("{:?}", failures);
+                self.violations.record_response(&failures);
+                violations.extend(failures.0.iter().map(|failure| failure.summary()));
+            }
+            Err(failures) => {
+                let e = Error::new(failures).context("Failed validation of the response.");
+                return Err(response_error(e, &uri));
+            }
+        }
+
+        if !violations.is_empty() {
+            let value = HeaderValue::from_str(&violations.join(", "))?;
+            res.headers_mut().insert("OAS-Violations", value);
+        }
         Ok(Next)
     }
 }
 
-fn middleware_error(error: Error, uri: &Uri) -> MiddlewareError {
+/// What `before_request` remembers about a proxied request to check its response.
+#[derive(Serialize, Deserialize)]
+struct MatchedRequest {
+    /// Index of the API in `OASMiddleware::apis`.
+    api: usize,
+    /// The Host header, the URI has the authority of the backend by now.
+    host: Option<String>,
+    method: String,
+    uri: String,
+    /// Failures of the request when running in observe mode.
+    violations: Vec<String>,
+}
+
+/// The Host header of the request.
+fn request_host(req: &Request<Body>) -> Option<String> {
+    req.headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(String::from)
+}
+
+/// Sends the request to the backend of its API, without the prefix of the API.
+fn forward(req: &mut Request<Body>, api: &Api) -> Result<(), MiddlewareError> {
+    let mut parts = req.uri().clone().into_parts();
+    if let Some(backend) = &api.backend {
+        parts.authority = Some(backend.clone());
+    }
+    if !api.prefix.is_empty() {
+        let path_and_query = parts.path_and_query.as_ref().map_or("/", |path| path.as_str());
+        let rest = &path_and_query[api.prefix.len()..];
+        let path_and_query = if rest.starts_with('/') {
+            rest.to_string()
+        } else {
+            format!("/{}", rest)
+        };
+        parts.path_and_query = Some(path_and_query.parse()?);
+    }
+    *req.uri_mut() = Uri::from_parts(parts)?;
+    Ok(())
+}
+
+fn middleware_error(error: Error, uri: &Uri, status: StatusCode) -> MiddlewareError {
     info!("Failed to validate. Not proxying");
     info!("{:?}", error);
+    let problem = Problem::new(
+        "urn:oas-proxy:request-contract-broken",
+        "The request does not agree with the API contract. Not proxying.",
+        status,
+        &error,
+        uri,
+    );
     MiddlewareError::new(
         String::from("Request not consistent with OpenAPI description."),
-        Some(error_to_json(error, uri)),
-        StatusCode::BAD_REQUEST,
+        Some(problem.to_json()),
+        status,
+    )
+    .with_content_type(PROBLEM_JSON)
+}
+
+fn response_error(error: Error, uri: &Uri) -> MiddlewareError {
+    warn!("The response from the backend does not agree with the contract");
+    warn!("{:?}", error);
+    let problem = Problem::new(
+        "urn:oas-proxy:response-contract-broken",
+        "The response does not agree with the API contract.",
+        StatusCode::BAD_GATEWAY,
+        &error,
+        uri,
+    );
+    MiddlewareError::new(
+        String::from("Response not consistent with OpenAPI description."),
+        Some(problem.to_json()),
+        StatusCode::BAD_GATEWAY,
     )
+    .with_content_type(PROBLEM_JSON)
+}
+
+/// Compares every byte, so the time it takes does not tell how much of a secret was right.
+fn same_secret(given: &[u8], secret: &[u8]) -> bool {
+    given.len() == secret.len()
+        && given.iter().zip(secret).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
 }
 
-fn error_to_json(error: Error, uri: &Uri) -> String {
-    let causes: Vec<String> = error.chain().map(|e| e.to_string()).collect();
-
-    json!({
-        "type": "errors:contract_broken",
-        "title": "The request does not agree with the API contract. Not proxying.",
-        "failed_url": uri.to_string(),
-        "causes": causes,
-        "status": 422,
-    })
-    .to_string()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OperationOptions;
+    use serde_json::json;
+
+    fn users() -> serde_json::Value {
+        json!({
+            "openapi": "3.0.0",
+            "info": {"title": "Test", "version": "1"},
+            "paths": {"/users": {
+                "post": {"responses": {"201": {"description": "Created"}}},
+                "get": {
+                    "operationId": "listUsers",
+                    "responses": {"200": {"description": "The users"}}
+                }
+            }}
+        })
+    }
+
+    /// Writes the spec in a temporary file named after the test.
+    fn spec_file(name: &str, spec: &serde_json::Value) -> PathBuf {
+        let file = std::env::temp_dir().join(format!("oas-proxy-{}-{}.json", std::process::id(), name));
+        std::fs::write(&file, spec.to_string()).unwrap();
+        file
+    }
+
+    /// Loads the APIs, then removes their spec files.
+    fn load(apis: Vec<Api>, options: Options) -> OASMiddleware {
+        let files: Vec<PathBuf> = apis.iter().map(|api| api.spec.clone()).collect();
+        let middleware = OASMiddleware::with_apis(apis, options).unwrap();
+        for file in files {
+            std::fs::remove_file(file).unwrap();
+        }
+        middleware
+    }
+
+    fn middleware(name: &str, options: Options) -> OASMiddleware {
+        load(vec![Api::new(spec_file(name, &users()))], options)
+    }
+
+    fn context() -> ServiceContext {
+        ServiceContext {
+            remote_addr: "127.0.0.1:3000".parse().unwrap(),
+            req_id: 1,
+        }
+    }
+
+    fn send(middleware: &mut OASMiddleware, method: Method, path: &str) -> MiddlewareResult {
+        let mut req = Request::builder().method(method).uri(path).body(Body::empty()).unwrap();
+        middleware.before_request(&mut req, &context(), &State::default()).unwrap()
+    }
+
+    /// The URI `GET uri` is proxied to.
+    fn forwarded(middleware: &mut OASMiddleware, uri: &str) -> Uri {
+        let mut req = Request::get(uri).body(Body::empty()).unwrap();
+        let result = middleware.before_request(&mut req, &context(), &State::default()).unwrap();
+        assert!(matches!(result, Next), "{} was not proxied", uri);
+        req.uri().clone()
+    }
+
+    /// Proxies `GET path` and checks `res` as the answer of the backend, returns what the
+    /// client gets.
+    fn answer(
+        middleware: &mut OASMiddleware,
+        path: &str,
+        mut res: Response<Body>,
+    ) -> Result<Response<Body>, MiddlewareError> {
+        let state = State::default();
+        let mut req = Request::get(path).body(Body::empty()).unwrap();
+        assert!(matches!(middleware.before_request(&mut req, &context(), &state)?, Next));
+        match middleware.after_request(Some(&mut res), &context(), &state)? {
+            RespondWith(response) => Ok(response),
+            Next => Ok(res),
+        }
+    }
+
+    /// A response the contract does not describe.
+    fn server_error() -> Response<Body> {
+        Response::builder().status(500).body(Body::empty()).unwrap()
+    }
+
+    fn response(result: MiddlewareResult) -> Response<Body> {
+        match result {
+            RespondWith(response) => response,
+            Next => panic!("The request was proxied"),
+        }
+    }
+
+    #[test]
+    fn undescribed_paths_are_not_found() {
+        let mut middleware = middleware("not-found", Options::default());
+        let response = response(send(&mut middleware, Method::GET, "/groups"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_JSON);
+        assert!(response.headers().get(ALLOW).is_none());
+    }
+
+    #[test]
+    fn undescribed_methods_are_not_allowed() {
+        let mut middleware = middleware("not-allowed", Options::default());
+        for method in [Method::DELETE, Method::TRACE, Method::CONNECT] {
+            let response = response(send(&mut middleware, method, "/users"));
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(response.headers()[ALLOW], "GET, POST");
+        }
+    }
+
+    #[test]
+    fn invalid_requests_are_answered_with_a_problem() {
+        let options = Options {
+            strict_query: true,
+            ..Options::default()
+        };
+        let mut middleware = middleware("problem", options);
+        let mut req = Request::get("/users?debug=true").body(Body::empty()).unwrap();
+        let error = match middleware.before_request(&mut req, &context(), &State::default()) {
+            Err(error) => error,
+            Ok(_) => panic!("The request was accepted"),
+        };
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.content_type, PROBLEM_JSON);
+        let problem: serde_json::Value = serde_json::from_str(&error.body).unwrap();
+        assert_eq!(problem["status"], 400);
+        assert_eq!(problem["errors"][0]["name"], "debug");
+    }
+
+    #[test]
+    fn undescribed_requests_are_proxied_in_observe_mode() {
+        let options = Options {
+            observe: true,
+            ..Options::default()
+        };
+        let mut middleware = middleware("observe", options);
+        assert!(matches!(send(&mut middleware, Method::GET, "/groups"), Next));
+        assert!(matches!(send(&mut middleware, Method::DELETE, "/users"), Next));
+    }
+
+    #[test]
+    fn responses_breaking_the_contract_are_proxied_and_listed() {
+        let mut middleware = middleware("drift", Options::default());
+        let response = answer(&mut middleware, "/users", server_error()).unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.headers()["OAS-Violations"],
+            "status urn:oas-proxy:status-not-described"
+        );
+        let violations = serde_json::to_value(&middleware.violations).unwrap();
+        assert_eq!(violations["responses"], 1);
+
+        let described = Response::new(Body::empty());
+        let response = answer(&mut middleware, "/users", described).unwrap();
+        assert!(response.headers().get("OAS-Violations").is_none());
+    }
+
+    #[test]
+    fn responses_breaking_the_contract_can_be_blocked() {
+        let options = Options {
+            block_responses: true,
+            ..Options::default()
+        };
+        let mut blocking = middleware("block", options.clone());
+        let error = answer(&mut blocking, "/users", server_error()).unwrap_err();
+        assert_eq!(error.status, StatusCode::BAD_GATEWAY);
+        assert_eq!(error.content_type, PROBLEM_JSON);
+
+        let observing = Options {
+            observe: true,
+            ..options
+        };
+        let mut observing = middleware("block-observe", observing);
+        assert!(answer(&mut observing, "/users", server_error()).is_ok());
+    }
+
+    #[test]
+    fn apis_and_operations_can_block_their_responses() {
+        let api = Api::new(spec_file("block-api", &users())).with_block_responses(true);
+        let mut blocking = load(vec![api], Options::default());
+        assert!(answer(&mut blocking, "/users", server_error()).is_err());
+
+        let mut options = Options::default();
+        let operation = OperationOptions {
+            block_responses: Some(false),
+            ..OperationOptions::default()
+        };
+        options.operations.insert(String::from("listUsers"), operation);
+        let api = Api::new(spec_file("block-operation", &users())).with_block_responses(true);
+        let mut proxying = load(vec![api], options);
+        assert!(answer(&mut proxying, "/users", server_error()).is_ok());
+    }
+
+    fn with_groups() -> serde_json::Value {
+        let mut spec = users();
+        spec["paths"]["/groups"] = json!({"get": {"responses": {"200": {"description": "The groups"}}}});
+        spec
+    }
+
+    fn reload(middleware: &mut OASMiddleware, authorization: Option<&str>) -> Response<Body> {
+        let mut req = Request::post("/admin/reload");
+        if let Some(authorization) = authorization {
+            req.header(AUTHORIZATION, authorization);
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        response(middleware.before_request(&mut req, &context(), &State::default()).unwrap())
+    }
+
+    /// True once the middleware proxies `GET path`, it waits for the watcher thread.
+    fn proxies_eventually(middleware: &mut OASMiddleware, path: &str) -> bool {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while std::time::Instant::now() < deadline {
+            if matches!(send(middleware, Method::GET, path), Next) {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        false
+    }
+
+    fn reloading() -> Options {
+        Options {
+            reload_path: Some(String::from("/admin/reload")),
+            reload_token: Some(String::from("secret")),
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn changed_specs_are_swapped_in() {
+        let file = spec_file("watch", &users());
+        let options = Options {
+            watch: true,
+            ..Options::default()
+        };
+        let mut middleware = OASMiddleware::new(&file, options).unwrap();
+        assert!(matches!(send(&mut middleware, Method::GET, "/groups"), RespondWith(_)));
+        // The watcher compares modification times, which can be coarse.
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        std::fs::write(&file, with_groups().to_string()).unwrap();
+        assert!(proxies_eventually(&mut middleware, "/groups"));
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn reload_requests_swap_the_spec_in() {
+        let file = spec_file("reload", &users());
+        let mut middleware = OASMiddleware::new(&file, reloading()).unwrap();
+        std::fs::write(&file, with_groups().to_string()).unwrap();
+        assert_eq!(reload(&mut middleware, Some("Bearer secret")).status(), StatusCode::ACCEPTED);
+        assert!(proxies_eventually(&mut middleware, "/groups"));
+        assert!(matches!(send(&mut middleware, Method::GET, "/users"), Next));
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn reload_requests_need_the_token() {
+        let mut middleware = middleware("reload-token", reloading());
+        for authorization in [None, Some("Bearer wrong"), Some("Bearer secrets"), Some("secret")] {
+            let response = reload(&mut middleware, authorization);
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+            assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_JSON);
+        }
+    }
+
+    #[test]
+    fn reload_requests_are_disabled_without_a_token() {
+        let options = Options {
+            reload_token: None,
+            ..reloading()
+        };
+        let mut middleware = middleware("reload-disabled", options);
+        assert!(middleware.apis[0].watcher.is_none());
+        let response = reload(&mut middleware, Some("Bearer "));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn secrets_are_compared_whole() {
+        assert!(same_secret(b"Bearer secret", b"Bearer secret"));
+        assert!(!same_secret(b"Bearer secreT", b"Bearer secret"));
+        assert!(!same_secret(b"Bearer secret", b"Bearer secrets"));
+        assert!(!same_secret(b"", b"Bearer secret"));
+    }
+
+    /// `/api` with the users, `/api/v2` with the users and the groups.
+    fn versions(name: &str, options: Options) -> OASMiddleware {
+        let mut v1 = users();
+        v1["paths"]["/v2/users"] = json!({"get": {"responses": {"200": {"description": "The users"}}}});
+        let apis = vec![
+            Api::new(spec_file(&format!("{}-v1", name), &v1))
+                .with_prefix("/api")
+                .with_backend("localhost:3001".parse().unwrap()),
+            Api::new(spec_file(&format!("{}-v2", name), &with_groups()))
+                .with_prefix("/api/v2/")
+                .with_backend("localhost:3002".parse().unwrap()),
+        ];
+        load(apis, options)
+    }
+
+    #[test]
+    fn requests_go_to_the_api_with_the_longest_prefix() {
+        let mut middleware = versions("longest-prefix", Options::default());
+        let uri = forwarded(&mut middleware, "http://localhost:3000/api/v2/users");
+        assert_eq!(uri, "http://localhost:3002/users");
+        let uri = forwarded(&mut middleware, "http://localhost:3000/api/users?limit=1");
+        assert_eq!(uri, "http://localhost:3001/users?limit=1");
+    }
+
+    #[test]
+    fn prefixes_are_not_part_of_the_paths_of_the_spec() {
+        let mut middleware = versions("stripped-prefix", Options::default());
+        let uri = forwarded(&mut middleware, "http://localhost:3000/api/v2/groups");
+        assert_eq!(uri, "http://localhost:3002/groups");
+        let response = response(send(&mut middleware, Method::GET, "/api/groups"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn requests_outside_every_prefix_are_not_found() {
+        let options = Options {
+            proxy_undocumented: true,
+            ..Options::default()
+        };
+        let mut middleware = versions("outside-prefixes", options);
+        for path in ["/users", "/apis/users", "/v2/groups"] {
+            let response = response(send(&mut middleware, Method::GET, path));
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
 }
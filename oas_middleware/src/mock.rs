@@ -0,0 +1,250 @@
+use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Response};
+use log::warn;
+use openapiv3::*;
+use serde_json::Value;
+
+use openapi_utils::{ExampleOptions, OperationExt, SchemaExt};
+
+use crate::error::E;
+use crate::spec_utils;
+
+/// Builds the response the contract describes for the operation.
+/// The status code can be chosen with the `Prefer: code=404` header, otherwise, or when
+/// the operation does not describe that code, the first success code is used.
+/// The body is the example of the media type or a value built from its schema.
+pub fn mock_response(
+    operation: &Operation,
+    components: &Components,
+    prefer: Option<&str>,
+) -> Result<Response<Body>, E> {
+    let status = match prefer.and_then(preferred_code) {
+        Some(code) if operation.response(code).is_some() => code,
+        Some(code) => {
+            let status = default_code(operation);
+            warn!(
+                "The preferred code {} is not described, mocking {} instead",
+                code, status
+            );
+            status
+        }
+        None => default_code(operation),
+    };
+    let response = operation
+        .response(status)
+        .ok_or(E::StatusCodeError(status))?;
+
+    let mut mocked = Response::builder();
+    mocked.status(status);
+
+    for (name, header) in &response.headers {
+        let header = match header.as_item() {
+            Some(header) if !name.eq_ignore_ascii_case("content-type") => header,
+            _ => continue,
+        };
+        let value = match &header.format {
+            ParameterSchemaOrContent::Schema(schema) => {
+                schema.as_item().map(|schema| sample(schema, components))
+            }
+            ParameterSchemaOrContent::Content(_) => None,
+        };
+        let value = match value {
+            Some(Value::String(string)) => string,
+            Some(Value::Null) | None => continue,
+            Some(value) => value.to_string(),
+        };
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            mocked.header(name, value);
+        }
+    }
+
+    let body = match media_type(&response.content) {
+        Some((content_type, media)) => {
+            mocked.header(CONTENT_TYPE, content_type);
+            match media_example(media, components) {
+                Value::String(string) if !spec_utils::is_json(content_type) => Body::from(string),
+                value => Body::from(value.to_string()),
+            }
+        }
+        None => Body::empty(),
+    };
+
+    Ok(mocked
+        .body(body)
+        .expect("Mocked responses are always valid."))
+}
+
+/// `Prefer: code=404, dynamic=true` prefers the 404 response.
+fn preferred_code(prefer: &str) -> Option<u16> {
+    prefer
+        .split([',', ';'])
+        .filter_map(|preference| {
+            let mut parts = preference.splitn(2, '=');
+            match (parts.next()?.trim(), parts.next()) {
+                ("code", Some(code)) => code.trim().trim_matches('"').parse().ok(),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// The lowest success code, or the lowest code when there are no success responses.
+fn default_code(operation: &Operation) -> u16 {
+    let mut codes: Vec<u16> = operation
+        .responses
+        .responses
+        .keys()
+        .map(|code| match code {
+            StatusCode::Code(code) => *code,
+            StatusCode::Range(range) => range * 100,
+        })
+        .collect();
+    codes.sort_unstable();
+    codes
+        .iter()
+        .find(|code| (200..300).contains(*code))
+        .or_else(|| codes.first())
+        .copied()
+        .unwrap_or(200)
+}
+
+/// JSON media types are preferred, wildcards are answered as JSON.
+fn media_type(content: &Content) -> Option<(&str, &MediaType)> {
+    let (content_type, media) = content
+        .iter()
+        .find(|(content_type, _)| spec_utils::is_json(content_type))
+        .or_else(|| content.iter().next())?;
+    if content_type.contains('*') {
+        Some(("application/json", media))
+    } else {
+        Some((content_type.as_str(), media))
+    }
+}
+
+fn media_example(media: &MediaType, components: &Components) -> Value {
+    if let Some(example) = &media.example {
+        return example.clone();
+    }
+    let example = media
+        .examples
+        .values()
+        .filter_map(|example| example.as_item())
+        .find_map(|example| example.value.clone());
+    match (example, &media.schema) {
+        (Some(example), _) => example,
+        (None, Some(schema)) => schema
+            .as_item()
+            .map(|schema| sample(schema, components))
+            .unwrap_or(Value::Null),
+        (None, None) => Value::Null,
+    }
+}
+
+/// Write only properties are never part of a response.
+fn sample(schema: &Schema, components: &Components) -> Value {
+    let options = ExampleOptions {
+        skip_write_only: true,
+        ..ExampleOptions::default()
+    };
+    schema.example_in(components, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{Future, Stream};
+    use serde_json::json;
+
+    fn operation(responses: Value) -> Operation {
+        serde_json::from_value(json!({ "responses": responses })).unwrap()
+    }
+
+    /// The status, content type and body of the mocked response.
+    fn mock(operation: &Operation, prefer: Option<&str>) -> (u16, Option<String>, String) {
+        let response = mock_response(operation, &Components::default(), prefer).unwrap();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let status = response.status().as_u16();
+        let body = response.into_body().concat2().wait().unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    fn described(description: &str) -> Value {
+        json!({ "description": description })
+    }
+
+    #[test]
+    fn the_preferred_code_is_mocked_when_it_is_described() {
+        let operation = operation(json!({"200": described("ok"), "404": described("missing")}));
+        assert_eq!(mock(&operation, Some("code=404, dynamic=true")).0, 404);
+        assert_eq!(mock(&operation, Some("respond-async; code=\"404\"")).0, 404);
+        assert_eq!(mock(&operation, Some("code=418")).0, 200);
+        assert_eq!(mock(&operation, Some("dynamic=true")).0, 200);
+    }
+
+    #[test]
+    fn the_lowest_success_code_is_the_default() {
+        let codes = operation(
+            json!({"500": described("error"), "204": described("gone"), "201": described("new")}),
+        );
+        assert_eq!(default_code(&codes), 201);
+        let range = operation(json!({"4XX": described("error"), "2XX": described("ok")}));
+        assert_eq!(default_code(&range), 200);
+        let failures = operation(json!({"503": described("busy"), "404": described("missing")}));
+        assert_eq!(default_code(&failures), 404);
+    }
+
+    #[test]
+    fn the_example_comes_before_the_examples_and_the_schema() {
+        let schema = json!({"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}});
+        let examples = json!({"first": {"value": {"id": 2}}});
+        let content = |media: Value| {
+            operation(json!({"200": {"description": "ok", "content": {"application/json": media}}}))
+        };
+
+        let example =
+            content(json!({"schema": schema, "example": {"id": 1}, "examples": examples}));
+        assert_eq!(mock(&example, None).2, r#"{"id":1}"#);
+        let from_examples = content(json!({"schema": schema, "examples": examples}));
+        assert_eq!(mock(&from_examples, None).2, r#"{"id":2}"#);
+        let generated = content(json!({"schema": schema}));
+        let body: Value = serde_json::from_str(&mock(&generated, None).2).unwrap();
+        assert!(body["id"].is_i64(), "{}", body);
+    }
+
+    #[test]
+    fn json_media_types_are_preferred() {
+        let both = operation(json!({"200": {"description": "ok", "content": {
+            "text/plain": {"example": "hello"},
+            "application/problem+json": {"example": "hello"}
+        }}}));
+        let (_, content_type, body) = mock(&both, None);
+        assert_eq!(
+            (content_type.as_deref(), body.as_str()),
+            (Some("application/problem+json"), r#""hello""#)
+        );
+
+        let text = operation(
+            json!({"200": {"description": "ok", "content": {"text/plain": {"example": "hello"}}}}),
+        );
+        let (_, content_type, body) = mock(&text, None);
+        assert_eq!(
+            (content_type.as_deref(), body.as_str()),
+            (Some("text/plain"), "hello")
+        );
+
+        let anything = operation(
+            json!({"200": {"description": "ok", "content": {"*/*": {"example": "hello"}}}}),
+        );
+        assert_eq!(mock(&anything, None).1.as_deref(), Some("application/json"));
+    }
+}
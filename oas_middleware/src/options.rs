@@ -0,0 +1,172 @@
+use http::uri::Authority;
+use hyper::StatusCode;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Settings of the OpenAPI middleware.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Rejects requests with query parameters not described in the OpenAPI file.
+    /// When false those parameters are ignored.
+    pub strict_query: bool,
+    /// Status of the responses to requests which do not agree with the contract.
+    pub error_status: StatusCode,
+    /// Requests and responses which do not agree with the contract are still proxied.
+    /// The failures are logged, counted in the report and listed in the `OAS-Violations` header.
+    /// Requests which start with the prefix of no API are not proxied either way.
+    pub observe: bool,
+    /// Responses of the backend which do not agree with the contract are replaced with a
+    /// `502 Bad Gateway`. When false they are logged, counted in the report and listed in
+    /// the `OAS-Violations` header. Never in observe mode, see `Api` to block single APIs.
+    pub block_responses: bool,
+    /// Valid requests are answered with the examples of the contract instead of being proxied.
+    pub mock: bool,
+    /// The OpenAPI file is reloaded when it changes or the process gets a SIGHUP.
+    /// A file which fails to load is logged and the previous contract is kept.
+    pub watch: bool,
+    /// `POST` requests to this path, `/admin/reload`, reload the OpenAPI files through the
+    /// same thread as `watch`, the response does not wait for them. Disabled when None
+    /// or without `reload_token`.
+    pub reload_path: Option<String>,
+    /// Requests to `reload_path` must have the header `Authorization: Bearer <token>`.
+    pub reload_token: Option<String>,
+    /// Requests and responses are checked against the contract. When false they are proxied
+    /// unchecked, which is mostly useful for single operations, see `operations`.
+    pub validate: bool,
+    /// Requests only match the servers of the spec with their Host header, so one spec can
+    /// describe several deployments. Servers with relative URLs match every host.
+    pub match_host: bool,
+    /// Requests to paths the contract does not describe are proxied without checks to the
+    /// backend of the API whose prefix they start with. When false they are answered with 404.
+    /// Requests which start with the prefix of no API are always answered with 404.
+    pub proxy_undocumented: bool,
+    /// Requests to these paths are proxied to the default backend without any check.
+    /// `/metrics` is a path, `/internal/*` every path starting with `/internal/`.
+    pub ignored_paths: Vec<String>,
+    /// Settings of single operations, by `operationId`, which replace the ones above.
+    pub operations: HashMap<String, OperationOptions>,
+}
+
+/// Settings of an operation, the ones which are None are the same as for the rest.
+#[derive(Clone, Debug, Default)]
+pub struct OperationOptions {
+    pub strict_query: Option<bool>,
+    pub observe: Option<bool>,
+    pub block_responses: Option<bool>,
+    pub mock: Option<bool>,
+    pub validate: Option<bool>,
+}
+
+impl Options {
+    /// The settings for requests to an operation.
+    pub fn for_operation(&self, operation_id: Option<&str>) -> Options {
+        let overrides = operation_id
+            .and_then(|operation_id| self.operations.get(operation_id))
+            .cloned()
+            .unwrap_or_default();
+        Options {
+            strict_query: overrides.strict_query.unwrap_or(self.strict_query),
+            error_status: self.error_status,
+            observe: overrides.observe.unwrap_or(self.observe),
+            block_responses: overrides.block_responses.unwrap_or(self.block_responses),
+            mock: overrides.mock.unwrap_or(self.mock),
+            watch: self.watch,
+            reload_path: None,
+            reload_token: None,
+            validate: overrides.validate.unwrap_or(self.validate),
+            match_host: self.match_host,
+            proxy_undocumented: self.proxy_undocumented,
+            ignored_paths: Vec::new(),
+            operations: HashMap::new(),
+        }
+    }
+
+    /// True when responses of the operation which do not agree with the contract are
+    /// replaced with a 502. The setting of the operation wins over the one of its API.
+    pub fn blocks_responses(&self, api: &Api, operation_id: Option<&str>) -> bool {
+        operation_id
+            .and_then(|operation_id| self.operations.get(operation_id))
+            .and_then(|operation| operation.block_responses)
+            .or(api.block_responses)
+            .unwrap_or(self.block_responses)
+    }
+
+    /// True when `POST` requests to `reload_path` reload the specs.
+    pub fn reloads_on_request(&self) -> bool {
+        self.reload_path.is_some() && self.reload_token.is_some()
+    }
+
+    /// True when requests to the path are not checked.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.ignored_paths.iter().any(|ignored| match ignored.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == ignored,
+        })
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            strict_query: false,
+            error_status: StatusCode::BAD_REQUEST,
+            observe: false,
+            block_responses: false,
+            mock: false,
+            watch: false,
+            reload_path: None,
+            reload_token: None,
+            validate: true,
+            match_host: false,
+            proxy_undocumented: false,
+            ignored_paths: Vec::new(),
+            operations: HashMap::new(),
+        }
+    }
+}
+
+/// An OpenAPI file and the backend the requests it describes are proxied to.
+#[derive(Clone, Debug)]
+pub struct Api {
+    /// Path or URL of the OpenAPI file.
+    pub spec: PathBuf,
+    /// Where the requests go, the backend of the proxy when None.
+    pub backend: Option<Authority>,
+    /// Requests to the API start with this path, `/users-api`. It is removed before
+    /// the request is proxied. Empty when the paths of the spec are used as they are.
+    pub prefix: String,
+    /// Replaces `Options::block_responses` for the operations of this API.
+    pub block_responses: Option<bool>,
+}
+
+impl Api {
+    pub fn new<P: Into<PathBuf>>(spec: P) -> Self {
+        Api {
+            spec: spec.into(),
+            backend: None,
+            prefix: String::new(),
+            block_responses: None,
+        }
+    }
+
+    pub fn with_backend(mut self, backend: Authority) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn with_block_responses(mut self, block_responses: bool) -> Self {
+        self.block_responses = Some(block_responses);
+        self
+    }
+
+    /// `users-api/` is the same prefix as `/users-api`.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        self.prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", prefix)
+        };
+        self
+    }
+}
@@ -1,64 +1,658 @@
+use http::uri::Authority;
+use hyper::Method;
 use openapiv3::*;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use regex::Regex;
+use log::warn;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 //use anyhow::Result;
 
-use openapi_utils::ReferenceOrExt;
 use openapi_utils::ServerExt;
 
 use crate::error::E;
+use crate::spec_utils;
 
+/// The methods of the operations of path items, in the order of `spec_utils::operation_list`.
+const METHODS: [&str; 8] = ["delete", "get", "head", "options", "patch", "post", "put", "trace"];
+
+/// Characters which can't be part of a path segment without encoding.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Finds the path of the spec a request goes to.
+///
+/// The paths are kept in a tree of their segments. A request walks it from the left,
+/// trying literal segments before templated ones, as OpenAPI asks to match concrete
+/// paths before their templated counterparts. `/users/me` wins over `/users/{id}` and
+/// `/files/{name}.json` wins over `/files/{name}`.
+///
+/// Each path is matched under the base path of every server which serves it, the
+/// servers of the spec unless the path or its operations list their own. The locations
+/// share the path items, an operation is the same wherever its requests went.
 #[derive(Debug)]
 pub struct PathFinder {
     pub path_matches: Vec<PathMatch>,
+    /// The path items of the spec.
+    pub items: Vec<PathItem>,
+    root: Node,
 }
 
 #[derive(Debug)]
 pub struct PathMatch {
+    /// Extracts the path variables of the requests.
     pub regex: Regex,
-    pub path: PathItem,
+    /// The names of the path variables, in the order of the groups of `regex`.
+    pub variables: Vec<String>,
+    /// The methods served under this base path, with the index of their path item in
+    /// `PathFinder::items`.
+    pub operations: Vec<(&'static str, usize)>,
+    /// The path with the prefix and base path, `/v1/users/{id}`.
+    pub template: String,
+    /// The host of the servers, when requests are matched by host. None matches any host.
+    pub host: Option<String>,
+}
+
+/// The path of the spec a request goes to.
+pub struct FoundPath<'a> {
+    /// Extracts the path variables of the request.
+    pub regex: &'a Regex,
+    /// The names of the path variables, in the order of the groups of `regex`.
+    pub variables: &'a [String],
+    operations: &'a [(&'static str, usize)],
+    items: &'a mut [PathItem],
+}
+
+impl<'a> FoundPath<'a> {
+    /// The operation of the method, if it is served where the request went.
+    pub fn operation(&mut self, method: &Method) -> Result<&mut Operation, E> {
+        let method_name = method.as_str().to_lowercase();
+        match self.operations.iter().find(|(name, _)| *name == method_name) {
+            Some((_, item)) => spec_utils::path_to_operation(&mut self.items[*item], method),
+            None => Err(E::MethodError(method.to_string())),
+        }
+    }
+
+    /// The methods served where the request went, as listed in an `Allow` header.
+    pub fn allowed_methods(&self) -> String {
+        let methods: Vec<String> = self.operations.iter().map(|(name, _)| name.to_uppercase()).collect();
+        methods.join(", ")
+    }
+}
+
+/// Where a server serves its paths.
+#[derive(Clone, Debug, PartialEq)]
+struct Location {
+    host: Option<String>,
+    base_path: String,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    literals: HashMap<String, Node>,
+    /// Sorted with the most specific segments first.
+    templates: Vec<(Segment, Node)>,
+    /// Indexes of the paths ending in this node, one for each host.
+    paths: Vec<usize>,
+}
+
+/// A path segment with variables, `{id}` or `{name}.{ext}`.
+#[derive(Debug)]
+struct Segment {
+    /// The segment without the names of the variables, `{}.{}`.
+    shape: String,
+    regex: Regex,
+    /// Characters which are not part of a variable, more of them make the segment more specific.
+    literal_length: usize,
 }
 
 impl PathFinder {
-    pub fn new(spec: OpenAPI) -> Self {
+    /// The paths of the spec are matched after the `prefix`, `/users-api` matches
+    /// `/users-api/v1/users` with the path `/users` of a spec with the server `/v1`.
+    /// With `match_host` the servers only match requests with their Host header.
+    pub fn new(spec: OpenAPI, prefix: &str, match_host: bool) -> Self {
+        let (path_matches, items) = Self::create_path_regexes(spec, prefix, match_host);
+        let mut root = Node::default();
+        for (index, path_match) in path_matches.iter().enumerate() {
+            if let Some(previous) = root.insert(&path_matches, index) {
+                warn!(
+                    "The paths {} and {} are the same path, requests go to {}.",
+                    path_matches[previous].template, path_match.template, path_matches[previous].template
+                );
+            }
+        }
+        for ambiguity in ambiguities(&path_matches) {
+            warn!("{}", ambiguity);
+        }
         PathFinder {
-            path_matches: Self::create_path_regexes(spec),
+            path_matches,
+            items,
+            root,
+        }
+    }
+
+    /// The path for a request, `host` is its Host header.
+    pub fn find<'a>(&'a mut self, path: &str, host: Option<&str>) -> Result<FoundPath<'a>, E> {
+        match self.position(path, host) {
+            Some(index) => {
+                let path_match = &self.path_matches[index];
+                Ok(FoundPath {
+                    regex: &path_match.regex,
+                    variables: &path_match.variables,
+                    operations: &path_match.operations,
+                    items: &mut self.items,
+                })
+            }
+            None => Err(E::PathError(path.to_string())),
+        }
+    }
+
+    /// The operations served under a path, with their methods.
+    pub fn operations(&self, path_match: &PathMatch) -> Vec<(&'static str, &Operation)> {
+        path_match
+            .operations
+            .iter()
+            .filter_map(|(method, item)| {
+                spec_utils::operation_list(&self.items[*item])
+                    .into_iter()
+                    .find(|(name, _)| name == method)
+            })
+            .collect()
+    }
+
+    /// True if a path of the spec matches the path of the request.
+    pub fn matches(&self, path: &str, host: Option<&str>) -> bool {
+        self.position(path, host).is_some()
+    }
+
+    fn position(&self, path: &str, host: Option<&str>) -> Option<usize> {
+        let segments: Vec<&str> = segments(path).collect();
+        let accepts = |index: usize| match &self.path_matches[index].host {
+            Some(server_host) => host.is_some_and(|host| same_host(server_host, host)),
+            None => true,
+        };
+        self.root.find(&segments, &accepts)
+    }
+
+    /// The regex of a path with the names of its variables, `/study/{uuid}/test` is
+    /// `^/study/(?P<uuid>[^/]*)/test$`.
+    fn spec_path_to_regex_str(path: &str) -> (Regex, Vec<String>) {
+        let (string, variables) = template_regex(path);
+        // The literal parts are escaped and the groups have valid names, it always compiles.
+        let regex = Regex::new(&format!("^{}$", string))
+            .unwrap_or_else(|error| panic!("Could not create regex from path {}: {}", path, error));
+        (regex, variables)
+    }
+
+
+    fn create_path_regexes(spec: OpenAPI, prefix: &str, match_host: bool) -> (Vec<PathMatch>, Vec<PathItem>) {
+        let mut result: Vec<PathMatch> = Vec::new();
+        let mut items = Vec::new();
+        let spec_locations = locations(&spec.servers, match_host);
+        for (p, path_item) in spec.paths.paths {
+            let path_item = path_item
+                .into_item()
+                .expect("Path items are always dereferenced.");
+            let path_locations = if path_item.servers.is_empty() {
+                spec_locations.clone()
+            } else {
+                locations(&path_item.servers, match_host)
+            };
+
+            // Operations with their own servers are only matched under those.
+            let mut served = Vec::new();
+            let mut rest = Vec::new();
+            for (method, operation) in spec_utils::operation_list(&path_item) {
+                if operation.servers.is_empty() {
+                    rest.push(method);
+                    continue;
+                }
+                for location in locations(&operation.servers, match_host) {
+                    served.push((location, vec![method]));
+                }
+            }
+            if served.is_empty() || !rest.is_empty() {
+                served.extend(path_locations.into_iter().map(|location| (location, rest.clone())));
+            }
+
+            let item = items.len();
+            items.push(path_item);
+            for (location, methods) in served {
+                let template = format!("{}{}{}", prefix, location.base_path, p);
+                let existing = result
+                    .iter_mut()
+                    .find(|existing| existing.template == template && existing.host == location.host);
+                match existing {
+                    // Several servers with the same base path.
+                    Some(existing) => {
+                        for method in methods {
+                            if !existing.operations.iter().any(|(name, _)| *name == method) {
+                                existing.operations.push((method, item));
+                            }
+                        }
+                        existing.operations.sort_by_key(|(name, _)| METHODS.iter().position(|method| method == name));
+                    }
+                    None => {
+                        let (regex, variables) = Self::spec_path_to_regex_str(&template);
+                        result.push(PathMatch {
+                            regex,
+                            variables,
+                            operations: methods.into_iter().map(|method| (method, item)).collect(),
+                            template,
+                            host: location.host,
+                        });
+                    }
+                }
+            }
         }
+        (result, items)
     }
-    pub fn find<'a>(&'a mut self, path: &str) -> Result<&'a mut PathMatch, E> {
-        // /users/<user_id> and /users/copy regexes would match /users/copy path.
-        // We choose the most specific one, the one with minimum number of variable captures.
-        self.path_matches
-            .iter_mut()
-            .filter(|path_match| path_match.regex.is_match(&path))
-            .min_by_key(|path_match| path_match.regex.captures_len())
-            .ok_or_else(|| E::PathError(path.to_string()))
-    }
-
-    ///
-    /// # Examples
-    ///
-    ///
-    /// let result = oas_middleware::validator::spec_path_to_regex_str("/study/{uuid}/test");
-    /// assert_eq!(result, "^/study/(?P<uuid>[^/]*)/test$");
-    ///
-    fn spec_path_to_regex_str(path: &str) -> Regex {
-        let replaced = path.replace("{", "(?P<").replace("}", ">[^/]*)");
-        let string = format!(r"^{}$", replaced);
-        Regex::new(&string).expect(&format!("Could not create regex from path {}.", path))
-    }
-
-
-    fn create_path_regexes(spec: OpenAPI) -> Vec<PathMatch> {
-        let mut result = Vec::new();
-        let base_path = spec.servers[0].base_path();
-        for (p, path_item) in spec.paths {
-            let path = format!("{}{}", base_path, p);
-            let pr = PathMatch {
-                regex: Self::spec_path_to_regex_str(&path),
-                path: path_item.to_item(),
+}
+
+/// Where the servers serve their paths, without repetitions. No servers is the same as
+/// the server `/`.
+fn locations(servers: &[Server], match_host: bool) -> Vec<Location> {
+    let mut result = Vec::new();
+    let urls: Vec<String> = servers.iter().flat_map(|server| server.urls()).collect();
+    if urls.is_empty() {
+        result.push(Location {
+            host: None,
+            base_path: String::new(),
+        });
+    }
+    for url in urls {
+        let server = Server {
+            url: url.clone(),
+            ..Server::default()
+        };
+        let host = if match_host { url_host(&url) } else { None };
+        let location = Location {
+            host,
+            base_path: server.base_path(),
+        };
+        if !result.contains(&location) {
+            result.push(location);
+        }
+    }
+    result
+}
+
+/// The host of absolute URLs, with its port when it has one.
+fn url_host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    Some(host.to_lowercase())
+}
+
+/// True when the Host header of a request is the host of a server. Servers without a
+/// port match the host on any port. IPv6 hosts are in brackets, `[::1]:8080`.
+fn same_host(server_host: &str, host: &str) -> bool {
+    match (server_host.parse::<Authority>(), host.parse::<Authority>()) {
+        (Ok(server), Ok(request)) => {
+            server.host().eq_ignore_ascii_case(request.host())
+                && (server.port_u16().is_none() || server.port_u16() == request.port_u16())
+        }
+        _ => false,
+    }
+}
+
+impl Node {
+    /// Adds a path, returns the index of the path which already had the same segments
+    /// for the same host.
+    fn insert(&mut self, path_matches: &[PathMatch], index: usize) -> Option<usize> {
+        let mut node = self;
+        for segment in segments(&path_matches[index].template) {
+            node = if segment.contains('{') {
+                let segment = Segment::new(segment);
+                let position = node
+                    .templates
+                    .iter()
+                    .position(|(existing, _)| existing.shape == segment.shape);
+                let position = match position {
+                    Some(position) => position,
+                    None => {
+                        let position = node
+                            .templates
+                            .iter()
+                            .position(|(existing, _)| existing.literal_length < segment.literal_length)
+                            .unwrap_or(node.templates.len());
+                        node.templates.insert(position, (segment, Node::default()));
+                        position
+                    }
+                };
+                &mut node.templates[position].1
+            } else {
+                let literal = utf8_percent_encode(segment, PATH_SEGMENT).to_string();
+                node.literals.entry(literal).or_default()
             };
-            result.push(pr);
         }
-        result
+        let host = &path_matches[index].host;
+        match node.paths.iter().find(|previous| path_matches[**previous].host == *host) {
+            Some(previous) => Some(*previous),
+            None => {
+                node.paths.push(index);
+                None
+            }
+        }
+    }
+
+    /// The first path in the order of preference which `accepts`, falls back to
+    /// templates when the literal segment leads nowhere.
+    fn find(&self, segments: &[&str], accepts: &dyn Fn(usize) -> bool) -> Option<usize> {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return self.paths.iter().copied().find(|index| accepts(*index)),
+        };
+        if let Some(index) = self.literals.get(*segment).and_then(|node| node.find(rest, accepts)) {
+            return Some(index);
+        }
+        self.templates
+            .iter()
+            .filter(|(template, _)| template.regex.is_match(segment))
+            .find_map(|(_, node)| node.find(rest, accepts))
+    }
+}
+
+impl Segment {
+    fn new(segment: &str) -> Self {
+        let mut shape = String::new();
+        let mut literal_length = 0;
+        for (idx, part) in segment.split('{').enumerate() {
+            match part.find('}') {
+                Some(end) if idx > 0 => {
+                    shape.push_str("{}");
+                    shape.push_str(&part[end + 1..]);
+                    literal_length += part.len() - end - 1;
+                }
+                _ => {
+                    shape.push_str(part);
+                    literal_length += part.len();
+                }
+            }
+        }
+        let regex = format!("^{}$", template_regex(segment).0);
+        Segment {
+            shape,
+            regex: Regex::new(&regex)
+                .unwrap_or_else(|error| panic!("Could not create regex from path segment {}: {}", segment, error)),
+            literal_length,
+        }
+    }
+}
+
+/// The segments of a path without the leading `/`.
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.strip_prefix('/').unwrap_or(path).split('/')
+}
+
+/// Regex for a path or segment with variables, without anchors, and the names of the
+/// variables in the order of its groups.
+fn template_regex(path: &str) -> (String, Vec<String>) {
+    // Requests arrive percent-encoded so literal parts are encoded before matching.
+    let mut string = String::new();
+    let mut variables = Vec::new();
+    let mut groups = Vec::new();
+    for (idx, part) in path.split('{').enumerate() {
+        let literal = match part.find('}') {
+            Some(end) if idx > 0 => {
+                let group = group_name(&part[..end], &groups);
+                string.push_str(&format!("(?P<{}>[^/]*)", group));
+                groups.push(group);
+                variables.push(part[..end].to_string());
+                &part[end + 1..]
+            }
+            _ => part,
+        };
+        let encoded = utf8_percent_encode(literal, PATH_SEGMENT).to_string();
+        string.push_str(&regex::escape(&encoded));
+    }
+    (string, variables)
+}
+
+/// The name of the regex group of a variable. Names which can't name a group, like
+/// `user-id`, or which are already taken get a generated one.
+fn group_name(variable: &str, groups: &[String]) -> String {
+    let mut chars = variable.chars();
+    let valid = chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid && !groups.iter().any(|group| group == variable) {
+        return variable.to_string();
+    }
+    (groups.len()..)
+        .map(|index| format!("_{}", index))
+        .find(|group| !groups.contains(group))
+        .expect("There are names which are not taken.")
+}
+
+/// A segment of a path as it is compared with the segments of other paths.
+enum Part {
+    /// Percent-encoded like the segments of requests.
+    Literal(String),
+    Template(Segment),
+}
+
+impl Part {
+    fn new(segment: &str) -> Self {
+        if segment.contains('{') {
+            Part::Template(Segment::new(segment))
+        } else {
+            Part::Literal(utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        }
+    }
+}
+
+/// Pairs of paths which can match the same request where neither is more specific in
+/// every segment, like `/a/{x}/c` and `/a/b/{y}`. The one with the most specific
+/// segment first, from the left, gets the request.
+fn ambiguities(path_matches: &[PathMatch]) -> Vec<String> {
+    let parsed: Vec<Vec<Part>> = path_matches
+        .iter()
+        .map(|path_match| segments(&path_match.template).map(Part::new).collect())
+        .collect();
+    let mut result = Vec::new();
+    for (first, first_parts) in parsed.iter().enumerate() {
+        for (second, second_parts) in parsed.iter().enumerate().skip(first + 1) {
+            if first_parts.len() != second_parts.len() {
+                continue;
+            }
+            let hosts = (&path_matches[first].host, &path_matches[second].host);
+            if let (Some(a), Some(b)) = hosts {
+                if a != b {
+                    continue;
+                }
+            }
+            let orders = match compare(first_parts, second_parts) {
+                Some(orders) => orders,
+                None => continue,
+            };
+            let ambiguous = orders.contains(&Ordering::Equal)
+                || (orders.contains(&Ordering::Greater) && orders.contains(&Ordering::Less));
+            if ambiguous {
+                let (a, b) = (&path_matches[first].template, &path_matches[second].template);
+                // Templates as specific as each other are tried in the order of the spec.
+                let used = if orders[0] == Ordering::Less { b } else { a };
+                result.push(format!(
+                    "The paths {} and {} can match the same requests, {} is used for them.",
+                    a, b, used
+                ));
+            }
+        }
+    }
+    result
+}
+
+/// How specific the first path is in each segment where the paths differ, compared to
+/// the second one. None when no request can match both.
+fn compare(first: &[Part], second: &[Part]) -> Option<Vec<Ordering>> {
+    let mut orders = Vec::new();
+    for pair in first.iter().zip(second) {
+        let order = match pair {
+            (Part::Literal(a), Part::Literal(b)) if a == b => continue,
+            (Part::Literal(_), Part::Literal(_)) => return None,
+            (Part::Literal(a), Part::Template(b)) if b.regex.is_match(a) => Ordering::Greater,
+            (Part::Template(a), Part::Literal(b)) if a.regex.is_match(b) => Ordering::Less,
+            (Part::Literal(_), Part::Template(_)) | (Part::Template(_), Part::Literal(_)) => return None,
+            (Part::Template(a), Part::Template(b)) if a.shape == b.shape => continue,
+            // Different templates can match the same segment, `{a}.json` and `{a}.{b}`.
+            (Part::Template(a), Part::Template(b)) => a.literal_length.cmp(&b.literal_length),
+        };
+        orders.push(order);
+    }
+    Some(orders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path_finder(spec: serde_json::Value) -> PathFinder {
+        let mut spec = spec;
+        spec["openapi"] = json!("3.0.0");
+        spec["info"] = json!({"title": "Test", "version": "1"});
+        PathFinder::new(serde_json::from_value(spec).unwrap(), "", false)
+    }
+
+    fn response() -> serde_json::Value {
+        json!({"200": {"description": "ok"}})
+    }
+
+    /// A spec with a GET operation for each path.
+    fn paths(paths: &[&str]) -> serde_json::Value {
+        let items: serde_json::Map<String, serde_json::Value> = paths
+            .iter()
+            .map(|path| (path.to_string(), json!({"get": {"responses": response()}})))
+            .collect();
+        json!({ "paths": items })
+    }
+
+    /// The template of the path a request goes to.
+    fn found<'a>(path_finder: &'a PathFinder, path: &str) -> Option<&'a str> {
+        let index = path_finder.position(path, None)?;
+        Some(&path_finder.path_matches[index].template)
+    }
+
+    #[test]
+    fn literal_segments_beat_templates() {
+        let path_finder = path_finder(paths(&["/users/{id}", "/users/me"]));
+        assert_eq!(found(&path_finder, "/users/me"), Some("/users/me"));
+        assert_eq!(found(&path_finder, "/users/42"), Some("/users/{id}"));
+    }
+
+    #[test]
+    fn templates_with_more_literal_characters_win() {
+        let path_finder = path_finder(paths(&["/files/{name}", "/files/{name}.json"]));
+        assert_eq!(found(&path_finder, "/files/a.json"), Some("/files/{name}.json"));
+        assert_eq!(found(&path_finder, "/files/a.txt"), Some("/files/{name}"));
+    }
+
+    #[test]
+    fn templates_are_tried_when_the_literal_segment_leads_nowhere() {
+        let path_finder = path_finder(paths(&["/users/me", "/users/{id}/posts"]));
+        assert_eq!(found(&path_finder, "/users/me/posts"), Some("/users/{id}/posts"));
+        assert_eq!(found(&path_finder, "/users/me/friends"), None);
+    }
+
+    #[test]
+    fn literal_segments_are_percent_encoded() {
+        let path_finder = path_finder(paths(&["/café/{id}"]));
+        assert_eq!(found(&path_finder, "/caf%C3%A9/1"), Some("/café/{id}"));
+    }
+
+    #[test]
+    fn variables_which_can_not_name_groups_get_generated_names() {
+        let mut path_finder = path_finder(paths(&["/users/{user-id}/{_1}/{user-id}", "/groups/{id}"]));
+        let found = path_finder.find("/users/a/b/c", None).unwrap();
+        assert_eq!(found.variables, ["user-id", "_1", "user-id"]);
+        let captures = found.regex.captures("/users/a/b/c").unwrap();
+        let values: Vec<&str> = captures.iter().skip(1).flatten().map(|value| value.as_str()).collect();
+        assert_eq!(values, ["a", "b", "c"]);
+        let found = path_finder.find("/groups/1", None).unwrap();
+        assert_eq!(found.regex.as_str(), "^/groups/(?P<id>[^/]*)$");
+    }
+
+    #[test]
+    fn ambiguous_paths_are_reported() {
+        let path_finder = path_finder(paths(&["/a/{x}/c", "/a/b/{y}", "/users/{id}", "/users/me"]));
+        assert_eq!(
+            ambiguities(&path_finder.path_matches),
+            vec!["The paths /a/{x}/c and /a/b/{y} can match the same requests, /a/b/{y} is used for them."]
+        );
+        assert_eq!(found(&path_finder, "/a/b/c"), Some("/a/b/{y}"));
+    }
+
+    #[test]
+    fn paths_differing_in_the_names_of_their_variables_are_the_same() {
+        let path_finder = path_finder(paths(&["/users/{id}", "/users/{name}"]));
+        assert!(ambiguities(&path_finder.path_matches).is_empty());
+        assert_eq!(found(&path_finder, "/users/42"), Some("/users/{id}"));
+    }
+
+    #[test]
+    fn paths_are_matched_after_the_prefix_and_base_path() {
+        let mut spec = paths(&["/users"]);
+        spec["openapi"] = json!("3.0.0");
+        spec["info"] = json!({"title": "Test", "version": "1"});
+        spec["servers"] = json!([{"url": "https://example.com/v1"}]);
+        let path_finder = PathFinder::new(serde_json::from_value(spec).unwrap(), "/api", false);
+        assert_eq!(found(&path_finder, "/api/v1/users"), Some("/api/v1/users"));
+        assert_eq!(found(&path_finder, "/v1/users"), None);
+        assert_eq!(found(&path_finder, "/api/users"), None);
+    }
+
+    #[test]
+    fn server_locations_share_their_operations() {
+        let mut path_finder = path_finder(json!({
+            "servers": [{"url": "/v1"}, {"url": "/v2"}],
+            "paths": {"/users": {"get": {"responses": response()}}}
+        }));
+        let mut v1 = path_finder.find("/v1/users", None).unwrap();
+        crate::spec_utils::used(&mut v1.operation(&Method::GET).unwrap().description);
+        let mut v2 = path_finder.find("/v2/users", None).unwrap();
+        let description = &v2.operation(&Method::GET).unwrap().description;
+        assert_eq!(description.as_deref(), Some("1"));
+        assert_eq!(path_finder.items.len(), 1);
+    }
+
+    #[test]
+    fn operations_with_servers_are_only_served_there() {
+        let mut path_finder = path_finder(json!({
+            "servers": [{"url": "/v1"}],
+            "paths": {"/users": {
+                "get": {"responses": response()},
+                "post": {"servers": [{"url": "/admin"}], "responses": response()}
+            }}
+        }));
+        let mut v1 = path_finder.find("/v1/users", None).unwrap();
+        assert!(v1.operation(&Method::POST).is_err());
+        assert_eq!(v1.allowed_methods(), "GET");
+        let mut admin = path_finder.find("/admin/users", None).unwrap();
+        assert!(admin.operation(&Method::POST).is_ok());
+        assert_eq!(admin.allowed_methods(), "POST");
+    }
+
+    #[test]
+    fn hosts_match_on_any_port_unless_the_server_has_one() {
+        assert!(same_host("example.com", "Example.com:8080"));
+        assert!(same_host("example.com:8080", "example.com:8080"));
+        assert!(!same_host("example.com:8080", "example.com"));
+        assert!(!same_host("example.com", "example.org"));
+    }
+
+    #[test]
+    fn ipv6_hosts_match_with_their_port() {
+        assert!(same_host("[::1]", "[::1]:8080"));
+        assert!(same_host("[::1]:8080", "[::1]:8080"));
+        assert!(!same_host("[::1]:8080", "[::1]:9090"));
+        assert!(!same_host("[::1]", "[::2]"));
     }
 }
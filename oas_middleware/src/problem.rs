@@ -0,0 +1,204 @@
+use anyhow::Error;
+use http::uri::Uri;
+use hyper::StatusCode;
+use serde::Serialize;
+
+use crate::error::{Failures, E};
+
+/// Media type of the problem documents.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Problem details for HTTP APIs as described in RFC 7807.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// Path and query of the request which failed.
+    pub instance: String,
+    pub failed_url: String,
+    /// Messages of all the errors, kept for clients of the previous format.
+    pub causes: Vec<String>,
+    pub errors: Vec<ProblemError>,
+}
+
+/// One entry of the `errors` member of a problem.
+/// Schema failures have one entry for each violation.
+#[derive(Debug, Serialize)]
+pub struct ProblemError {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    /// `path`, `query`, `header`, `cookie`, `body`, `status`, `method` or `request`.
+    pub location: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// JSON pointer inside the value of the parameter or body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    pub detail: String,
+}
+
+impl Problem {
+    pub fn new(type_uri: &str, title: &str, status: StatusCode, error: &Error, uri: &Uri) -> Self {
+        let errors = error
+            .chain()
+            .flat_map(|cause| {
+                if let Some(failures) = cause.downcast_ref::<Failures>() {
+                    failures
+                        .0
+                        .iter()
+                        .flat_map(|failure| {
+                            problem_errors(&failure.location, failure.name.as_deref(), &failure.error)
+                        })
+                        .collect()
+                } else if let Some(error) = cause.downcast_ref::<E>() {
+                    problem_errors(location(error), None, error)
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect();
+        let instance = uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or_else(|| uri.path());
+
+        Problem {
+            type_uri: type_uri.to_string(),
+            title: title.to_string(),
+            status: status.as_u16(),
+            detail: error.to_string(),
+            instance: instance.to_string(),
+            failed_url: uri.to_string(),
+            causes: causes(error),
+            errors,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Problems can always be serialized.")
+    }
+}
+
+/// The chain of errors with one cause for each validation failure.
+fn causes(error: &Error) -> Vec<String> {
+    error
+        .chain()
+        .flat_map(|cause| match cause.downcast_ref::<Failures>() {
+            Some(failures) => failures.0.iter().map(|failure| failure.to_string()).collect(),
+            None => vec![cause.to_string()],
+        })
+        .collect()
+}
+
+fn problem_errors(location: &str, name: Option<&str>, error: &E) -> Vec<ProblemError> {
+    let entry = |pointer, keyword, expected, actual| ProblemError {
+        type_uri: error.type_uri().to_string(),
+        location: location.to_string(),
+        name: name.map(|name| name.to_string()),
+        pointer,
+        keyword,
+        expected,
+        actual,
+        detail: error.to_string(),
+    };
+
+    if error.violations().is_empty() {
+        return vec![entry(None, None, None, None)];
+    }
+    error
+        .violations()
+        .iter()
+        .map(|violation| {
+            entry(
+                Some(violation.pointer.clone()),
+                Some(violation.keyword.clone()),
+                Some(violation.expected.clone()),
+                Some(violation.actual.clone()),
+            )
+        })
+        .collect()
+}
+
+/// Location of the errors found before the parts of the request are checked.
+fn location(error: &E) -> &'static str {
+    match error {
+        E::PathError(_) => "path",
+        E::MethodError(_) => "method",
+        E::StatusCodeError(_) => "status",
+        _ => "request",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Failure;
+    use crate::schema_validator::Violation;
+    use serde_json::{json, Value};
+
+    fn violation(pointer: &str, keyword: &str) -> Violation {
+        Violation {
+            pointer: pointer.to_string(),
+            keyword: keyword.to_string(),
+            expected: String::from("a string"),
+            actual: String::from("a number"),
+        }
+    }
+
+    fn problem(failures: Vec<Failure>) -> Value {
+        let error = Error::new(Failures(failures)).context("Failed validation of request variables.");
+        let uri: Uri = "http://localhost:3000/users?limit=a".parse().unwrap();
+        let problem = Problem::new(
+            "urn:oas-proxy:request-contract-broken",
+            "The request does not agree with the API contract. Not proxying.",
+            StatusCode::BAD_REQUEST,
+            &error,
+            &uri,
+        );
+        serde_json::from_str(&problem.to_json()).unwrap()
+    }
+
+    #[test]
+    fn problems_are_rfc_7807_documents() {
+        assert_eq!(PROBLEM_JSON, "application/problem+json");
+        let problem = problem(vec![Failure::new("query", Some("limit"), E::RequiredError(String::from("limit")))]);
+        assert_eq!(problem["type"], "urn:oas-proxy:request-contract-broken");
+        assert_eq!(problem["title"], "The request does not agree with the API contract. Not proxying.");
+        assert_eq!(problem["status"], 400);
+        assert_eq!(problem["detail"], "Failed validation of request variables.");
+        assert_eq!(problem["instance"], "/users?limit=a");
+        assert_eq!(problem["failed_url"], "http://localhost:3000/users?limit=a");
+        assert_eq!(
+            problem["errors"],
+            json!([{
+                "type": "urn:oas-proxy:required-missing",
+                "location": "query",
+                "name": "limit",
+                "detail": "The contract specifies `limit` as required but it is missing."
+            }])
+        );
+    }
+
+    #[test]
+    fn schema_failures_have_an_error_for_each_violation() {
+        let error = E::SchemaError(vec![violation("/name", "type"), violation("", "required")]);
+        let problem = problem(vec![Failure::new("body", None, error)]);
+        let errors = problem["errors"].as_array().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0]["location"], "body");
+        assert_eq!(errors[0]["type"], "urn:oas-proxy:body-schema");
+        assert_eq!((&errors[0]["pointer"], &errors[0]["keyword"]), (&json!("/name"), &json!("type")));
+        assert_eq!((&errors[1]["pointer"], &errors[1]["keyword"]), (&json!(""), &json!("required")));
+        assert_eq!(errors[0]["expected"], "a string");
+        assert!(errors[0].get("name").is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::middleware::Spec;
+use crate::options::Api;
+
+/// How often the files of the spec are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counts the SIGHUPs, each watcher reloads its spec on its next check when it changes.
+static HANGUPS: AtomicUsize = AtomicUsize::new(0);
+
+/// The specs loaded by a watcher thread and the way to ask it for a reload.
+pub(crate) struct Watcher {
+    specs: Receiver<Spec>,
+    requests: Sender<()>,
+}
+
+impl Watcher {
+    /// The latest spec the watcher loaded since the last call.
+    pub(crate) fn latest(&self) -> Option<Spec> {
+        self.specs.try_iter().last()
+    }
+
+    /// Asks the watcher to reload the spec, it does not wait for it.
+    pub(crate) fn request(&self) {
+        // The watcher only stops when the middleware is gone.
+        let _ = self.requests.send(());
+    }
+}
+
+/// Reloads the spec in its own thread when it is requested and, with `watch_files`, when
+/// one of its files changes or the process gets a SIGHUP. `files` are the ones the
+/// current spec was read from, the root one and every one it references. Loading specs blocks, on files or
+/// downloads, so it never happens in the proxy's runtime. The new spec is sent to the
+/// middleware, which swaps it between requests. Specs which fail to load are logged and
+/// never sent, the middleware keeps the one it has.
+pub(crate) fn watch(api: &Api, files: &[PathBuf], match_host: bool, watch_files: bool) -> Watcher {
+    let (sender, specs) = channel();
+    let (requests, receiver) = channel();
+    let api = api.clone();
+    let files = files.to_vec();
+    if watch_files {
+        listen_hangup();
+    }
+    thread::spawn(move || poll(api, files, match_host, watch_files, receiver, sender));
+    Watcher { specs, requests }
+}
+
+fn poll(
+    api: Api,
+    mut files: Vec<PathBuf>,
+    match_host: bool,
+    watch_files: bool,
+    requests: Receiver<()>,
+    sender: Sender<Spec>,
+) {
+    let filename = &api.spec;
+    let mut last_modified = modified(&files);
+    let mut last_hangups = HANGUPS.load(Ordering::SeqCst);
+    loop {
+        let requested = match requests.recv_timeout(POLL_INTERVAL) {
+            Ok(()) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let current = modified(&files);
+        let hangups = HANGUPS.load(Ordering::SeqCst);
+        let changed = watch_files && (current != last_modified || hangups != last_hangups);
+        if !requested && !changed {
+            continue;
+        }
+        last_modified = current;
+        last_hangups = hangups;
+        info!("Reloading the spec {}", filename.display());
+        match Spec::load(&api, match_host) {
+            Ok(spec) => {
+                // The spec can reference other files now.
+                if spec.files != files {
+                    files = spec.files.clone();
+                    last_modified = modified(&files);
+                }
+                // The middleware is gone, so is the proxy.
+                if sender.send(spec).is_err() {
+                    return;
+                }
+            }
+            Err(reason) => {
+                error!("Could not reload the spec, keeping the previous one: {}", reason)
+            }
+        }
+    }
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|file| modified_at(file)).collect()
+}
+
+fn modified_at(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(unix)]
+fn listen_hangup() {
+    extern "C" fn on_hangup(_: libc::c_int) {
+        HANGUPS.fetch_add(1, Ordering::SeqCst);
+    }
+    // Adding to an atomic is all the handler does, which is safe in a signal handler.
+    unsafe {
+        libc::signal(libc::SIGHUP, on_hangup as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn listen_hangup() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs_which_fail_to_load_are_not_sent() {
+        let file = std::env::temp_dir().join(format!("oas-proxy-{}-broken.yaml", std::process::id()));
+        std::fs::write(&file, "openapi: [").unwrap();
+        let api = Api::new(&file);
+        let watcher = watch(&api, std::slice::from_ref(&file), false, false);
+        watcher.request();
+        assert!(watcher.specs.recv_timeout(Duration::from_secs(2)).is_err());
+
+        // The watcher is still there for the next reload.
+        let spec = r#"{"openapi": "3.0.0", "info": {"title": "Test", "version": "1"}, "paths": {}}"#;
+        std::fs::write(&file, spec).unwrap();
+        watcher.request();
+        let spec = watcher.specs.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(spec.files, std::slice::from_ref(&file));
+        std::fs::remove_file(file).unwrap();
+    }
+}
@@ -1,29 +1,50 @@
-use hyper::body::Body;
+use http::Extensions;
+use hyper::header::{CONTENT_TYPE, COOKIE};
+use hyper::HeaderMap;
+use percent_encoding::percent_decode_str;
 use regex::Regex;
-
-// Body
-use futures::future::Future;
-use futures::stream::Stream;
-use hyper::Chunk;
-use serde_json::Value;
+use simple_proxy::proxy::service::BufferedBody;
 
 #[derive(Debug)]
 pub struct RequestParts {
     pub path_variables: Vec<Attribute>,
     pub query_variables: Vec<Attribute>,
-    pub body: Option<Value>,
+    /// Header names are lowercase.
+    pub header_variables: Vec<Attribute>,
+    pub cookie_variables: Vec<Attribute>,
+    pub body: Option<RawBody>,
+}
+
+/// The body of the request as it was received, not parsed yet.
+#[derive(Debug)]
+pub struct RawBody {
+    /// Value of the Content-Type header, if the client sent one.
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Attribute {
     pub name: String,
+    /// The value after percent-decoding.
     pub value: String,
+    /// The value as it was received.
+    pub raw_value: String,
 }
 impl Attribute {
-    fn new(name: &str, value: &str) -> Attribute {
+    pub fn new(name: &str, value: &str) -> Attribute {
         Attribute {
             name: name.to_string(),
             value: value.to_string(),
+            raw_value: value.to_string(),
+        }
+    }
+
+    fn encoded(name: String, raw_value: &str, decode: fn(&str) -> String) -> Attribute {
+        Attribute {
+            name,
+            value: decode(raw_value),
+            raw_value: raw_value.to_string(),
         }
     }
 }
@@ -31,43 +52,42 @@ impl Attribute {
 pub type Params = Vec<Attribute>;
 
 impl RequestParts {
-    pub fn new<'a>(regex: &Regex, request: &hyper::Request<hyper::Body>) -> RequestParts {
-        let path_variables = path_variables(regex, &request.uri().path());
+    /// `variables` are the names of the path variables, in the order of the groups of `regex`.
+    pub fn new(regex: &Regex, variables: &[String], request: &hyper::Request<hyper::Body>) -> RequestParts {
+        let path_variables = path_variables(regex, variables, request.uri().path());
         let query_variables = query_variables(&request.uri().query());
-        // let body = body_variables(request.into_body());
+        let header_variables = header_variables(request.headers());
+        let cookie_variables = cookie_variables(request.headers());
+        let body = body_variables(request.headers(), request.extensions());
         RequestParts {
             path_variables,
             query_variables,
-            body: None,
+            header_variables,
+            cookie_variables,
+            body,
         }
     }
 }
 
-/// Returns a list of query params from a string
+/// Returns a list of query params from a string.
+/// Names and values are percent-decoded, flags without `=` have an empty value.
 /// # Examples
 ///
-/// ```
 ///
-/// let input = Some("user=me&role=root");
-/// let output = Some(vec![Attribute::new("user","me"), Attribute::new("role", "root")]);
-/// assert_eq!(query_variables(&input), &output);
-/// ```
+/// `user=John%20Doe&role=root&debug`
+/// [("user", "John Doe"), ("role", "root"), ("debug", "")]
 ///
-/// ```
-/// assert_eq!(query_variables(&None), &None);
-/// ```
 fn query_variables(q: &Option<&str>) -> Params {
     match q {
         None => Vec::new(),
         Some(query) => query
             .split('&')
-            // Use flat_map to filter out all malformed pairs.
-            // Using map would result in a Vec<Option<(&str, &str)>>
-            .flat_map(|pair| {
-                pair.find('=') // This returns an option, since '=' might not exist
-                    .map(|idx| pair.split_at(idx)) // split it into (&str, &str)
-                    .map(|(a, b)| Attribute::new(a, &b[1..])) // Since split includes the '=' char, remove it.
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.find('=') {
+                Some(idx) => (&pair[..idx], &pair[idx + 1..]),
+                None => (pair, ""),
             })
+            .map(|(name, value)| Attribute::encoded(decode_query(name), value, decode_query))
             .collect(),
     }
 }
@@ -79,25 +99,149 @@ fn query_variables(q: &Option<&str>) -> Params {
 /// let path = "/v1/users/username/action";
 /// let regex = ...
 /// let output = ...
-/// assert_eq!(path_variables(&regex, path), output)
+/// assert_eq!(path_variables(&regex, &variables, path), output)
+///
+///
+fn path_variables(regex: &Regex, variables: &[String], path: &str) -> Params {
+    let captures = regex.captures(path).unwrap();
+    // The first capture is the whole string.
+    variables
+        .iter()
+        .zip(captures.iter().skip(1))
+        .map(|(name, value)| {
+            let value = value.map_or("", |value| value.as_str());
+            Attribute::encoded(name.clone(), value, decode_path)
+        })
+        .collect()
+}
+
+/// Decodes a component of a query string, `+` is a space in forms.
+pub fn decode_query(component: &str) -> String {
+    decode_path(&component.replace('+', " "))
+}
+
+/// Decodes a segment of a path, invalid UTF-8 sequences are replaced.
+pub fn decode_path(segment: &str) -> String {
+    percent_decode_str(segment).decode_utf8_lossy().into_owned()
+}
+
+/// Returns a list with all the headers which have a textual value.
+/// Header names are lowercase.
+pub fn header_variables(headers: &HeaderMap) -> Params {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| Attribute::new(name.as_str(), value))
+        })
+        .collect()
+}
+
+/// Returns a list of cookies from the Cookie headers
+/// # Examples
 ///
 ///
-fn path_variables(regex: &Regex, path: &str) -> Params {
-    let captures = regex.captures(&path).unwrap();
-    regex
-        .capture_names() // None indicate unnamed captures, like the one for the whole string.
-        .filter_map(|n| n.map(|name| Attribute::new(name, captures.name(&name).unwrap().as_str())))
+/// Cookie: session=abc; theme=dark
+/// [Attribute::new("session", "abc"), Attribute::new("theme", "dark")]
+///
+fn cookie_variables(headers: &HeaderMap) -> Params {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|pair| {
+            pair.find('=')
+                .map(|idx| pair.split_at(idx))
+                .map(|(name, value)| Attribute::new(name.trim(), value[1..].trim()))
+        })
         .collect()
 }
 
-fn body_variables(body: Body) -> Option<Value> {
-    let data = body.concat2().wait().unwrap();
-    let v: Value = serde_json::from_slice(data.into_bytes().as_ref()).unwrap();
-    Some(v)
-    // match v {
-    //     Value::Array(array) => None,
-    //     Value::Object(a_map) => None,
-    //     _ => unimplemented!("BUG: This Json format is not implemented!")
-    // }
-    // None
+/// Returns the body buffered by the proxy, None when there is no body.
+/// Works for both requests and responses.
+pub fn body_variables(headers: &HeaderMap, extensions: &Extensions) -> Option<RawBody> {
+    let buffered = extensions.get::<BufferedBody>()?;
+    if buffered.0.is_empty() {
+        return None;
+    }
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    Some(RawBody {
+        content_type,
+        data: buffered.0.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(params: &Params) -> Vec<(&str, &str)> {
+        params
+            .iter()
+            .map(|attribute| (attribute.name.as_str(), attribute.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn query_variables_are_decoded() {
+        let params = query_variables(&Some("user=John%20Doe&role=root&debug"));
+        assert_eq!(
+            values(&params),
+            vec![("user", "John Doe"), ("role", "root"), ("debug", "")]
+        );
+    }
+
+    #[test]
+    fn query_variables_keep_the_raw_value() {
+        let params = query_variables(&Some("q=a+b%2Cc&&name%5B0%5D=x"));
+        assert_eq!(values(&params), vec![("q", "a b,c"), ("name[0]", "x")]);
+        assert_eq!(params[0].raw_value, "a+b%2Cc");
+    }
+
+    #[test]
+    fn path_variables_take_the_names_of_the_variables() {
+        let regex = Regex::new("^/users/(?P<_0>[^/]*)$").unwrap();
+        let params = path_variables(&regex, &["user-id".to_string()], "/users/John%20Doe");
+        assert_eq!(values(&params), vec![("user-id", "John Doe")]);
+    }
+
+    #[test]
+    fn no_query_has_no_variables() {
+        assert!(query_variables(&None).is_empty());
+    }
+
+    #[test]
+    fn cookies_are_split() {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, "session=abc; theme=dark".parse().unwrap());
+        let params = cookie_variables(&headers);
+        assert_eq!(values(&params), vec![("session", "abc"), ("theme", "dark")]);
+    }
+
+    #[test]
+    fn buffered_bodies_are_read_with_their_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let mut extensions = Extensions::new();
+        extensions.insert(BufferedBody("{}".into()));
+        let body = body_variables(&headers, &extensions).unwrap();
+        assert_eq!(body.content_type.as_deref(), Some("application/json"));
+        assert_eq!(body.data, b"{}");
+    }
+
+    #[test]
+    fn empty_or_unread_bodies_are_missing() {
+        let headers = HeaderMap::new();
+        let mut extensions = Extensions::new();
+        assert!(body_variables(&headers, &extensions).is_none());
+        extensions.insert(BufferedBody("".into()));
+        assert!(body_variables(&headers, &extensions).is_none());
+    }
 }
@@ -0,0 +1,22 @@
+use hyper::{Body, Response};
+
+use crate::request::{body_variables, header_variables, Params, RawBody};
+
+/// The parts of a backend response checked against the contract.
+#[derive(Debug)]
+pub struct ResponseParts {
+    pub status: u16,
+    /// Header names are lowercase.
+    pub headers: Params,
+    pub body: Option<RawBody>,
+}
+
+impl ResponseParts {
+    pub fn new(response: &Response<Body>) -> ResponseParts {
+        ResponseParts {
+            status: response.status().as_u16(),
+            headers: header_variables(response.headers()),
+            body: body_variables(response.headers(), response.extensions()),
+        }
+    }
+}
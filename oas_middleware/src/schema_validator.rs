@@ -0,0 +1,1087 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use lazy_static::lazy_static;
+use log::warn;
+use openapi_utils::SchemaReferenceExt;
+use openapiv3::*;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Schemas nest deeper than this only through recursive references which never
+/// reach a value, like `Node: {allOf: [$ref: Node]}`. JSON bodies are at most 128 levels deep.
+const MAX_DEPTH: usize = 512;
+
+/// Keywords whose values are data, not schemas. A `pattern` inside them is not a keyword.
+const DATA_KEYWORDS: [&str; 5] = ["example", "examples", "default", "enum", "const"];
+
+lazy_static! {
+    static ref BASE64: Regex =
+        Regex::new("^([A-Za-z0-9+/]{4})*([A-Za-z0-9+/]{3}=|[A-Za-z0-9+/]{2}==)?$")
+            .expect("Could not create base64 regex");
+    static ref EMAIL: Regex =
+        Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").expect("Could not create email regex");
+    /// The `pattern`s of the schemas, compiled when the specs are loaded.
+    /// `None` for the patterns which don't compile, they are not checked.
+    static ref PATTERNS: RwLock<HashMap<String, Option<Regex>>> = RwLock::new(HashMap::new());
+}
+
+/// Whether the value travels in a request or a response.
+/// `readOnly` properties are only allowed in responses and
+/// `writeOnly` properties only in requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Request,
+    Response,
+}
+
+/// A place in a value that does not agree with its schema.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// JSON pointer to the failing value, empty for the whole value.
+    pub pointer: String,
+    /// The schema keyword that failed, `type`, `maxLength`, `required`...
+    pub keyword: String,
+    /// What the schema expects, in words.
+    pub expected: String,
+    /// What the value was, in words.
+    pub actual: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "`#{}` should have {} but got {}",
+            self.pointer, self.expected, self.actual
+        )
+    }
+}
+
+/// Checks a JSON value against a dereferenced schema.
+/// The components are needed to follow discriminator mappings and recursive schemas.
+/// Returns all the violations found, an empty list means the value is valid.
+pub fn validate(
+    schema: &Schema,
+    value: &Value,
+    direction: Direction,
+    components: &Components,
+) -> Vec<Violation> {
+    let mut validator = Validator::new(direction, components);
+    validator.check(schema, value, "");
+    validator.violations
+}
+
+struct Validator<'a> {
+    direction: Direction,
+    components: &'a Components,
+    violations: Vec<Violation>,
+    /// Schemas being checked, to stop recursive schemas which never reach a value.
+    depth: usize,
+    /// Properties declared by the `allOf` siblings of the object being checked.
+    /// They are not additional properties of any of the siblings.
+    merged_properties: Option<(String, Vec<String>)>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(direction: Direction, components: &'a Components) -> Self {
+        Validator {
+            direction,
+            components,
+            violations: Vec::new(),
+            depth: 0,
+            merged_properties: None,
+        }
+    }
+
+    /// Runs a separate validation, used to try the alternatives of a composition.
+    fn try_schema(&self, schema: &Schema, value: &Value, pointer: &str) -> Vec<Violation> {
+        let mut validator = Validator::new(self.direction, self.components);
+        validator.merged_properties = self.merged_properties.clone();
+        validator.depth = self.depth;
+        validator.check(schema, value, pointer);
+        validator.violations
+    }
+
+    fn check(&mut self, schema: &Schema, value: &Value, pointer: &str) {
+        if self.depth >= MAX_DEPTH {
+            return;
+        }
+        self.depth += 1;
+        self.check_schema(schema, value, pointer);
+        self.depth -= 1;
+    }
+
+    fn check_schema(&mut self, schema: &Schema, value: &Value, pointer: &str) {
+        if value.is_null() {
+            // A schema without a type accepts anything, including null.
+            let typed = match &schema.schema_kind {
+                SchemaKind::Type(_) => true,
+                SchemaKind::Any(any) => any.typ.is_some(),
+                _ => false,
+            };
+            if typed && !schema.schema_data.nullable {
+                self.add(pointer, "nullable", "a non null value", describe(value));
+            }
+            if typed || schema.schema_data.nullable {
+                return;
+            }
+        }
+
+        let discriminator = schema.schema_data.discriminator.as_ref();
+        match &schema.schema_kind {
+            SchemaKind::Type(the_type) => self.check_type(the_type, value, pointer),
+            SchemaKind::Any(any) => {
+                self.check_any(any, value, pointer);
+                self.check_all_of(&any.all_of, value, pointer);
+                if !any.one_of.is_empty() {
+                    self.check_one_of(&any.one_of, discriminator, value, pointer);
+                }
+                if !any.any_of.is_empty() {
+                    self.check_any_of(&any.any_of, discriminator, value, pointer);
+                }
+                if let Some(not) = &any.not {
+                    self.check_not(not, value, pointer);
+                }
+            }
+            SchemaKind::OneOf { one_of } => {
+                self.check_one_of(one_of, discriminator, value, pointer)
+            }
+            SchemaKind::AnyOf { any_of } => {
+                self.check_any_of(any_of, discriminator, value, pointer)
+            }
+            SchemaKind::AllOf { all_of } => self.check_all_of(all_of, value, pointer),
+            SchemaKind::Not { not } => self.check_not(not, value, pointer),
+        }
+    }
+
+    /// Exactly one of the schemas must agree with the value.
+    fn check_one_of(
+        &mut self,
+        schemas: &[ReferenceOr<Schema>],
+        discriminator: Option<&Discriminator>,
+        value: &Value,
+        pointer: &str,
+    ) {
+        if let Some(discriminator) = discriminator {
+            return self.check_discriminator(discriminator, schemas, value, pointer);
+        }
+        let matching = schemas
+            .iter()
+            .filter_map(|schema| schema.resolve(self.components))
+            .filter(|schema| self.try_schema(schema, value, pointer).is_empty())
+            .count();
+        if matching != 1 {
+            let actual = format!("{} matching schemas", matching);
+            self.add(pointer, "oneOf", "exactly one matching schema", actual);
+        }
+    }
+
+    /// At least one of the schemas must agree with the value.
+    fn check_any_of(
+        &mut self,
+        schemas: &[ReferenceOr<Schema>],
+        discriminator: Option<&Discriminator>,
+        value: &Value,
+        pointer: &str,
+    ) {
+        if let Some(discriminator) = discriminator {
+            return self.check_discriminator(discriminator, schemas, value, pointer);
+        }
+        let matches = schemas
+            .iter()
+            .filter_map(|schema| schema.resolve(self.components))
+            .any(|schema| self.try_schema(schema, value, pointer).is_empty());
+        if !matches {
+            let actual = String::from("no matching schema");
+            self.add(pointer, "anyOf", "at least one matching schema", actual);
+        }
+    }
+
+    /// All the schemas must agree with the value. Properties declared in any of
+    /// them are known to the rest, so `additionalProperties: false` in one of the
+    /// schemas does not reject properties coming from its siblings.
+    fn check_all_of(&mut self, schemas: &[ReferenceOr<Schema>], value: &Value, pointer: &str) {
+        let components = self.components;
+        let schemas: Vec<&Schema> = schemas
+            .iter()
+            .filter_map(|schema| schema.resolve(components))
+            .collect();
+        if schemas.is_empty() {
+            return;
+        }
+        let mut merged = Vec::new();
+        for schema in &schemas {
+            collect_properties(schema, components, MAX_DEPTH, &mut merged);
+        }
+
+        let previous = self.merged_properties.replace((pointer.to_string(), merged));
+        for schema in schemas {
+            self.check(schema, value, pointer);
+        }
+        self.merged_properties = previous;
+    }
+
+    /// The value must not agree with the schema.
+    fn check_not(&mut self, schema: &ReferenceOr<Schema>, value: &Value, pointer: &str) {
+        if let Some(schema) = schema.resolve(self.components) {
+            if self.try_schema(schema, value, pointer).is_empty() {
+                let expected = String::from("a value not matching the `not` schema");
+                self.add(pointer, "not", &expected, describe(value));
+            }
+        }
+    }
+
+    /// The discriminator property names the schema to use, directly or through the mapping.
+    /// Only the schemas of the composition can be named.
+    fn check_discriminator(
+        &mut self,
+        discriminator: &Discriminator,
+        schemas: &[ReferenceOr<Schema>],
+        value: &Value,
+        pointer: &str,
+    ) {
+        let property = &discriminator.property_name;
+        let name = match value.get(property).and_then(Value::as_str) {
+            Some(name) => name,
+            None => {
+                let expected = format!("the discriminator property `{}`", property);
+                return self.add(pointer, "discriminator", &expected, String::from("nothing"));
+            }
+        };
+
+        let options = discriminator_options(discriminator, schemas, self.components);
+        match options.iter().find(|(option, _)| *option == name) {
+            Some((_, schema)) => self.check(schema, value, pointer),
+            None => {
+                let names: Vec<&str> = options.iter().map(|(option, _)| *option).collect();
+                let expected = format!("`{}` to be one of {}", property, names.join(", "));
+                self.add(pointer, "discriminator", &expected, format!("`{}`", name));
+            }
+        }
+    }
+
+    fn check_type(&mut self, the_type: &Type, value: &Value, pointer: &str) {
+        match the_type {
+            Type::String(string_type) => match value {
+                Value::String(string) => {
+                    let format = format_name(&string_type.format);
+                    self.check_string(string, format.as_deref(), string_type, pointer);
+                    let enumeration = string_type
+                        .enumeration
+                        .iter()
+                        .map(|item| item.clone().map_or(Value::Null, Value::String))
+                        .collect::<Vec<_>>();
+                    self.check_enum(&enumeration, value, pointer);
+                }
+                _ => self.type_mismatch("string", value, pointer),
+            },
+            Type::Number(number_type) => match value.as_f64() {
+                Some(number) => {
+                    self.check_number(number, &NumberLimits::from(number_type), pointer);
+                    if let VariantOrUnknownOrEmpty::Item(NumberFormat::Float) = number_type.format {
+                        if number.abs() > f64::from(f32::MAX) {
+                            self.add(pointer, "format", "a float", describe(value));
+                        }
+                    }
+                    let enumeration = number_type
+                        .enumeration
+                        .iter()
+                        .map(|item| item.map_or(Value::Null, Value::from))
+                        .collect::<Vec<_>>();
+                    self.check_enum(&enumeration, value, pointer);
+                }
+                None => self.type_mismatch("number", value, pointer),
+            },
+            Type::Integer(integer_type) => match as_integer(value) {
+                Some(number) => {
+                    self.check_number(number, &NumberLimits::from(integer_type), pointer);
+                    if let VariantOrUnknownOrEmpty::Item(IntegerFormat::Int32) = integer_type.format
+                    {
+                        if number < f64::from(i32::MIN) || number > f64::from(i32::MAX) {
+                            self.add(pointer, "format", "an int32 integer", describe(value));
+                        }
+                    }
+                    let enumeration = integer_type
+                        .enumeration
+                        .iter()
+                        .map(|item| item.map_or(Value::Null, Value::from))
+                        .collect::<Vec<_>>();
+                    self.check_enum(&enumeration, value, pointer);
+                }
+                None => self.type_mismatch("integer", value, pointer),
+            },
+            Type::Boolean(boolean_type) => match value {
+                Value::Bool(_) => {
+                    let enumeration = boolean_type
+                        .enumeration
+                        .iter()
+                        .map(|item| item.map_or(Value::Null, Value::Bool))
+                        .collect::<Vec<_>>();
+                    self.check_enum(&enumeration, value, pointer);
+                }
+                _ => self.type_mismatch("boolean", value, pointer),
+            },
+            Type::Object(object_type) => match value {
+                Value::Object(map) => {
+                    self.check_object(&ObjectLimits::from(object_type), map, pointer)
+                }
+                _ => self.type_mismatch("object", value, pointer),
+            },
+            Type::Array(array_type) => match value {
+                Value::Array(items) => {
+                    self.check_array(&ArrayLimits::from(array_type), items, pointer)
+                }
+                _ => self.type_mismatch("array", value, pointer),
+            },
+        }
+    }
+
+    /// Schemas mixing keywords of different types. Each keyword only
+    /// applies to values of the type it describes, as in JSON Schema.
+    fn check_any(&mut self, any: &AnySchema, value: &Value, pointer: &str) {
+        if let Some(typ) = &any.typ {
+            let matches = match typ.as_str() {
+                "string" => value.is_string(),
+                "number" => value.is_number(),
+                "integer" => as_integer(value).is_some(),
+                "boolean" => value.is_boolean(),
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                _ => true,
+            };
+            if !matches {
+                self.type_mismatch(typ, value, pointer);
+                return;
+            }
+        }
+
+        match value {
+            Value::String(string) => {
+                let string_type = StringType {
+                    pattern: any.pattern.clone(),
+                    min_length: any.min_length,
+                    max_length: any.max_length,
+                    ..StringType::default()
+                };
+                self.check_string(string, any.format.as_deref(), &string_type, pointer);
+            }
+            Value::Number(_) => {
+                let limits = NumberLimits {
+                    minimum: any.minimum,
+                    maximum: any.maximum,
+                    exclusive_minimum: any.exclusive_minimum.unwrap_or(false),
+                    exclusive_maximum: any.exclusive_maximum.unwrap_or(false),
+                    multiple_of: any.multiple_of,
+                };
+                self.check_number(value.as_f64().unwrap_or_default(), &limits, pointer);
+            }
+            Value::Object(map) => {
+                let limits = ObjectLimits {
+                    properties: &any.properties,
+                    required: &any.required,
+                    additional_properties: any.additional_properties.as_ref(),
+                    min_properties: any.min_properties,
+                    max_properties: any.max_properties,
+                };
+                self.check_object(&limits, map, pointer);
+            }
+            Value::Array(items) => {
+                let limits = ArrayLimits {
+                    items: any.items.as_ref(),
+                    min_items: any.min_items,
+                    max_items: any.max_items,
+                    unique_items: any.unique_items.unwrap_or(false),
+                };
+                self.check_array(&limits, items, pointer);
+            }
+            _ => {}
+        }
+        self.check_enum(&any.enumeration, value, pointer);
+    }
+
+    fn check_string(
+        &mut self,
+        string: &str,
+        format: Option<&str>,
+        string_type: &StringType,
+        pointer: &str,
+    ) {
+        let length = string.chars().count();
+        if let Some(min_length) = string_type.min_length {
+            if length < min_length {
+                let expected = format!("a minimum length of {}", min_length);
+                self.add(
+                    pointer,
+                    "minLength",
+                    &expected,
+                    format!("length {}", length),
+                );
+            }
+        }
+        if let Some(max_length) = string_type.max_length {
+            if length > max_length {
+                let expected = format!("a maximum length of {}", max_length);
+                self.add(
+                    pointer,
+                    "maxLength",
+                    &expected,
+                    format!("length {}", length),
+                );
+            }
+        }
+        if let Some(pattern) = &string_type.pattern {
+            if matches_pattern(pattern, string) == Some(false) {
+                let expected = format!("the pattern `{}`", pattern);
+                self.add(pointer, "pattern", &expected, format!("`{}`", string));
+            }
+        }
+        if let Some(format) = format {
+            if !is_valid_format(format, string) {
+                let expected = format!("the format {}", format);
+                self.add(pointer, "format", &expected, format!("`{}`", string));
+            }
+        }
+    }
+
+    fn check_number(&mut self, number: f64, limits: &NumberLimits, pointer: &str) {
+        if let Some(minimum) = limits.minimum {
+            if number < minimum || (limits.exclusive_minimum && number == minimum) {
+                let qualifier = if limits.exclusive_minimum {
+                    "an exclusive "
+                } else {
+                    "a "
+                };
+                let expected = format!("{}minimum of {}", qualifier, minimum);
+                self.add(pointer, "minimum", &expected, number.to_string());
+            }
+        }
+        if let Some(maximum) = limits.maximum {
+            if number > maximum || (limits.exclusive_maximum && number == maximum) {
+                let qualifier = if limits.exclusive_maximum {
+                    "an exclusive "
+                } else {
+                    "a "
+                };
+                let expected = format!("{}maximum of {}", qualifier, maximum);
+                self.add(pointer, "maximum", &expected, number.to_string());
+            }
+        }
+        if let Some(multiple_of) = limits.multiple_of {
+            let quotient = number / multiple_of;
+            if multiple_of > 0.0
+                && (quotient - quotient.round()).abs() > f64::EPSILON * quotient.abs().max(1.0)
+            {
+                let expected = format!("a multiple of {}", multiple_of);
+                self.add(pointer, "multipleOf", &expected, number.to_string());
+            }
+        }
+    }
+
+    fn check_object(&mut self, limits: &ObjectLimits, map: &Map<String, Value>, pointer: &str) {
+        let components = self.components;
+        for name in limits.required {
+            let property = limits.properties.get(name).and_then(|p| p.resolve(components));
+            if !map.contains_key(name) && self.is_allowed(property.map(|p| &p.schema_data)) {
+                let expected = format!("the required property `{}`", name);
+                self.add(pointer, "required", &expected, String::from("nothing"));
+            }
+        }
+
+        let merged = match &self.merged_properties {
+            Some((merged_pointer, names)) if merged_pointer == pointer => names.clone(),
+            _ => Vec::new(),
+        };
+        for (name, value) in map {
+            let property_pointer = format!("{}/{}", pointer, escape(name));
+            match limits.properties.get(name) {
+                Some(property) => {
+                    if let Some(property) = property.resolve(components) {
+                        if !self.is_allowed(Some(&property.schema_data)) {
+                            let keyword = match self.direction {
+                                Direction::Request => "readOnly",
+                                Direction::Response => "writeOnly",
+                            };
+                            let expected = format!("no {} property", keyword);
+                            self.add(&property_pointer, keyword, &expected, describe(value));
+                        }
+                        self.check(property, value, &property_pointer);
+                    }
+                }
+                None if merged.contains(name) => {}
+                None => match limits.additional_properties {
+                    Some(AdditionalProperties::Any(false)) => {
+                        self.add(
+                            &property_pointer,
+                            "additionalProperties",
+                            "no additional properties",
+                            describe(value),
+                        );
+                    }
+                    Some(AdditionalProperties::Schema(schema)) => {
+                        if let Some(schema) = schema.resolve(components) {
+                            self.check(schema, value, &property_pointer);
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        }
+
+        if let Some(min_properties) = limits.min_properties {
+            if map.len() < min_properties {
+                let expected = format!("a minimum of {} properties", min_properties);
+                self.add(pointer, "minProperties", &expected, map.len().to_string());
+            }
+        }
+        if let Some(max_properties) = limits.max_properties {
+            if map.len() > max_properties {
+                let expected = format!("a maximum of {} properties", max_properties);
+                self.add(pointer, "maxProperties", &expected, map.len().to_string());
+            }
+        }
+    }
+
+    fn check_array(&mut self, limits: &ArrayLimits, items: &[Value], pointer: &str) {
+        if let Some(schema) = limits.items.and_then(|items| items.resolve(self.components)) {
+            for (index, item) in items.iter().enumerate() {
+                self.check(schema, item, &format!("{}/{}", pointer, index));
+            }
+        }
+        if let Some(min_items) = limits.min_items {
+            if items.len() < min_items {
+                let expected = format!("a minimum of {} items", min_items);
+                self.add(pointer, "minItems", &expected, items.len().to_string());
+            }
+        }
+        if let Some(max_items) = limits.max_items {
+            if items.len() > max_items {
+                let expected = format!("a maximum of {} items", max_items);
+                self.add(pointer, "maxItems", &expected, items.len().to_string());
+            }
+        }
+        if limits.unique_items {
+            let repeated = items
+                .iter()
+                .enumerate()
+                .any(|(index, item)| items[..index].contains(item));
+            if repeated {
+                self.add(
+                    pointer,
+                    "uniqueItems",
+                    "unique items",
+                    String::from("repeated items"),
+                );
+            }
+        }
+    }
+
+    fn check_enum(&mut self, enumeration: &[Value], value: &Value, pointer: &str) {
+        if !enumeration.is_empty() && !enumeration.contains(value) {
+            let options: Vec<String> = enumeration.iter().map(Value::to_string).collect();
+            let expected = format!("one of {}", options.join(", "));
+            self.add(pointer, "enum", &expected, describe(value));
+        }
+    }
+
+    /// `readOnly` properties can't be sent in requests and `writeOnly` ones can't be returned.
+    fn is_allowed(&self, schema_data: Option<&SchemaData>) -> bool {
+        match (schema_data, self.direction) {
+            (Some(data), Direction::Request) => !data.read_only,
+            (Some(data), Direction::Response) => !data.write_only,
+            (None, _) => true,
+        }
+    }
+
+    fn type_mismatch(&mut self, type_name: &str, value: &Value, pointer: &str) {
+        self.add(
+            pointer,
+            "type",
+            &format!("type {}", type_name),
+            describe(value),
+        );
+    }
+
+    fn add(&mut self, pointer: &str, keyword: &str, expected: &str, actual: String) {
+        self.violations.push(Violation {
+            pointer: pointer.to_string(),
+            keyword: keyword.to_string(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+}
+
+/// Adds the names of the properties an object schema declares, looking into `allOf`.
+fn collect_properties(schema: &Schema, components: &Components, depth: usize, names: &mut Vec<String>) {
+    if depth == 0 {
+        return;
+    }
+    let (properties, all_of) = match &schema.schema_kind {
+        SchemaKind::Type(Type::Object(object)) => (&object.properties, None),
+        SchemaKind::Any(any) => (&any.properties, Some(&any.all_of)),
+        SchemaKind::AllOf { all_of } => return all_of
+            .iter()
+            .filter_map(|schema| schema.resolve(components))
+            .for_each(|schema| collect_properties(schema, components, depth - 1, names)),
+        _ => return,
+    };
+    names.extend(properties.keys().cloned());
+    for schema in all_of.into_iter().flatten().filter_map(|schema| schema.resolve(components)) {
+        collect_properties(schema, components, depth - 1, names);
+    }
+}
+
+/// Numeric keywords shared by `number` and `integer` types.
+struct NumberLimits {
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: bool,
+    exclusive_maximum: bool,
+    multiple_of: Option<f64>,
+}
+
+impl From<&NumberType> for NumberLimits {
+    fn from(number_type: &NumberType) -> Self {
+        NumberLimits {
+            minimum: number_type.minimum,
+            maximum: number_type.maximum,
+            exclusive_minimum: number_type.exclusive_minimum,
+            exclusive_maximum: number_type.exclusive_maximum,
+            multiple_of: number_type.multiple_of,
+        }
+    }
+}
+
+impl From<&IntegerType> for NumberLimits {
+    fn from(integer_type: &IntegerType) -> Self {
+        NumberLimits {
+            minimum: integer_type.minimum.map(|minimum| minimum as f64),
+            maximum: integer_type.maximum.map(|maximum| maximum as f64),
+            exclusive_minimum: integer_type.exclusive_minimum,
+            exclusive_maximum: integer_type.exclusive_maximum,
+            multiple_of: integer_type.multiple_of.map(|multiple| multiple as f64),
+        }
+    }
+}
+
+struct ObjectLimits<'a> {
+    properties: &'a indexmap::IndexMap<String, ReferenceOr<Box<Schema>>>,
+    required: &'a [String],
+    additional_properties: Option<&'a AdditionalProperties>,
+    min_properties: Option<usize>,
+    max_properties: Option<usize>,
+}
+
+impl<'a> From<&'a ObjectType> for ObjectLimits<'a> {
+    fn from(object_type: &'a ObjectType) -> Self {
+        ObjectLimits {
+            properties: &object_type.properties,
+            required: &object_type.required,
+            additional_properties: object_type.additional_properties.as_ref(),
+            min_properties: object_type.min_properties,
+            max_properties: object_type.max_properties,
+        }
+    }
+}
+
+struct ArrayLimits<'a> {
+    items: Option<&'a ReferenceOr<Box<Schema>>>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    unique_items: bool,
+}
+
+impl<'a> From<&'a ArrayType> for ArrayLimits<'a> {
+    fn from(array_type: &'a ArrayType) -> Self {
+        ArrayLimits {
+            items: array_type.items.as_ref(),
+            min_items: array_type.min_items,
+            max_items: array_type.max_items,
+            unique_items: array_type.unique_items,
+        }
+    }
+}
+
+/// The values of the discriminator and the schemas they select, the names in the mapping
+/// and the names of the components which are part of the composition.
+/// The composition is dereferenced, its schemas are compared with the components.
+fn discriminator_options<'a>(
+    discriminator: &'a Discriminator,
+    schemas: &'a [ReferenceOr<Schema>],
+    components: &'a Components,
+) -> Vec<(&'a str, &'a Schema)> {
+    let members: Vec<&Schema> = schemas
+        .iter()
+        .filter_map(|schema| schema.resolve(components))
+        .collect();
+    let component = |name: &str| {
+        components
+            .schemas
+            .get(name)
+            .and_then(|schema| schema.as_item())
+            .filter(|schema| members.contains(schema))
+    };
+
+    let mut options: Vec<(&str, &Schema)> = discriminator
+        .mapping
+        .iter()
+        .filter_map(|(value, reference)| {
+            let name = reference.rsplit('/').next().unwrap_or(reference);
+            component(name).map(|schema| (value.as_str(), schema))
+        })
+        .collect();
+    for name in components.schemas.keys() {
+        if options.iter().all(|(option, _)| option != name) {
+            if let Some(schema) = component(name) {
+                options.push((name.as_str(), schema));
+            }
+        }
+    }
+    options
+}
+
+fn format_name(format: &VariantOrUnknownOrEmpty<StringFormat>) -> Option<String> {
+    match format {
+        VariantOrUnknownOrEmpty::Item(StringFormat::Date) => Some(String::from("date")),
+        VariantOrUnknownOrEmpty::Item(StringFormat::DateTime) => Some(String::from("date-time")),
+        VariantOrUnknownOrEmpty::Item(StringFormat::Byte) => Some(String::from("byte")),
+        VariantOrUnknownOrEmpty::Item(StringFormat::Password) => Some(String::from("password")),
+        VariantOrUnknownOrEmpty::Item(StringFormat::Binary) => Some(String::from("binary")),
+        VariantOrUnknownOrEmpty::Unknown(format) => Some(format.clone()),
+        VariantOrUnknownOrEmpty::Empty => None,
+    }
+}
+
+/// Compiles the `pattern` of every schema of the spec, so values are not checked against
+/// a regex built for each of them. Returns the pointer to and the error of each pattern
+/// which can't be compiled, those patterns are not checked.
+pub fn compile_patterns(spec: &OpenAPI) -> Vec<(String, regex::Error)> {
+    let mut errors = Vec::new();
+    match serde_json::to_value(spec) {
+        Ok(value) => collect_patterns(&value, String::new(), &mut errors),
+        Err(error) => warn!("The patterns of the spec can't be compiled: {}", error),
+    }
+    errors
+}
+
+fn collect_patterns(value: &Value, pointer: String, errors: &mut Vec<(String, regex::Error)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let pointer = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                match value {
+                    Value::String(pattern) if key == "pattern" => {
+                        if let Err(error) = compile_pattern(pattern) {
+                            errors.push((pointer, error));
+                        }
+                    }
+                    _ if DATA_KEYWORDS.contains(&key.as_str()) => {}
+                    _ => collect_patterns(value, pointer, errors),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_patterns(item, format!("{}/{}", pointer, index), errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn compile_pattern(pattern: &str) -> Result<(), regex::Error> {
+    let compiled = Regex::new(pattern);
+    let error = compiled.as_ref().err().cloned();
+    if let Ok(mut patterns) = PATTERNS.write() {
+        patterns.insert(pattern.to_string(), compiled.ok());
+    }
+    error.map_or(Ok(()), Err)
+}
+
+/// Whether the string matches the pattern, `None` when the pattern does not compile.
+/// An invalid pattern is a problem of the contract, not of the value.
+fn matches_pattern(pattern: &str, string: &str) -> Option<bool> {
+    if let Some(compiled) = PATTERNS.read().ok()?.get(pattern) {
+        return compiled.as_ref().map(|regex| regex.is_match(string));
+    }
+    // Schemas which were not in a loaded spec.
+    compile_pattern(pattern).ok()?;
+    matches_pattern(pattern, string)
+}
+
+/// Formats we don't know about are always valid, as the specification mandates.
+fn is_valid_format(format: &str, string: &str) -> bool {
+    match format {
+        "date" => NaiveDate::parse_from_str(string, "%Y-%m-%d").is_ok(),
+        "date-time" => DateTime::<FixedOffset>::parse_from_rfc3339(string).is_ok(),
+        "byte" => BASE64.is_match(string),
+        "uuid" => Uuid::parse_str(string).is_ok(),
+        "email" => EMAIL.is_match(string),
+        "ipv4" => string.parse::<Ipv4Addr>().is_ok(),
+        "ipv6" => string.parse::<Ipv6Addr>().is_ok(),
+        "uri" => string
+            .parse::<http::Uri>()
+            .map(|uri| uri.scheme_part().is_some())
+            .unwrap_or(false),
+        _ => true,
+    }
+}
+
+/// JSON Schema considers 1.0 an integer.
+fn as_integer(value: &Value) -> Option<f64> {
+    value.as_f64().filter(|number| number.fract() == 0.0)
+}
+
+/// Escapes a property name to be used as a JSON pointer segment.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Object(_) => String::from("an object"),
+        Value::Array(_) => String::from("an array"),
+        Value::Null => String::from("null"),
+        Value::String(string) => format!("`{}`", string),
+        _ => format!("`{}`", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapi_utils::SpecExt;
+    use serde_json::json;
+
+    /// The schema `Root` among `schemas`, dereferenced like the specs the proxy loads.
+    fn schemas(schemas: Value) -> (Schema, Components) {
+        let spec: OpenAPI = serde_json::from_value(json!({
+            "openapi": "3.0.0",
+            "info": {"title": "Test", "version": "1"},
+            "paths": {},
+            "components": {"schemas": schemas}
+        }))
+        .unwrap();
+        let components = spec.deref_all().components.unwrap();
+        let root = components.schemas["Root"].as_item().unwrap().clone();
+        (root, components)
+    }
+
+    fn violations(schema: &Schema, components: &Components, value: Value) -> Vec<String> {
+        validate(schema, &value, Direction::Request, components)
+            .iter()
+            .map(|violation| format!("{} {}", violation.pointer, violation.keyword))
+            .collect()
+    }
+
+    fn pets() -> Value {
+        json!({
+            "Root": {
+                "oneOf": [{"$ref": "#/components/schemas/Cat"}, {"$ref": "#/components/schemas/Dog"}],
+                "discriminator": {"propertyName": "kind", "mapping": {"dog": "#/components/schemas/Dog"}}
+            },
+            "Cat": {
+                "type": "object",
+                "required": ["kind", "lives"],
+                "properties": {"kind": {"type": "string"}, "lives": {"type": "integer"}}
+            },
+            "Dog": {
+                "type": "object",
+                "required": ["kind", "bark"],
+                "properties": {"kind": {"type": "string"}, "bark": {"type": "boolean"}}
+            },
+            "Anything": {"type": "object"}
+        })
+    }
+
+    #[test]
+    fn discriminator_selects_the_mapped_schema() {
+        let (root, components) = schemas(pets());
+        assert!(violations(&root, &components, json!({"kind": "dog", "bark": true})).is_empty());
+        assert_eq!(violations(&root, &components, json!({"kind": "dog"})), vec![" required"]);
+    }
+
+    #[test]
+    fn discriminator_selects_members_by_their_name() {
+        let (root, components) = schemas(pets());
+        assert!(violations(&root, &components, json!({"kind": "Cat", "lives": 9})).is_empty());
+        assert_eq!(violations(&root, &components, json!({"kind": "Cat", "lives": "9"})), vec!["/lives type"]);
+    }
+
+    #[test]
+    fn discriminator_can_not_select_schemas_outside_the_composition() {
+        let (root, components) = schemas(pets());
+        assert_eq!(
+            violations(&root, &components, json!({"kind": "Anything"})),
+            vec![" discriminator"]
+        );
+    }
+
+    #[test]
+    fn discriminator_property_is_required() {
+        let (root, components) = schemas(pets());
+        assert_eq!(violations(&root, &components, json!({"bark": true})), vec![" discriminator"]);
+    }
+
+    #[test]
+    fn generated_examples_are_valid() {
+        use openapi_utils::{ExampleOptions, SchemaExt};
+        let (root, components) = schemas(json!({
+            "Root": {
+                "type": "object",
+                "required": ["id", "tags", "size", "tree"],
+                "properties": {
+                    "id": {"type": "string", "format": "uuid"},
+                    "email": {"type": "string", "format": "email"},
+                    "created": {"type": "string", "format": "date-time"},
+                    "birthday": {"type": "string", "format": "date"},
+                    "color": {"type": "string", "enum": ["red", "green", "blue"]},
+                    "name": {"type": "string", "minLength": 2, "maxLength": 3},
+                    "age": {"type": "integer", "minimum": 0, "maximum": 10, "exclusiveMinimum": true},
+                    "price": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "exclusiveMaximum": true,
+                        "multipleOf": 0.25
+                    },
+                    "tags": {"type": "array", "items": {"type": "string"}, "minItems": 2, "uniqueItems": true},
+                    "size": {"oneOf": [{"type": "integer"}, {"type": "boolean"}]},
+                    "owner": {"allOf": [
+                        {"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}},
+                        {"type": "object", "required": ["name"], "properties": {"name": {"type": "string"}}}
+                    ]},
+                    "secret": {"type": "string", "readOnly": true},
+                    "tree": {"$ref": "#/components/schemas/Node"}
+                },
+                "additionalProperties": false
+            },
+            "Node": {
+                "type": "object",
+                "required": ["value"],
+                "properties": {
+                    "value": {"type": "integer", "multipleOf": 3},
+                    "children": {"type": "array", "items": {"$ref": "#/components/schemas/Node"}}
+                }
+            }
+        }));
+        for seed in 0..50 {
+            let options = ExampleOptions {
+                seed,
+                skip_read_only: true,
+                ..ExampleOptions::default()
+            };
+            let value = root.example_in(&components, &options);
+            assert!(violations(&root, &components, value.clone()).is_empty(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn patterns_are_checked() {
+        let (root, components) = schemas(json!({"Root": {"type": "string", "pattern": "^[a-z]+$"}}));
+        assert!(violations(&root, &components, json!("pets")).is_empty());
+        assert_eq!(violations(&root, &components, json!("Pets")), vec![" pattern"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported_and_not_checked() {
+        let spec: OpenAPI = serde_json::from_value(json!({
+            "openapi": "3.0.0",
+            "info": {"title": "Test", "version": "1"},
+            "paths": {},
+            "components": {"schemas": {
+                "Root": {"type": "string", "pattern": "^(?=a)", "example": {"pattern": "("}}
+            }}
+        }))
+        .unwrap();
+        let errors = compile_patterns(&spec);
+        let pointers: Vec<&str> = errors.iter().map(|(pointer, _)| pointer.as_str()).collect();
+        assert_eq!(pointers, vec!["/components/schemas/Root/pattern"]);
+
+        let (root, components) = schemas(json!({"Root": {"type": "string", "pattern": "^(?=a)"}}));
+        assert!(violations(&root, &components, json!("b")).is_empty());
+    }
+
+    #[test]
+    fn formats_are_checked() {
+        let (root, components) = schemas(json!({
+            "Root": {
+                "type": "object",
+                "properties": {
+                    "email": {"type": "string", "format": "email"},
+                    "data": {"type": "string", "format": "byte"},
+                    "custom": {"type": "string", "format": "custom"}
+                }
+            }
+        }));
+        let valid = json!({"email": "a@b.io", "data": "cGV0cw==", "custom": "anything"});
+        assert!(violations(&root, &components, valid).is_empty());
+        let invalid = json!({"email": "a@b", "data": "cGV0cw="});
+        assert_eq!(violations(&root, &components, invalid), vec!["/email format", "/data format"]);
+    }
+
+    #[test]
+    fn lengths_and_limits_are_checked() {
+        let (root, components) = schemas(json!({
+            "Root": {
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string", "minLength": 2, "maxLength": 3},
+                    "age": {"type": "integer", "minimum": 0, "maximum": 30, "exclusiveMaximum": true},
+                    "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 1, "uniqueItems": true}
+                }
+            }
+        }));
+        assert!(violations(&root, &components, json!({"name": "Rex", "age": 0, "tags": ["a"]})).is_empty());
+        assert_eq!(violations(&root, &components, json!({"name": "R"})), vec!["/name minLength"]);
+        assert_eq!(violations(&root, &components, json!({"age": 30})), vec!["/age maximum"]);
+        assert_eq!(violations(&root, &components, json!({"age": -1})), vec!["/age minimum"]);
+        assert_eq!(violations(&root, &components, json!({"tags": ["a", "b"]})), vec!["/tags maxItems"]);
+    }
+
+    #[test]
+    fn required_and_additional_properties_are_checked() {
+        let (root, components) = schemas(json!({
+            "Root": {
+                "type": "object",
+                "required": ["id"],
+                "properties": {"id": {"type": "integer"}},
+                "additionalProperties": false
+            }
+        }));
+        assert!(violations(&root, &components, json!({"id": 1})).is_empty());
+        assert_eq!(violations(&root, &components, json!({})), vec![" required"]);
+        assert_eq!(
+            violations(&root, &components, json!({"id": 1, "name": "Rex"})),
+            vec!["/name additionalProperties"]
+        );
+    }
+
+    #[test]
+    fn nullable_values_accept_null() {
+        let (root, components) = schemas(json!({
+            "Root": {
+                "type": "object",
+                "properties": {
+                    "a": {"type": "string", "nullable": true},
+                    "b": {"type": "string"}
+                }
+            }
+        }));
+        assert!(violations(&root, &components, json!({"a": null})).is_empty());
+        assert_eq!(violations(&root, &components, json!({"b": null})), vec!["/b nullable"]);
+    }
+
+    #[test]
+    fn read_only_properties_are_rejected_in_requests() {
+        let (root, components) = schemas(json!({
+            "Root": {"type": "object", "properties": {"id": {"type": "integer", "readOnly": true}}}
+        }));
+        assert_eq!(violations(&root, &components, json!({"id": 1})), vec!["/id readOnly"]);
+        assert!(validate(&root, &json!({"id": 1}), Direction::Response, &components).is_empty());
+    }
+}
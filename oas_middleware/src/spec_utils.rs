@@ -1,16 +1,152 @@
-use crate::error::E;
+use crate::error::{Format, SpecError, E};
+use crate::schema_validator;
 use openapiv3::*;
-use hyper::Method;
-use log::debug;
-use serde_yaml;
+use futures::{Future, Stream};
+use hyper::{Client, Method};
+use hyper_tls::HttpsConnector;
+use log::{debug, info, warn};
+use openapi_utils::{Documents, SpecExt};
+use serde::de::DeserializeOwned;
 use std::path::Path;
+use std::time::Duration;
+use tokio::timer::Timeout;
 
-pub fn read<P: AsRef<Path>>(filename: P) -> OpenAPI {
-    let data = std::fs::read_to_string(filename).expect("OpenAPI file could not be read.");
-    let spec =
-        serde_yaml::from_str(&data).expect("Could not deserialize file as OpenAPI v3.0 yaml");
+/// How long the server of a document has to send it.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reads an OpenAPI description written in JSON or YAML with all its references resolved.
+/// The description can be split in several files or URLs, references to other documents
+/// are relative to the document they are written in. Each document is read only once.
+/// The files and URLs of the documents are returned with the description.
+pub fn read<P: AsRef<Path>>(filename: P) -> Result<(OpenAPI, Vec<String>), SpecError> {
+    let filename = filename.as_ref();
+    let root = if is_url(&filename.to_string_lossy()) {
+        filename.to_string_lossy().into_owned()
+    } else {
+        std::fs::canonicalize(filename)
+            .map_err(|source| SpecError::Io {
+                filename: filename.display().to_string(),
+                source,
+            })?
+            .display()
+            .to_string()
+    };
+
+    let (data, format) = fetch(&root)?;
+    let mut documents =
+        Documents::new(&root, parse(&data, format).map_err(|error| error.in_file(filename))?);
+    // OpenAPI 3.0 specs are checked before references are rewritten, errors keep their
+    // line and column. 3.1 and Swagger 2.0 specs are only valid once rewritten as 3.0.
+    if !documents.is_openapi_3_1() && !documents.is_swagger_2() {
+        parse::<OpenAPI>(&data, format).map_err(|error| error.in_file(filename))?;
+    }
+    loop {
+        let missing = documents.missing();
+        if missing.is_empty() {
+            break;
+        }
+        for uri in missing {
+            info!("Reading the referenced document {}", uri);
+            let (data, format) = fetch(&uri)?;
+            let document = parse(&data, format).map_err(|error| error.in_file(Path::new(&uri)))?;
+            documents.insert(&uri, document);
+        }
+    }
+
+    if documents.is_swagger_2() {
+        info!("Converting the Swagger 2.0 spec {} to OpenAPI 3.0", filename.display());
+    }
+    for keyword in documents.unsupported_keywords() {
+        warn!("The keyword {} is not supported and will not be checked", keyword);
+    }
+
+    let spec: OpenAPI = serde_path_to_error::deserialize(documents.root().clone())
+        .map_err(|error| SpecError::parse(format, None, error.path(), error.inner()).in_file(filename))?;
+    let spec = spec
+        .try_deref_all_with(&documents)
+        .map_err(|errors| SpecError::Reference {
+            filename: filename.display().to_string(),
+            errors,
+        })?;
+    for (pointer, error) in schema_validator::compile_patterns(&spec) {
+        warn!("The pattern at #{} can't be compiled and will not be checked: {}", pointer, error);
+    }
     debug!("The openapi after parsed {:?}", spec);
-    spec
+    let uris = documents.uris().into_iter().map(String::from).collect();
+    Ok((spec, uris))
+}
+
+/// Deserializes an OpenAPI document, errors point to the element which could not be read.
+pub fn parse<T: DeserializeOwned>(data: &str, format: Format) -> Result<T, SpecError> {
+    match format {
+        Format::Json => {
+            let deserializer = &mut serde_json::Deserializer::from_str(data);
+            serde_path_to_error::deserialize(deserializer).map_err(|error| {
+                let inner = error.inner();
+                let position = Some((inner.line(), inner.column()));
+                SpecError::parse(format, position, error.path(), inner)
+            })
+        }
+        Format::Yaml => {
+            let deserializer = serde_yaml::Deserializer::from_str(data);
+            serde_path_to_error::deserialize(deserializer).map_err(|error| {
+                let inner = error.inner();
+                let position = inner
+                    .location()
+                    .map(|location| (location.line(), location.column()));
+                SpecError::parse(format, position, error.path(), inner)
+            })
+        }
+    }
+}
+
+pub fn is_url(uri: &str) -> bool {
+    uri.starts_with("http://") || uri.starts_with("https://")
+}
+
+/// Contents of a document in a file or URL, and the format it is written in.
+fn fetch(uri: &str) -> Result<(String, Format), SpecError> {
+    let data = if is_url(uri) {
+        download(uri, DOWNLOAD_TIMEOUT)?
+    } else {
+        std::fs::read_to_string(uri).map_err(|source| SpecError::Io {
+            filename: uri.to_string(),
+            source,
+        })?
+    };
+    let format = Format::detect(Path::new(uri), &data);
+    Ok((data, format))
+}
+
+/// Documents are downloaded before the proxy starts or in the reload thread, never in the
+/// proxy's runtime, so blocking on a runtime of its own is fine. A server which does not
+/// send the whole document within `timeout` fails the download.
+fn download(url: &str, timeout: Duration) -> Result<String, SpecError> {
+    let error = |reason: String| SpecError::Download {
+        url: url.to_string(),
+        reason,
+    };
+    let uri: hyper::Uri = url.parse().map_err(|e: http::uri::InvalidUri| error(e.to_string()))?;
+    let connector = HttpsConnector::new(1).map_err(|e| error(e.to_string()))?;
+    let request = Client::builder().build::<_, hyper::Body>(connector).get(uri).and_then(|response| {
+        let status = response.status();
+        response.into_body().concat2().map(move |body| (status, body))
+    });
+    let mut runtime =
+        tokio::runtime::current_thread::Runtime::new().map_err(|e| error(e.to_string()))?;
+    let (status, body) = runtime
+        .block_on(Timeout::new(request, timeout))
+        .map_err(|e| {
+            if e.is_elapsed() {
+                error(format!("no answer within {} seconds", timeout.as_secs_f64()))
+            } else {
+                error(e.to_string())
+            }
+        })?;
+    if !status.is_success() {
+        return Err(error(format!("the server answered {}", status)));
+    }
+    String::from_utf8(body.to_vec()).map_err(|e| error(e.to_string()))
 }
 
 pub fn path_to_operation<'a>(
@@ -28,19 +164,23 @@ pub fn path_to_operation<'a>(
         Method::PATCH => inner(&mut item.patch),
         Method::POST => inner(&mut item.post),
         Method::PUT => inner(&mut item.put),
-        _ => unimplemented!("Method not supported"),
+        Method::TRACE => inner(&mut item.trace),
+        // Path items can't describe CONNECT or extension methods.
+        _ => Err(E::MethodError(method.to_string())),
     }
 }
 
-pub fn operation_list(item: &PathItem) -> Vec<(&str, &Operation)> {
-    let mut result = Vec::new();
-    result.push(("delete", &item.delete));
-    result.push(("get", &item.get));
-    result.push(("head", &item.head));
-    result.push(("options", &item.options));
-    result.push(("patch", &item.patch));
-    result.push(("post", &item.post));
-    result.push(("put", &item.put));
+pub fn operation_list(item: &PathItem) -> Vec<(&'static str, &Operation)> {
+    let result = [
+        ("delete", &item.delete),
+        ("get", &item.get),
+        ("head", &item.head),
+        ("options", &item.options),
+        ("patch", &item.patch),
+        ("post", &item.post),
+        ("put", &item.put),
+        ("trace", &item.trace),
+    ];
     result
         .iter()
         .filter(|(_n, o)| o.is_some())
@@ -51,3 +191,65 @@ pub fn operation_list(item: &PathItem) -> Vec<(&str, &Operation)> {
 pub fn used(description: &mut Option<String>) {
     *description = Some("1".to_string());
 }
+
+/// Finds the media type in the content map matching a Content-Type header.
+/// Exact matches are preferred over ranges like `application/*` or `*/*`.
+pub fn find_media_type<'a>(content: &'a Content, content_type: &str) -> Option<&'a MediaType> {
+    let essence = media_type_essence(content_type);
+    let range = match essence.find('/') {
+        Some(idx) => format!("{}/*", &essence[..idx]),
+        None => String::from("*/*"),
+    };
+    [essence.as_str(), range.as_str(), "*/*"].iter().find_map(|wanted| {
+        content
+            .iter()
+            .find(|(name, _)| media_type_essence(name) == *wanted)
+            .map(|(_, media)| media)
+    })
+}
+
+/// True for `application/json` and any `+json` structured syntax suffix.
+pub fn is_json(content_type: &str) -> bool {
+    let essence = media_type_essence(content_type);
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// Removes parameters like `charset` from a media type and lowercases it.
+fn media_type_essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn failed_downloads_name_the_url() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{}/openapi.yaml", port);
+        let error = read(&url).unwrap_err();
+        assert!(matches!(&error, SpecError::Download { url: failed, .. } if *failed == url));
+        assert!(error.to_string().contains(&url), "{}", error);
+    }
+
+    #[test]
+    fn servers_which_do_not_answer_time_out() {
+        // Connections wait in the backlog of the listener, nobody answers them.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        for scheme in ["http", "https"] {
+            let url = format!("{}://127.0.0.1:{}/openapi.yaml", scheme, port);
+            let error = download(&url, Duration::from_millis(200)).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("The OpenAPI document `{}` could not be downloaded: no answer within 0.2 seconds.", url)
+            );
+        }
+    }
+}
@@ -1,14 +1,47 @@
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use crate::error::Failures;
 use crate::path_finder::PathFinder;
-use crate::spec_utils;
-use openapi_utils::ReferenceOrExt;
 use openapi_utils::ParameterExt;
 
 #[derive(Serialize)]
-struct UsedSpec {
+struct UsedSpec<'a> {
     spec: HashMap<String, Vec<UsedMethod>>,
+    violations: &'a ViolationCounts,
+}
+
+/// Failures of the requests and responses which were proxied anyway, see `Options::observe`
+/// and `Options::block_responses`.
+#[derive(Debug, Default, Serialize)]
+pub struct ViolationCounts {
+    /// Requests which did not agree with the contract.
+    requests: u64,
+    /// Responses which did not agree with the contract.
+    responses: u64,
+    /// Number of failures of each type.
+    failures: BTreeMap<String, u64>,
+}
+
+impl ViolationCounts {
+    pub fn record_request(&mut self, failures: &Failures) {
+        self.requests += 1;
+        self.record(failures);
+    }
+
+    pub fn record_response(&mut self, failures: &Failures) {
+        self.responses += 1;
+        self.record(failures);
+    }
+
+    fn record(&mut self, failures: &Failures) {
+        for failure in &failures.0 {
+            *self
+                .failures
+                .entry(failure.error.type_uri().to_string())
+                .or_insert(0) += 1;
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -39,23 +72,28 @@ struct UsedParam {
     location: String,
 }
 
-pub fn render_report(builder: &PathFinder) -> String {
-    serde_json::to_string(&usage_summary(&builder))
+pub fn render_report(builders: &[&PathFinder], violations: &ViolationCounts) -> String {
+    serde_json::to_string(&usage_summary(builders, violations))
         .expect("Not possible to render usage report. This is a bug.")
 }
 
-fn usage_summary(builder: &PathFinder) -> UsedSpec {
+fn usage_summary<'a>(builders: &[&PathFinder], violations: &'a ViolationCounts) -> UsedSpec<'a> {
     let mut spec = HashMap::new();
     //let mut paths = Vec::new();
-    for path_match in &builder.path_matches {
-        //let path = path_match.path.clone();
+    let path_matches = builders
+        .iter()
+        .flat_map(|builder| builder.path_matches.iter().map(move |path_match| (builder, path_match)));
+    for (builder, path_match) in path_matches {
         let mut methods = Vec::new();
-        for (name, operation) in spec_utils::operation_list(&path_match.path) {
+        for (name, operation) in builder.operations(path_match) {
             let mut params = Vec::new();
             for parameter in &operation.parameters {
                 //  parameter_location
-                let param = parameter.to_item_ref();
-                let param_data = param.parameter_data();
+                let param = match parameter.as_item() {
+                    Some(param) => param,
+                    None => continue,
+                };
+                let param_data = param.parameter_data_ref();
                 let used = UsedParam {
                     used: is_used(&param_data.description),
                     name: param_data.name.clone(),
@@ -73,7 +111,7 @@ fn usage_summary(builder: &PathFinder) -> UsedSpec {
         }
         spec.insert(path_match.regex.to_string(), methods);
     }
-    UsedSpec { spec }
+    UsedSpec { spec, violations }
 }
 
 fn is_used(description: &Option<String>) -> bool {
@@ -1,60 +1,566 @@
 use log::debug;
 use openapiv3::*;
-use anyhow::{Context, Result};
-use openapi_utils::{ParameterExt, ParameterDataExt, ReferenceOrExt};
+use indexmap::IndexMap;
+use openapi_utils::{OperationExt, ParameterExt, ReferenceOrExt};
 
-use crate::check_type;
-use crate::error::E;
+use crate::deserializer;
+use crate::error::{Failure, Failures, E};
+use crate::options::Options;
 use crate::parts::OpenAPIParts;
-use crate::request::{Attribute, Params, RequestParts};
+use crate::request::{Params, RawBody, RequestParts};
+use crate::response::ResponseParts;
+use crate::schema_validator::{self, Direction};
 use crate::spec_utils;
+use serde_json::Value;
 
+/// Checks every part of the request, all the failures found are returned together.
+pub fn validate(
+    openapi_parts: &mut OpenAPIParts,
+    request_parts: &RequestParts,
+    options: &Options,
+) -> Result<(), Failures> {
+    let operation = &mut openapi_parts.operation;
+    let components = openapi_parts.components;
+    let mut failures = Vec::new();
 
-pub fn validate(openapi_parts: &mut OpenAPIParts, request_parts: &RequestParts) -> Result<()> {
-    let mut operation = &mut openapi_parts.operation;
+    // Specs are dereferenced when they are loaded, a reference left is a broken spec.
+    for parameter in operation.parameters.iter_mut() {
+        if let Err(error) = parameter.try_to_item_mut() {
+            failures.push(Failure::new("request", None, E::ReferenceError(error)));
+        }
+    }
 
-    validate_variables(&request_parts.path_variables, &mut operation)
-        .context("Failure in a path variable.")?;
+    validate_parameters(operation, components, request_parts, "path", true, &mut failures);
+    validate_parameters(
+        operation,
+        components,
+        request_parts,
+        "query",
+        options.strict_query,
+        &mut failures,
+    );
+    // Clients send many headers and cookies the contract does not care about.
+    validate_parameters(operation, components, request_parts, "header", false, &mut failures);
+    validate_parameters(operation, components, request_parts, "cookie", false, &mut failures);
 
-    validate_variables(&request_parts.query_variables, &mut operation)
-        .context("Failure in a query parameter.")?;
+    if let Err(error) = validate_body(operation, components, &request_parts.body) {
+        failures.push(Failure::new("body", None, error));
+    }
 
-    Ok(())
+    Failures::check(failures)
 }
 
-fn validate_variables(variables: &Params, operation: &mut Operation) -> Result<()> {
-    variables
-        .iter()
-        .map(|variable| {
-            let param = find_param(operation, &variable.name)?;
-            check_format(param, variable)
-        })
-        .collect()
+/// Checks the response of the backend, all the failures found are returned together.
+pub fn validate_response(
+    operation: &Operation,
+    components: &Components,
+    response_parts: &ResponseParts,
+) -> Result<(), Failures> {
+    let response = match operation.response(response_parts.status) {
+        Some(response) => response,
+        None => {
+            let error = E::StatusCodeError(response_parts.status);
+            return Failures::check(vec![Failure::new("status", None, error)]);
+        }
+    };
+    let mut failures = Vec::new();
+
+    validate_headers(&response.headers, components, &response_parts.headers, &mut failures);
+
+    if let Some(body) = &response_parts.body {
+        if !response.content.is_empty() {
+            if let Err(error) = validate_content(&response.content, components, body, Direction::Response) {
+                failures.push(Failure::new("body", None, error));
+            }
+        }
+    }
+
+    Failures::check(failures)
+}
+
+fn validate_headers(
+    headers: &IndexMap<String, ReferenceOr<Header>>,
+    components: &Components,
+    received: &Params,
+    failures: &mut Vec<Failure>,
+) {
+    for (name, header) in headers {
+        // The contract can't describe the Content-Type using headers.
+        let header = match header.as_item() {
+            Some(header) if !name.eq_ignore_ascii_case("content-type") => header,
+            _ => continue,
+        };
+        let result = match received.iter().find(|attribute| attribute.name.eq_ignore_ascii_case(name)) {
+            Some(attribute) => {
+                let value = deserializer::header_value(header, &attribute.value);
+                check_value(name, header_schema(header), &value, Direction::Response, components)
+            }
+            None if header.required => Err(E::RequiredError(name.to_string())),
+            None => Ok(()),
+        };
+        if let Err(error) = result {
+            failures.push(Failure::new("header", Some(name), error));
+        }
+    }
 }
 
-fn find_param<'a>(operation: &'a mut Operation, param_name: &str) -> Result<&'a ParameterData> {
-    debug!("Searching for parameter {}", param_name);
-    let mutable_params: &mut Vec<ReferenceOr<Parameter>> = operation.parameters.as_mut();
-    //operation.responses.responses
-    let a: StatusCode = StatusCode::Code(200);
+fn header_schema(header: &Header) -> Option<&Schema> {
+    match &header.format {
+        ParameterSchemaOrContent::Schema(schema) => schema.as_item(),
+        ParameterSchemaOrContent::Content(content) => media_schema(content),
+    }
+}
+
+fn validate_body(
+    operation: &Operation,
+    components: &Components,
+    body: &Option<RawBody>,
+) -> Result<(), E> {
+    let request_body = match operation.request_body.as_ref().and_then(|b| b.as_item()) {
+        Some(request_body) => request_body,
+        None => return Ok(()),
+    };
+    match body {
+        Some(body) => validate_content(&request_body.content, components, body, Direction::Request),
+        None if request_body.required => Err(E::BodyMissing),
+        None => Ok(()),
+    }
+}
+
+/// Checks the body is one of the media types in the content and agrees with its schema.
+fn validate_content(
+    content: &Content,
+    components: &Components,
+    body: &RawBody,
+    direction: Direction,
+) -> Result<(), E> {
+    let content_type = body
+        .content_type
+        .as_deref()
+        .unwrap_or("application/octet-stream");
+    let media = spec_utils::find_media_type(content, content_type)
+        .ok_or_else(|| E::MediaTypeError(content_type.to_string()))?;
+
+    // Only JSON bodies can be checked against the schema.
+    let schema = match media.schema.as_ref().and_then(|schema| schema.as_item()) {
+        Some(schema) if spec_utils::is_json(content_type) => schema,
+        _ => return Ok(()),
+    };
+    let value: Value = serde_json::from_slice(&body.data).map_err(|error| E::BodyParseError {
+        media_type: content_type.to_string(),
+        reason: error.to_string(),
+    })?;
+
+    let violations = schema_validator::validate(schema, &value, direction, components);
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(E::SchemaError(violations))
+    }
+}
+
+/// Checks the parameters of a location of the request against the contract.
+/// When `strict` is false variables not described in the contract are ignored.
+fn validate_parameters(
+    operation: &mut Operation,
+    components: &Components,
+    request_parts: &RequestParts,
+    location: &str,
+    strict: bool,
+    failures: &mut Vec<Failure>,
+) {
+    let variables = match location {
+        "path" => &request_parts.path_variables,
+        "query" => &request_parts.query_variables,
+        "header" => &request_parts.header_variables,
+        _ => &request_parts.cookie_variables,
+    };
+
+    for variable in variables {
+        let described = operation
+            .parameters
+            .iter()
+            .filter_map(|param| param.as_item())
+            .filter(|param| param.location_string() == location)
+            .any(|param| is_variable_of(param, location, &variable.name));
+        if described {
+            continue;
+        }
+        if strict {
+            let error = E::ParamError(variable.name.to_string());
+            failures.push(Failure::new(location, Some(&variable.name), error));
+            continue;
+        }
+        debug!("Ignoring undescribed {} parameter {}", location, variable.name);
+    }
+
+    let params = operation
+        .parameters
+        .iter_mut()
+        .filter_map(|parameter| parameter.try_to_item_mut().ok());
+    for param in params {
+        if param.location_string() != location || is_ignored(param) {
+            continue;
+        }
+        let result = match deserializer::parameter_value(param, request_parts) {
+            Ok(Some(value)) => {
+                debug!("Checking parameter {} {:?}", param.name(), value);
+                let param_data = param.parameter_data_ref();
+                let schema = match &param_data.format {
+                    ParameterSchemaOrContent::Schema(schema) => schema.as_item(),
+                    ParameterSchemaOrContent::Content(content) => media_schema(content),
+                };
+                check_value(&param_data.name, schema, &value, Direction::Request, components)
+            }
+            Ok(None) if param.parameter_data_ref().required => {
+                Err(E::RequiredError(param.name().to_string()))
+            }
+            Ok(None) => continue,
+            Err(error) => Err(error),
+        };
 
-    for parameter2 in mutable_params {
-        let parameter: &mut ReferenceOr<Parameter> = parameter2;
-        let param = parameter.to_item_mut();
-        let mut param_data = param.parameter_data_mut();
-        if param_data.name == param_name {
-            debug!("Used! {}", param_name);
-            param_data.description = Some("1".to_string());
-            spec_utils::used(&mut param_data.description);
-            return Ok(param_data);
+        match result {
+            Ok(()) => {
+                debug!("Used! {}", param.name());
+                spec_utils::used(&mut param.parameter_data_mut().description);
+            }
+            Err(error) => failures.push(Failure::new(location, Some(param.name()), error)),
         }
     }
-    Err(E::ParamError(param_name.to_string()))?
 }
 
+/// Checks the value of a parameter or header agrees with its schema.
+fn check_value(
+    name: &str,
+    schema: Option<&Schema>,
+    value: &Value,
+    direction: Direction,
+    components: &Components,
+) -> Result<(), E> {
+    let schema = match schema {
+        Some(schema) => schema,
+        None => return Ok(()),
+    };
+    let violations = schema_validator::validate(schema, value, direction, components);
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(E::ParamSchemaError {
+            param_name: name.to_string(),
+            violations,
+        })
+    }
+}
+
+/// Schema of the first media type of a parameter described with `content`.
+fn media_schema(content: &Content) -> Option<&Schema> {
+    content
+        .values()
+        .next()
+        .and_then(|media| media.schema.as_ref())
+        .and_then(|schema| schema.as_item())
+}
+
+/// True if a variable sent in the request is part of the parameter.
+fn is_variable_of(param: &Parameter, location: &str, variable_name: &str) -> bool {
+    match location {
+        "query" => deserializer::is_query_variable_of(param, variable_name),
+        "header" => param.name().eq_ignore_ascii_case(variable_name),
+        _ => param.name() == variable_name,
+    }
+}
+
+/// The specification mandates to ignore the Accept, Content-Type and Authorization header parameters.
+fn is_ignored(param: &Parameter) -> bool {
+    match param {
+        Parameter::Header { parameter_data, .. } => ["accept", "content-type", "authorization"]
+            .iter()
+            .any(|ignored| parameter_data.name.eq_ignore_ascii_case(ignored)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Attribute;
+    use serde_json::json;
+
+    fn operation(operation: Value) -> Operation {
+        serde_json::from_value(operation).unwrap()
+    }
+
+    fn body(content_type: &str, data: &str) -> Option<RawBody> {
+        Some(RawBody {
+            content_type: Some(content_type.to_string()),
+            data: data.as_bytes().to_vec(),
+        })
+    }
+
+    /// An operation taking a user, as JSON or as text.
+    fn create_user() -> Operation {
+        operation(json!({
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "application/json": {"schema": {
+                        "type": "object",
+                        "required": ["name"],
+                        "properties": {"name": {"type": "string"}}
+                    }},
+                    "text/plain": {"schema": {"type": "object"}}
+                }
+            },
+            "responses": {}
+        }))
+    }
+
+    fn check_body(body: Option<RawBody>) -> Result<(), E> {
+        validate_body(&create_user(), &Components::default(), &body)
+    }
+
+    #[test]
+    fn bodies_agreeing_with_the_schema_are_valid() {
+        assert!(check_body(body("application/json", r#"{"name": "John"}"#)).is_ok());
+        assert!(check_body(body("application/json; charset=utf-8", r#"{"name": "John"}"#)).is_ok());
+    }
 
-fn check_format(param: &ParameterData, request_param_data: &Attribute) -> Result<()> {
-    debug!("Checking parameter {:?}", request_param_data);
-    // TODO: Why this works? Does it work?
-    Ok(check_type::check_type(param.get_type(), request_param_data)?)
+    #[test]
+    fn bodies_breaking_the_schema_fail() {
+        match check_body(body("application/json", r#"{"name": 1}"#)) {
+            Err(E::SchemaError(violations)) => assert_eq!(violations.len(), 1),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(matches!(
+            check_body(body("application/json", "{\"name\":")),
+            Err(E::BodyParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn bodies_which_are_not_json_are_not_checked_against_the_schema() {
+        assert!(check_body(body("text/plain", "John")).is_ok());
+    }
+
+    #[test]
+    fn bodies_must_be_sent_with_a_described_media_type() {
+        assert!(matches!(
+            check_body(body("application/xml", "<name>John</name>")),
+            Err(E::MediaTypeError(ref media_type)) if media_type == "application/xml"
+        ));
+        let without_content_type = Some(RawBody {
+            content_type: None,
+            data: b"John".to_vec(),
+        });
+        assert!(matches!(check_body(without_content_type), Err(E::MediaTypeError(_))));
+    }
+
+    #[test]
+    fn required_bodies_must_be_sent() {
+        assert!(matches!(check_body(None), Err(E::BodyMissing)));
+    }
+
+    /// An operation answering a user with a required `X-Request-Id` header.
+    fn get_user() -> Operation {
+        operation(json!({
+            "responses": {"200": {
+                "description": "The user",
+                "headers": {"X-Request-Id": {"required": true, "schema": {"type": "integer"}}},
+                "content": {"application/json": {"schema": {
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": {"name": {"type": "string"}}
+                }}}
+            }}
+        }))
+    }
+
+    fn check_response(status: u16, headers: &[(&str, &str)], body: Option<RawBody>) -> Vec<Failure> {
+        let response_parts = ResponseParts {
+            status,
+            headers: headers.iter().map(|(name, value)| Attribute::new(name, value)).collect(),
+            body,
+        };
+        match validate_response(&get_user(), &Components::default(), &response_parts) {
+            Ok(()) => Vec::new(),
+            Err(failures) => failures.0,
+        }
+    }
+
+    #[test]
+    fn responses_agreeing_with_the_contract_are_valid() {
+        let user = body("application/json", r#"{"name": "John"}"#);
+        assert!(check_response(200, &[("x-request-id", "1")], user).is_empty());
+    }
+
+    #[test]
+    fn undescribed_status_codes_fail() {
+        let failures = check_response(500, &[], None);
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(failures[0].error, E::StatusCodeError(500)));
+    }
+
+    #[test]
+    fn required_response_headers_must_be_sent() {
+        let failures = check_response(200, &[], None);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name.as_deref(), Some("X-Request-Id"));
+        assert!(matches!(failures[0].error, E::RequiredError(_)));
+
+        let failures = check_response(200, &[("x-request-id", "abc")], None);
+        assert!(matches!(failures[0].error, E::ParamSchemaError { .. }));
+    }
+
+    #[test]
+    fn response_bodies_breaking_the_schema_fail() {
+        let failures = check_response(200, &[("x-request-id", "1")], body("application/json", "{}"));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].location, "body");
+        assert!(matches!(failures[0].error, E::SchemaError(_)));
+    }
+
+    fn attributes(attributes: &[(&str, &str)]) -> Params {
+        attributes.iter().map(|(name, value)| Attribute::new(name, value)).collect()
+    }
+
+    fn request(path: &[(&str, &str)], query: &[(&str, &str)], body: Option<RawBody>) -> RequestParts {
+        RequestParts {
+            path_variables: attributes(path),
+            query_variables: attributes(query),
+            header_variables: Vec::new(),
+            cookie_variables: Vec::new(),
+            body,
+        }
+    }
+
+    fn check_request(mut operation: Operation, request_parts: &RequestParts, options: &Options) -> Vec<Failure> {
+        let components = Components::default();
+        let mut openapi_parts = OpenAPIParts::new(&mut operation, &components);
+        match validate(&mut openapi_parts, request_parts, options) {
+            Ok(()) => Vec::new(),
+            Err(failures) => failures.0,
+        }
+    }
+
+    /// `PUT /users/{id}?notify=true` with a user.
+    fn update_user() -> Operation {
+        let mut operation = create_user();
+        operation.parameters = serde_json::from_value(json!([
+            {"in": "path", "name": "id", "required": true, "schema": {"type": "integer"}},
+            {"in": "query", "name": "notify", "required": true, "schema": {"type": "boolean"}}
+        ]))
+        .unwrap();
+        operation
+    }
+
+    #[test]
+    fn every_failure_of_the_request_is_returned() {
+        let user = body("application/json", r#"{"name": "John"}"#);
+        let valid = request(&[("id", "1")], &[("notify", "true")], user);
+        assert!(check_request(update_user(), &valid, &Options::default()).is_empty());
+
+        let invalid = request(&[("id", "one")], &[], body("application/json", r#"{"name": 1}"#));
+        let failures = check_request(update_user(), &invalid, &Options::default());
+        let locations: Vec<&str> = failures.iter().map(|failure| failure.location.as_str()).collect();
+        assert_eq!(locations, ["path", "query", "body"]);
+        assert!(matches!(failures[0].error, E::ParamSchemaError { .. }));
+        assert!(matches!(failures[1].error, E::RequiredError(ref name) if name == "notify"));
+        assert!(matches!(failures[2].error, E::SchemaError(_)));
+    }
+
+    /// `GET /users?limit=10&filter[name]=John`, the limit is required.
+    fn list_users() -> Operation {
+        operation(json!({
+            "parameters": [
+                {"in": "query", "name": "limit", "required": true, "schema": {"type": "integer"}},
+                {"in": "query", "name": "filter", "style": "deepObject", "schema": {
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}}
+                }}
+            ],
+            "responses": {}
+        }))
+    }
+
+    #[test]
+    fn required_parameters_must_be_sent() {
+        let failures = check_request(list_users(), &request(&[], &[], None), &Options::default());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name.as_deref(), Some("limit"));
+        assert!(matches!(failures[0].error, E::RequiredError(_)));
+    }
+
+    #[test]
+    fn undescribed_query_parameters_only_fail_in_strict_mode() {
+        let strict = Options {
+            strict_query: true,
+            ..Options::default()
+        };
+        let described = request(&[], &[("limit", "10"), ("filter[name]", "John")], None);
+        assert!(check_request(list_users(), &described, &Options::default()).is_empty());
+        assert!(check_request(list_users(), &described, &strict).is_empty());
+
+        let undescribed = request(&[], &[("limit", "10"), ("debug", "true")], None);
+        assert!(check_request(list_users(), &undescribed, &Options::default()).is_empty());
+        let failures = check_request(list_users(), &undescribed, &strict);
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].location.as_str(), failures[0].name.as_deref()), ("query", Some("debug")));
+        assert!(matches!(failures[0].error, E::ParamError(_)));
+    }
+
+    /// `GET /me` with a required `X-Request-Id` header and `session` cookie.
+    fn get_me() -> Operation {
+        operation(json!({
+            "parameters": [
+                {"in": "header", "name": "X-Request-Id", "required": true, "schema": {"type": "integer"}},
+                {"in": "cookie", "name": "session", "required": true, "schema": {"type": "string", "minLength": 4}},
+                {"in": "header", "name": "Accept", "required": true, "schema": {"type": "integer"}},
+                {"in": "header", "name": "Content-Type", "required": true, "schema": {"type": "integer"}},
+                {"in": "header", "name": "Authorization", "required": true, "schema": {"type": "integer"}}
+            ],
+            "responses": {}
+        }))
+    }
+
+    /// Header names are lowercase, like the ones `header_variables` reads.
+    fn sent(headers: &[(&str, &str)], cookies: &[(&str, &str)]) -> RequestParts {
+        RequestParts {
+            header_variables: attributes(headers),
+            cookie_variables: attributes(cookies),
+            ..request(&[], &[], None)
+        }
+    }
+
+    #[test]
+    fn header_and_cookie_parameters_are_checked() {
+        let valid = sent(&[("x-request-id", "1"), ("user-agent", "curl")], &[("session", "abcd"), ("theme", "dark")]);
+        assert!(check_request(get_me(), &valid, &Options::default()).is_empty());
+
+        let failures = check_request(get_me(), &sent(&[], &[]), &Options::default());
+        let names: Vec<(&str, Option<&str>)> = failures
+            .iter()
+            .map(|failure| (failure.location.as_str(), failure.name.as_deref()))
+            .collect();
+        assert_eq!(names, [("header", Some("X-Request-Id")), ("cookie", Some("session"))]);
+        assert!(failures.iter().all(|failure| matches!(failure.error, E::RequiredError(_))));
+
+        let invalid = sent(&[("x-request-id", "one")], &[("session", "abc")]);
+        let failures = check_request(get_me(), &invalid, &Options::default());
+        assert_eq!(failures.len(), 2);
+        assert!(failures.iter().all(|failure| matches!(failure.error, E::ParamSchemaError { .. })));
+    }
+
+    #[test]
+    fn accept_content_type_and_authorization_parameters_are_ignored() {
+        let headers = [
+            ("x-request-id", "1"),
+            ("accept", "application/json"),
+            ("content-type", "text/plain"),
+            ("authorization", "Bearer secret"),
+        ];
+        let request_parts = sent(&headers, &[("session", "abcd")]);
+        assert!(check_request(get_me(), &request_parts, &Options::default()).is_empty());
+        let request_parts = sent(&headers[..1], &[("session", "abcd")]);
+        assert!(check_request(get_me(), &request_parts, &Options::default()).is_empty());
+    }
 }
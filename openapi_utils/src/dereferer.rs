@@ -1,11 +1,20 @@
+use crate::documents::Documents;
 use crate::error::DerefError;
+use crate::parameter::ParameterExt;
 use crate::reference::ReferenceOrExt;
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::String;
 use alloc::string::ToString;
+use alloc::vec;
 use alloc::vec::Vec;
+use core::mem;
 use indexmap::IndexMap;
 use openapiv3::*;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+const SCHEMAS: &str = "#/components/schemas/";
 
 /// Extends an openapi spec with a method to dereference all its contents
 pub trait SpecExt {
@@ -16,239 +25,398 @@ pub trait SpecExt {
     /// to access the data. But these methods will always succeed as all
     /// references have been resolved ahead of time.
     ///
+    /// Recursive schemas are the exception, the reference which closes the cycle is kept
+    /// and points to a schema in `components`. Recursive schemas which are not in
+    /// `components` are added to it. Use `SchemaReferenceExt::resolve` to follow them.
+    ///
     /// # Panics
     /// This method will panic if the referenced item is not present in the OpenAPI description.
-    /// This method will panic AdditionalProperties
+    /// Use `try_deref_all` to get the list of problems instead.
     ///
     /// # Example
     ///
     /// ```
-    ///   let data = std::fs::read_to_string(filename).expect("OpenAPI file could not be read.");
-    ///   let deser = serde_yaml::from_str(&data).expect("Could not deserialize file as OpenAPI v3.0");
-    ///   let spec = spec::read(&deser).deref_all();
-    ///   let paths = spec.paths;
+    /// use openapi_utils::SpecExt;
+    /// use openapiv3::OpenAPI;
+    ///
+    /// let data = r##"{
+    ///     "openapi": "3.0.0",
+    ///     "info": {"title": "Users", "version": "1"},
+    ///     "paths": {"/users": {"get": {
+    ///         "parameters": [{"$ref": "#/components/parameters/limit"}],
+    ///         "responses": {"200": {"description": "OK"}}
+    ///     }}},
+    ///     "components": {"parameters": {
+    ///         "limit": {"name": "limit", "in": "query", "schema": {"type": "integer"}}
+    ///     }}
+    /// }"##;
+    /// let spec: OpenAPI = serde_json::from_str(data).expect("Could not deserialize as OpenAPI v3.0");
+    /// let spec = spec.deref_all();
+    /// let users = spec.paths.paths["/users"].as_item().unwrap();
+    /// assert!(users.get.as_ref().unwrap().parameters[0].as_item().is_some());
     /// ```
     fn deref_all(self) -> OpenAPI;
+
+    /// Dereferences all the $ref references, including the ones pointing to other documents.
+    /// The documents must contain the spec as their root and every document it references,
+    /// the spec must be deserialized from `documents.root()`.
+    ///
+    /// # Panics
+    /// This method will panic if a referenced item is not present in the documents.
+    fn deref_all_with(self, documents: &Documents) -> OpenAPI;
+
+    /// Like `deref_all`, but returns every reference which could not be resolved
+    /// instead of panicking on the first one.
+    fn try_deref_all(self) -> Result<OpenAPI, Vec<DerefError>>;
+
+    /// Like `deref_all_with`, but returns every reference which could not be resolved
+    /// instead of panicking on the first one.
+    fn try_deref_all_with(self, documents: &Documents) -> Result<OpenAPI, Vec<DerefError>>;
 }
 
 impl SpecExt for OpenAPI {
     /// Dereferences all the internal references in a document by copying
     /// the items in the place of the references.
-    fn deref_all(mut self) -> OpenAPI {
-        if let Some(comp) = self.components.as_ref() {
-            for (_, path_item) in &mut self.paths.paths {
-                deref_everything_in_path(path_item, comp);
+    fn deref_all(self) -> OpenAPI {
+        self.try_deref_all().unwrap_or_else(|errors| panic_with(&errors))
+    }
+
+    fn deref_all_with(self, documents: &Documents) -> OpenAPI {
+        self.try_deref_all_with(documents)
+            .unwrap_or_else(|errors| panic_with(&errors))
+    }
+
+    fn try_deref_all(self) -> Result<OpenAPI, Vec<DerefError>> {
+        let document = serde_json::to_value(&self).expect("OpenAPI can always be serialized.");
+        let documents = Documents::new("", document);
+        self.try_deref_all_with(&documents)
+    }
+
+    fn try_deref_all_with(mut self, documents: &Documents) -> Result<OpenAPI, Vec<DerefError>> {
+        let names = match &self.components {
+            Some(components) => components.schemas.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+        let mut dereferer = Dereferer::new(documents, names);
+
+        for (_, path_item) in &mut self.paths.paths {
+            dereferer.deref_everything_in_path(path_item);
+        }
+        // Schemas in components are dereferenced as well, they are the targets
+        // of discriminator mappings and of recursive references.
+        if let Some(components) = self.components.as_mut() {
+            for (name, schema) in components.schemas.iter_mut() {
+                dereferer.expanding = vec![component_reference(name)];
+                dereferer.set_deref_schema(schema);
             }
         }
-        //    println!("{:?}", spec);
-        self
+        // Recursive schemas found in other places are added to the components.
+        let mut index = 0;
+        while let Some((reference, name)) = dereferer.recursive.get_index(index) {
+            let (name, mut schema) = (name.clone(), ReferenceOr::ref_(reference));
+            dereferer.expanding.clear();
+            dereferer.set_deref_schema(&mut schema);
+            if let ReferenceOr::Item(_) = schema {
+                let components = self.components.get_or_insert_with(Components::default);
+                components.schemas.insert(name, schema);
+            }
+            index += 1;
+        }
+        if dereferer.errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(dereferer.errors)
+        }
     }
 }
 
-fn deref_everything_in_path(path_item: &mut ReferenceOr<PathItem>, components: &Components) {
-    let p_item = path_item.to_item_mut(); //TODO: no Deref possible? Where in components?
-    set_deref_all_params(&mut p_item.parameters, components);
+fn panic_with(errors: &[DerefError]) -> ! {
+    let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+    panic!("No Reference found! {}", errors.join(", "))
+}
 
-    let p_item2 = p_item.clone(); // hack as things are used, etc.
+struct Dereferer<'a> {
+    documents: &'a Documents,
+    /// References to the schemas being inlined, a reference to one of them is recursive.
+    expanding: Vec<String>,
+    /// Recursive schemas outside `components`, and the name they get in `components`.
+    recursive: IndexMap<String, String>,
+    /// Names of the schemas in `components`.
+    names: Vec<String>,
+    /// Every problem found, each one only once.
+    errors: Vec<DerefError>,
+}
 
-    for operation in operation_list(p_item) {
-        // inline params
-        set_deref_all_params(&mut operation.parameters, components);
-        // Move from path level params to each operation so it is easier later
-        for param in &p_item2.parameters {
-            operation.parameters.push(param.clone());
+impl<'a> Dereferer<'a> {
+    fn new(documents: &'a Documents, names: Vec<String>) -> Self {
+        Dereferer {
+            documents,
+            expanding: Vec::new(),
+            recursive: IndexMap::new(),
+            names,
+            errors: Vec::new(),
         }
+    }
 
-        // inline request body
-        if operation.request_body.is_some() {
-            let req_body = operation.request_body.as_mut().unwrap();
-            set_deref(req_body, &components.request_bodies, &mut Vec::new());
-            let body: &mut RequestBody = req_body.to_item_mut();
-            for (_, media) in &mut body.content {
-                if media.schema.is_none() {
-                    continue;
-                }
-                let schema = media.schema.as_mut().unwrap();
-                let mut referred = Vec::new();
-                set_deref(schema, &components.schemas, &mut referred);
-                set_defer_schema_contents(schema.to_item_mut(), components, 10, &mut referred);
-            }
+    fn error(&mut self, error: DerefError) {
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
         }
+    }
+
+    fn deref_everything_in_path(&mut self, path_item: &mut ReferenceOr<PathItem>) {
+        self.set_deref(path_item);
+        let p_item = match path_item {
+            ReferenceOr::Item(p_item) => p_item,
+            ReferenceOr::Reference { .. } => return,
+        };
+        self.set_deref_all_params(&mut p_item.parameters);
 
-        // inline responses
-        for (_status_code, response) in &mut operation.responses.responses {
-            set_deref(response, &components.responses, &mut Vec::new());
-            for (_name, header) in &mut response.to_item_mut().headers {
-                set_deref(header, &components.headers, &mut Vec::new());
+        let p_item2 = p_item.clone(); // hack as things are used, etc.
+
+        for operation in operation_list(p_item) {
+            // inline params
+            self.set_deref_all_params(&mut operation.parameters);
+            // Move from path level params to each operation so it is easier later
+            for param in &p_item2.parameters {
+                operation.parameters.push(param.clone());
             }
-            for (_, media) in &mut response.to_item_mut().content {
-                if media.schema.is_none() {
-                    continue;
+
+            // inline request body
+            if let Some(req_body) = operation.request_body.as_mut() {
+                self.set_deref(req_body);
+                if let ReferenceOr::Item(req_body) = req_body {
+                    self.set_deref_content(&mut req_body.content);
                 }
-                let schema = media.schema.as_mut().unwrap();
-                let mut referred = Vec::new();
-                set_deref(schema, &components.schemas, &mut referred);
-                set_defer_schema_contents(schema.to_item_mut(), components, 10, &mut referred);
             }
-        }
-    }
-}
 
-fn set_defer_schema_contents(
-    schema: &mut Schema,
-    components: &Components,
-    recursion: i32,
-    referred: &mut Vec<String>,
-) {
-    if recursion == 0 {
-        return;
-    }
-    match &mut schema.schema_kind {
-        SchemaKind::Type(schema_type) => match schema_type {
-            Type::Object(object) => {
-                for (_name, property) in &mut object.properties {
-                    set_deref_box(property, &components.schemas, referred);
-                    set_defer_schema_contents(
-                        property.to_item_mut(),
-                        components,
-                        recursion - 1,
-                        referred,
-                    );
+            // inline responses
+            let default_response = operation.responses.default.iter_mut();
+            for response in operation.responses.responses.values_mut().chain(default_response) {
+                self.set_deref(response);
+                let response = match response {
+                    ReferenceOr::Item(response) => response,
+                    ReferenceOr::Reference { .. } => continue,
+                };
+                for (_name, header) in &mut response.headers {
+                    self.set_deref(header);
+                    if let ReferenceOr::Item(header) = header {
+                        self.set_deref_format(&mut header.format);
+                    }
                 }
+                self.set_deref_content(&mut response.content);
             }
-            Type::Array(array) => match &mut array.items {
-                None => {}
-                Some(items) => {
-                    set_deref_box(items, &components.schemas, referred);
-                    set_defer_schema_contents(
-                        items.to_item_mut(),
-                        components,
-                        recursion - 1,
-                        referred,
-                    );
-                }
-            },
-            _ => {}
-        },
+        }
+    }
 
-        SchemaKind::OneOf { ref mut one_of } => {
-            for sch in &mut one_of.iter_mut() {
-                set_deref(sch, &components.schemas, referred);
-                set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
+    fn set_deref_all_params(&mut self, parameters: &mut [ReferenceOr<Parameter>]) {
+        for parameter in parameters.iter_mut() {
+            self.set_deref(parameter);
+            if let ReferenceOr::Item(parameter) = parameter {
+                self.set_deref_format(&mut parameter.parameter_data_mut().format);
             }
         }
-        SchemaKind::AnyOf { ref mut any_of } => {
-            for sch in &mut any_of.iter_mut() {
-                set_deref(sch, &components.schemas, referred);
-                set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
-            }
+    }
+
+    fn set_deref_format(&mut self, format: &mut ParameterSchemaOrContent) {
+        match format {
+            ParameterSchemaOrContent::Schema(schema) => self.set_deref_schema(schema),
+            ParameterSchemaOrContent::Content(content) => self.set_deref_content(content),
         }
-        SchemaKind::AllOf { ref mut all_of } => {
-            for sch in &mut all_of.iter_mut() {
-                set_deref(sch, &components.schemas, referred);
-                set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
+    }
+
+    fn set_deref_content(&mut self, content: &mut Content) {
+        for (_, media) in content {
+            if let Some(schema) = media.schema.as_mut() {
+                self.set_deref_schema(schema);
             }
         }
-        SchemaKind::Not { ref mut not } => {
-            let sch = not;
-            set_deref(sch, &components.schemas, referred);
-            set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
+    }
+
+    /// Inlines the schema and everything inside it.
+    /// References to a schema which is being inlined are recursive, they are
+    /// left as references to `components`.
+    fn set_deref_schema(&mut self, item: &mut ReferenceOr<Schema>) {
+        let reference = match item {
+            ReferenceOr::Reference { reference } => reference.clone(),
+            ReferenceOr::Item(schema) => return self.set_defer_schema_contents(schema),
+        };
+        let (target, schema) = match find_reference(&reference, self.documents) {
+            Ok(found) => found,
+            Err(error) => return self.error(error),
+        };
+        if self.expanding.contains(&target) {
+            *item = ReferenceOr::ref_(&self.recursive_reference(&target));
+            return;
         }
-        SchemaKind::Any(schema) => {
-            for (_name, property) in &mut schema.properties {
-                set_deref_box(property, &components.schemas, referred);
-                set_defer_schema_contents(property.to_item_mut(), components, recursion - 1, referred);
-            }
-            if schema.items.is_some() {
-                let the_items = schema.items.as_mut().unwrap();
-                set_deref_box(the_items, &components.schemas, referred);
-                set_defer_schema_contents(the_items.to_item_mut(), components, recursion - 1, referred);
+        *item = ReferenceOr::Item(schema);
+        self.expanding.push(target);
+        self.set_defer_schema_contents(item.to_item_mut());
+        self.expanding.pop();
+    }
+
+    fn set_deref_box(&mut self, item: &mut ReferenceOr<Box<Schema>>) {
+        let mut unboxed = match mem::replace(item, ReferenceOr::ref_("")) {
+            ReferenceOr::Reference { reference } => ReferenceOr::Reference { reference },
+            ReferenceOr::Item(schema) => ReferenceOr::Item(*schema),
+        };
+        self.set_deref_schema(&mut unboxed);
+        *item = match unboxed {
+            ReferenceOr::Reference { reference } => ReferenceOr::Reference { reference },
+            ReferenceOr::Item(schema) => ReferenceOr::Item(Box::new(schema)),
+        };
+    }
+
+    fn set_defer_schema_contents(&mut self, schema: &mut Schema) {
+        match &mut schema.schema_kind {
+            SchemaKind::Type(schema_type) => match schema_type {
+                Type::Object(object) => {
+                    for (_name, property) in &mut object.properties {
+                        self.set_deref_box(property);
+                    }
+                    if let Some(AdditionalProperties::Schema(schema)) =
+                        &mut object.additional_properties
+                    {
+                        self.set_deref_schema(schema);
+                    }
+                }
+                Type::Array(array) => {
+                    if let Some(items) = &mut array.items {
+                        self.set_deref_box(items);
+                    }
+                }
+                _ => {}
+            },
+
+            SchemaKind::OneOf { ref mut one_of } => {
+                for sch in &mut one_of.iter_mut() {
+                    self.set_deref_schema(sch);
+                }
             }
-            for sch in &mut schema.one_of.iter_mut() {
-                set_deref(sch, &components.schemas, referred);
-                set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
+            SchemaKind::AnyOf { ref mut any_of } => {
+                for sch in &mut any_of.iter_mut() {
+                    self.set_deref_schema(sch);
+                }
             }
-            for sch in &mut schema.any_of.iter_mut() {
-                set_deref(sch, &components.schemas, referred);
-                set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
+            SchemaKind::AllOf { ref mut all_of } => {
+                for sch in &mut all_of.iter_mut() {
+                    self.set_deref_schema(sch);
+                }
             }
-            for sch in &mut schema.all_of.iter_mut() {
-                set_deref(sch, &components.schemas, referred);
-                set_defer_schema_contents(sch.to_item_mut(), components, recursion - 1, referred);
+            SchemaKind::Not { ref mut not } => self.set_deref_schema(not),
+            SchemaKind::Any(schema) => {
+                for (_name, property) in &mut schema.properties {
+                    self.set_deref_box(property);
+                }
+                if let Some(AdditionalProperties::Schema(schema)) =
+                    &mut schema.additional_properties
+                {
+                    self.set_deref_schema(schema);
+                }
+                if let Some(the_items) = &mut schema.items {
+                    self.set_deref_box(the_items);
+                }
+                if let Some(not) = &mut schema.not {
+                    self.set_deref_schema(not);
+                }
+                for sch in &mut schema.one_of.iter_mut() {
+                    self.set_deref_schema(sch);
+                }
+                for sch in &mut schema.any_of.iter_mut() {
+                    self.set_deref_schema(sch);
+                }
+                for sch in &mut schema.all_of.iter_mut() {
+                    self.set_deref_schema(sch);
+                }
             }
         }
     }
-}
 
-fn set_deref_all_params(parameters: &mut [ReferenceOr<Parameter>], components: &Components) {
-    for parameter in parameters.iter_mut() {
-        set_deref(parameter, &components.parameters, &mut Vec::new());
+    fn set_deref<T>(&mut self, item: &mut ReferenceOr<T>)
+    where
+        T: DeserializeOwned,
+    {
+        if let ReferenceOr::Reference { reference } = item {
+            match find_reference(reference, self.documents) {
+                Ok((_, p)) => *item = ReferenceOr::Item(p),
+                Err(error) => self.error(error),
+            }
+        }
     }
-}
 
-fn set_deref<T>(
-    item: &mut ReferenceOr<T>,
-    component_type: &IndexMap<String, ReferenceOr<T>>,
-    referred: &mut Vec<String>,
-) where
-    T: Clone,
-{
-    match item {
-        ReferenceOr::Reference { reference } => {
-            let p = find_reference(reference, component_type, referred)
-                .expect("No Reference found!");
-            *item = ReferenceOr::Item(p);
+    /// The reference to use for a recursive schema, schemas in the components of the
+    /// root document are used as they are, others are added to the components.
+    fn recursive_reference(&mut self, target: &str) -> String {
+        if let Some(name) = target.strip_prefix(SCHEMAS) {
+            if !name.contains('/') {
+                return target.to_string();
+            }
+        }
+        if let Some(name) = self.recursive.get(target) {
+            return component_reference(name);
         }
-        ReferenceOr::Item(_) => {}
-    }
-}
 
-fn set_deref_box<T>(
-    item: &mut ReferenceOr<Box<T>>,
-    component_type: &IndexMap<String, ReferenceOr<T>>,
-    referred: &mut Vec<String>,
-) where
-    T: Clone,
-{
-    match item {
-        ReferenceOr::Reference { reference } => {
-            let p = find_reference(reference, component_type, referred)
-                .expect("No Reference found!");
-            *item = ReferenceOr::Item(Box::new(p));
+        // `schemas/user.yaml#/User` is named `User`, `node.yaml#` is named `node`.
+        let (document, pointer) = target.split_once('#').unwrap_or((target, ""));
+        let last = match pointer.rsplit('/').next() {
+            Some(segment) if !segment.is_empty() => segment.replace("~1", "/").replace("~0", "~"),
+            _ => {
+                let file = document.rsplit('/').next().unwrap_or(document);
+                file.split('.').next().unwrap_or(file).to_string()
+            }
+        };
+        let base: String = last
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+        let mut name = base.clone();
+        let mut count = 1;
+        while self.names.contains(&name) {
+            count += 1;
+            name = format!("{}_{}", base, count);
         }
-        ReferenceOr::Item(_) => {}
+        self.names.push(name.clone());
+        self.recursive.insert(target.to_string(), name.clone());
+        component_reference(&name)
     }
 }
 
-fn find_reference<T>(
-    reference: &str,
-    component_type: &IndexMap<String, ReferenceOr<T>>,
-    referred: &mut Vec<String>,
-) -> Result<T, DerefError>
+/// `#/components/schemas/Name` with the name escaped as a JSON Pointer.
+fn component_reference(name: &str) -> String {
+    format!("{}{}", SCHEMAS, name.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Resolves the JSON Pointer of the reference in its document.
+/// References to references are followed, the reference of the item found is returned with it.
+fn find_reference<T>(reference: &str, documents: &Documents) -> Result<(String, T), DerefError>
 where
-    T: Clone,
+    T: DeserializeOwned,
 {
-    let reference_name: &str = reference.rsplit('/').next().unwrap();
-
-    let ref_item = component_type
-        .get(reference_name)
-        .ok_or(DerefError::ReferenceError {
-            name: reference_name.to_string(),
-        })?;
-
-    match ref_item {
-        ReferenceOr::Reference { reference } => {
-            if referred.iter().any(|v| v == reference_name) {
-                unimplemented!("Circular references are not supported")
-            } else {
-                referred.push(reference_name.to_string());
-                let p = find_reference(reference, component_type, referred)
-                    .expect("No Reference found!");
-                Ok(p)
-            }
+    let mut chain = vec![reference.to_string()];
+    let mut ref_item = documents.resolve(reference)?;
+    while let Some(Value::String(next)) = ref_item.get("$ref") {
+        if chain.contains(next) {
+            return Err(DerefError::CircularReference {
+                name: reference.to_string(),
+            });
         }
-        ReferenceOr::Item(item) => Ok(item.clone()),
+        chain.push(next.to_string());
+        ref_item = documents.resolve(next)?;
     }
+
+    let target = chain.pop().unwrap_or_default();
+    let item = serde_json::from_value(ref_item.clone()).map_err(|_| DerefError::TypeError {
+        name: reference.to_string(),
+        expected: type_name::<T>(),
+    })?;
+    Ok((target, item))
+}
+
+/// `openapiv3::schema::Schema` is a `Schema`.
+fn type_name<T>() -> String {
+    let name = core::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name).to_string()
 }
 
 fn operation_list<'a>(item: &'a mut PathItem) -> Vec<&'a mut Operation> {
@@ -268,3 +436,132 @@ fn operation_list<'a>(item: &'a mut PathItem) -> Vec<&'a mut Operation> {
     pusher(&mut item.trace);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference::SchemaReferenceExt;
+    use alloc::vec;
+    use serde_json::json;
+
+    fn spec(paths: Value, components: Value) -> OpenAPI {
+        let spec = json!({
+            "openapi": "3.0.0",
+            "info": {"title": "Tree", "version": "1"},
+            "paths": paths,
+            "components": components
+        });
+        serde_json::from_value(spec).unwrap()
+    }
+
+    fn properties(schema: &Schema) -> &IndexMap<String, ReferenceOr<Box<Schema>>> {
+        match &schema.schema_kind {
+            SchemaKind::Type(Type::Object(object)) => &object.properties,
+            kind => panic!("{:?} is not an object", kind),
+        }
+    }
+
+    fn items(schema: &Schema) -> &ReferenceOr<Box<Schema>> {
+        match &schema.schema_kind {
+            SchemaKind::Type(Type::Array(array)) => array.items.as_ref().unwrap(),
+            kind => panic!("{:?} is not an array", kind),
+        }
+    }
+
+    #[test]
+    fn recursive_schemas_keep_a_reference_to_the_components() {
+        let paths = json!({"/nodes": {"get": {"responses": {"200": {
+            "description": "The tree",
+            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Node"}}}
+        }}}}});
+        let components = json!({"schemas": {"Node": {
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "children": {"type": "array", "items": {"$ref": "#/components/schemas/Node"}}
+            }
+        }}});
+        let spec = spec(paths, components).deref_all();
+        let components = spec.components.as_ref().unwrap();
+
+        let operation = spec.paths.paths["/nodes"].as_item().unwrap().get.as_ref().unwrap();
+        let response = operation.responses.responses[&StatusCode::Code(200)].as_item().unwrap();
+        let node = response.content["application/json"].schema.as_ref().unwrap();
+        let node = node.as_item().expect("The first level is inlined");
+        let children = properties(node)["children"].as_item().unwrap();
+        let child = items(children);
+        assert_eq!(
+            child,
+            &ReferenceOr::Reference {
+                reference: String::from("#/components/schemas/Node")
+            }
+        );
+
+        let child = child.resolve(components).expect("The reference can be followed");
+        assert!(properties(child).contains_key("name"));
+        let grandchild = items(properties(child)["children"].as_item().unwrap());
+        assert_eq!(grandchild.resolve(components), Some(child));
+        assert_eq!(vec!["Node"], components.schemas.keys().collect::<Vec<_>>());
+    }
+
+    fn reference_error(name: &str) -> DerefError {
+        DerefError::ReferenceError {
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn every_broken_reference_is_returned() {
+        let paths = json!({"/users": {"get": {
+            "parameters": [{"$ref": "#/components/parameters/limit"}],
+            "responses": {
+                "200": {"$ref": "#/components/responses/Users"},
+                "404": {"description": "Missing", "content": {"application/json": {
+                    "schema": {"$ref": "#/components/schemas/Problem"}
+                }}}
+            }
+        }}});
+        let errors = spec(paths.clone(), json!({})).try_deref_all().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                reference_error("#/components/parameters/limit"),
+                reference_error("#/components/responses/Users"),
+                reference_error("#/components/schemas/Problem"),
+            ]
+        );
+
+        let spec = spec(paths, json!({"schemas": {"Problem": {"$ref": "problem.json#/Problem"}}}));
+        let documents = Documents::new("/specs/api.json", serde_json::to_value(spec).unwrap());
+        let spec: OpenAPI = serde_json::from_value(documents.root().clone()).unwrap();
+        let errors = spec.try_deref_all_with(&documents).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                reference_error("#/components/parameters/limit"),
+                reference_error("#/components/responses/Users"),
+                reference_error("/specs/problem.json#/Problem"),
+            ]
+        );
+    }
+
+    #[test]
+    fn references_which_only_lead_to_references_are_circular() {
+        let paths = json!({"/users": {"get": {"responses": {"200": {
+            "description": "The users",
+            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Users"}}}
+        }}}}});
+        let components = json!({"schemas": {
+            "Users": {"$ref": "#/components/schemas/People"},
+            "People": {"$ref": "#/components/schemas/Users"}
+        }});
+        let errors = spec(paths, components).try_deref_all().unwrap_err();
+        assert!(!errors.is_empty());
+        assert!(errors
+            .iter()
+            .all(|error| matches!(error, DerefError::CircularReference { .. })));
+        assert!(errors.contains(&DerefError::CircularReference {
+            name: String::from("#/components/schemas/Users")
+        }));
+    }
+}
@@ -0,0 +1,380 @@
+use crate::error::DerefError;
+use crate::openapi31;
+use crate::swagger2;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde_json::Value;
+
+/// The documents an OpenAPI description is split into.
+///
+/// The root document and every document it references, directly or through
+/// other documents, are kept by their URI so each one is loaded only once.
+/// References to other documents are rewritten to absolute URIs when a document
+/// is added, so they can be resolved no matter which document they appear in.
+/// References inside the root document keep their local `#/...` form.
+///
+/// When the root is an OpenAPI 3.1 document all the documents are rewritten as
+/// OpenAPI 3.0, `type: [string, "null"]` becomes `type: string, nullable: true`,
+/// `const` becomes a single value `enum` and so on.
+/// Swagger 2.0 documents are converted to OpenAPI 3.0 the same way: `definitions`
+/// become `components/schemas`, body and form parameters become request bodies and
+/// `host` with `basePath` become the servers.
+///
+/// # Example
+///
+/// ```
+/// use openapi_utils::{Documents, SpecExt};
+/// use openapiv3::OpenAPI;
+/// use serde_json::json;
+///
+/// let root = json!({
+///     "openapi": "3.0.0",
+///     "info": {"title": "Users", "version": "1"},
+///     "paths": {"/users": {"$ref": "paths.json#/users"}}
+/// });
+/// let mut documents = Documents::new("/specs/api.json", root);
+/// for uri in documents.missing() {
+///     assert_eq!(uri, "/specs/paths.json");
+///     let paths = json!({"users": {"get": {"responses": {"200": {"description": "OK"}}}}});
+///     documents.insert(&uri, paths);
+/// }
+/// let spec: OpenAPI = serde_json::from_value(documents.root().clone()).unwrap();
+/// let spec = spec.deref_all_with(&documents);
+/// assert!(spec.paths.paths["/users"].as_item().unwrap().get.is_some());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Documents {
+    root: String,
+    documents: BTreeMap<String, Value>,
+    openapi_3_1: bool,
+    swagger_2: bool,
+    unsupported: Vec<String>,
+}
+
+impl Documents {
+    /// Creates the cache with the root document, `root` is its URL or absolute path.
+    pub fn new(root: &str, mut document: Value) -> Self {
+        let openapi_3_1 = openapi31::is_3_1(&document);
+        let swagger_2 = swagger2::is_swagger_2(&document);
+        let mut unsupported = Vec::new();
+        if openapi_3_1 {
+            openapi31::normalize(&mut document, root, &mut unsupported);
+        }
+        if swagger_2 {
+            swagger2::convert(&mut document, root, &mut unsupported);
+        }
+        absolutize_references(&mut document, root, root);
+        let mut documents = BTreeMap::new();
+        documents.insert(root.to_string(), document);
+        Documents {
+            root: root.to_string(),
+            documents,
+            openapi_3_1,
+            swagger_2,
+            unsupported,
+        }
+    }
+
+    /// True if the root document is an OpenAPI 3.1 document.
+    pub fn is_openapi_3_1(&self) -> bool {
+        self.openapi_3_1
+    }
+
+    /// True if the root document is a Swagger 2.0 document.
+    pub fn is_swagger_2(&self) -> bool {
+        self.swagger_2
+    }
+
+    /// Locations of the keywords which are ignored because they can't be expressed
+    /// in OpenAPI 3.0, like `prefixItems` or `if` in 3.1 and a `tsv` collection
+    /// format in Swagger 2.0.
+    pub fn unsupported_keywords(&self) -> &[String] {
+        &self.unsupported
+    }
+
+    /// The root document, with its references to other documents made absolute.
+    pub fn root(&self) -> &Value {
+        &self.documents[&self.root]
+    }
+
+    /// Adds a document referenced from the documents already in the cache.
+    pub fn insert(&mut self, uri: &str, mut document: Value) {
+        if self.openapi_3_1 {
+            openapi31::normalize(&mut document, uri, &mut self.unsupported);
+        }
+        if self.swagger_2 {
+            swagger2::convert(&mut document, uri, &mut self.unsupported);
+        }
+        absolutize_references(&mut document, uri, &self.root);
+        self.documents.insert(uri.to_string(), document);
+    }
+
+    /// URIs of the documents in the cache, the root one included.
+    pub fn uris(&self) -> Vec<&str> {
+        self.documents.keys().map(String::as_str).collect()
+    }
+
+    /// URIs of the documents which are referenced but not loaded yet.
+    pub fn missing(&self) -> Vec<String> {
+        let mut missing = Vec::new();
+        for document in self.documents.values() {
+            visit_references(document, &mut |reference| {
+                let (uri, _) = split_reference(reference);
+                if !uri.is_empty()
+                    && !self.documents.contains_key(uri)
+                    && !missing.iter().any(|known| known == uri)
+                {
+                    missing.push(uri.to_string());
+                }
+            });
+        }
+        missing
+    }
+
+    /// Finds the value a reference points to.
+    /// The fragment of the reference is a JSON Pointer into the referenced document.
+    pub fn resolve(&self, reference: &str) -> Result<&Value, DerefError> {
+        let (uri, fragment) = split_reference(reference);
+        let uri = if uri.is_empty() { &self.root } else { uri };
+        let pointer = percent_decode(fragment);
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            return Err(DerefError::PointerError {
+                name: reference.to_string(),
+            });
+        }
+        self.documents
+            .get(uri)
+            .and_then(|document| document.pointer(&pointer))
+            .ok_or_else(|| DerefError::ReferenceError {
+                name: reference.to_string(),
+            })
+    }
+}
+
+/// Splits `common.yaml#/components/parameters/Page` in the document and the pointer.
+fn split_reference(reference: &str) -> (&str, &str) {
+    match reference.find('#') {
+        Some(index) => (&reference[..index], &reference[index + 1..]),
+        None => (reference, ""),
+    }
+}
+
+fn visit_references<F: FnMut(&str)>(value: &Value, visitor: &mut F) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get("$ref") {
+                visitor(reference);
+            }
+            for value in object.values() {
+                visit_references(value, visitor);
+            }
+        }
+        Value::Array(array) => {
+            for value in array {
+                visit_references(value, visitor);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rewrites the references in a document relative to the URI of the document.
+fn absolutize_references(value: &mut Value, base: &str, root: &str) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get_mut("$ref") {
+                let (uri, fragment) = split_reference(reference);
+                let uri = join(base, uri);
+                *reference = if uri == root {
+                    alloc::format!("#{}", fragment)
+                } else {
+                    alloc::format!("{}#{}", uri, fragment)
+                };
+            }
+            for value in object.values_mut() {
+                absolutize_references(value, base, root);
+            }
+        }
+        Value::Array(array) => {
+            for value in array {
+                absolutize_references(value, base, root);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Resolves a relative reference against the URI of the document it appears in.
+fn join(base: &str, reference: &str) -> String {
+    if reference.is_empty() {
+        return base.to_string();
+    }
+    if reference.contains("://") {
+        return reference.to_string();
+    }
+    // The scheme and host of URLs are kept as they are.
+    let (authority, base_path) = match base.find("://") {
+        Some(index) => {
+            let path_start = base[index + 3..]
+                .find('/')
+                .map(|start| index + 3 + start)
+                .unwrap_or(base.len());
+            base.split_at(path_start)
+        }
+        None => ("", base),
+    };
+    let path = if reference.starts_with('/') {
+        reference.to_string()
+    } else {
+        match base_path.rfind('/') {
+            Some(index) => alloc::format!("{}/{}", &base_path[..index], reference),
+            None => reference.to_string(),
+        }
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "." => {}
+            ".." => match segments.last() {
+                Some(&last) if !last.is_empty() && last != ".." => {
+                    segments.pop();
+                }
+                // There is nothing above the root directory.
+                Some(&"") => {}
+                _ => segments.push(segment),
+            },
+            _ => segments.push(segment),
+        }
+    }
+    alloc::format!("{}{}", authority, segments.join("/"))
+}
+
+/// Pointers in URI fragments can have percent encoded characters.
+fn percent_decode(fragment: &str) -> String {
+    let bytes = fragment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = |byte: u8| (byte as char).to_digit(16);
+        match (bytes[index], bytes.get(index + 1), bytes.get(index + 2)) {
+            (b'%', Some(&high), Some(&low)) if hex(high).is_some() && hex(low).is_some() => {
+                decoded.push((hex(high).unwrap() * 16 + hex(low).unwrap()) as u8);
+                index += 3;
+            }
+            (byte, _, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SpecExt;
+    use alloc::vec;
+    use openapiv3::{OpenAPI, ReferenceOr, SchemaKind, Type};
+    use serde_json::json;
+
+    #[test]
+    fn references_are_relative_to_their_document() {
+        assert_eq!(join("/specs/api.yaml", "common.yaml"), "/specs/common.yaml");
+        assert_eq!(join("/specs/paths/users.yaml", "../common.yaml"), "/specs/common.yaml");
+        assert_eq!(join("/specs/api.yaml", "/shared/common.yaml"), "/shared/common.yaml");
+        assert_eq!(join("/api.yaml", "../../common.yaml"), "/common.yaml");
+        assert_eq!(
+            join("https://example.com/specs/api.yaml", "./common.yaml"),
+            "https://example.com/specs/common.yaml"
+        );
+        assert_eq!(
+            join("/specs/api.yaml", "https://example.com/common.yaml"),
+            "https://example.com/common.yaml"
+        );
+    }
+
+    #[test]
+    fn referenced_documents_are_loaded_once() {
+        let mut documents = Documents::new(
+            "/specs/api.json",
+            json!({"a": {"$ref": "common.json#/A"}, "b": {"$ref": "paths/b.json#/B"}}),
+        );
+        assert_eq!(documents.missing(), vec!["/specs/common.json", "/specs/paths/b.json"]);
+        documents.insert("/specs/common.json", json!({"A": {"type": "string"}}));
+        documents.insert("/specs/paths/b.json", json!({"B": {"$ref": "../common.json#/A"}}));
+        assert!(documents.missing().is_empty());
+        assert_eq!(
+            documents.uris(),
+            vec!["/specs/api.json", "/specs/common.json", "/specs/paths/b.json"]
+        );
+    }
+
+    #[test]
+    fn references_back_to_the_root_are_local() {
+        let mut documents = Documents::new("/specs/api.json", json!({"a": {"$ref": "common.json#/A"}}));
+        documents.insert("/specs/common.json", json!({"A": {"$ref": "api.json#/components/x"}}));
+        let reference = documents.resolve("/specs/common.json#/A").unwrap();
+        assert_eq!(reference, &json!({"$ref": "#/components/x"}));
+    }
+
+    #[test]
+    fn unresolvable_references_are_errors() {
+        let documents = Documents::new("/specs/api.json", json!({"a": 1}));
+        assert_eq!(documents.resolve("#/a"), Ok(&json!(1)));
+        assert!(matches!(documents.resolve("#/b"), Err(DerefError::ReferenceError { .. })));
+        assert!(matches!(documents.resolve("#a"), Err(DerefError::PointerError { .. })));
+        let missing = documents.resolve("/specs/other.json#/a");
+        assert!(matches!(missing, Err(DerefError::ReferenceError { .. })));
+    }
+
+    #[test]
+    fn specs_are_dereferenced_across_documents() {
+        let root = json!({
+            "openapi": "3.0.0",
+            "info": {"title": "Users", "version": "1"},
+            "paths": {},
+            "components": {"schemas": {"User": {"$ref": "schemas/user.json#/User"}}}
+        });
+        let mut documents = Documents::new("/specs/api.json", root);
+        documents.insert(
+            "/specs/schemas/user.json",
+            json!({"User": {"type": "object", "properties": {"name": {"$ref": "#/Name"}}}, "Name": {"type": "string"}}),
+        );
+        let spec: OpenAPI = serde_json::from_value(documents.root().clone()).unwrap();
+        let spec = spec.try_deref_all_with(&documents).unwrap();
+        let user = match &spec.components.unwrap().schemas["User"] {
+            ReferenceOr::Item(user) => user.clone(),
+            ReferenceOr::Reference { reference } => panic!("{} was not resolved", reference),
+        };
+        let properties = match user.schema_kind {
+            SchemaKind::Type(Type::Object(object)) => object.properties,
+            kind => panic!("{:?} is not an object", kind),
+        };
+        assert!(matches!(
+            properties["name"].as_item().map(|name| &name.schema_kind),
+            Some(SchemaKind::Type(Type::String(_)))
+        ));
+    }
+
+    #[test]
+    fn missing_documents_are_reported() {
+        let root = json!({
+            "openapi": "3.0.0",
+            "info": {"title": "Users", "version": "1"},
+            "paths": {},
+            "components": {"schemas": {"User": {"$ref": "user.json#/User"}}}
+        });
+        let documents = Documents::new("/specs/api.json", root);
+        let spec: OpenAPI = serde_json::from_value(documents.root().clone()).unwrap();
+        let errors = spec.try_deref_all_with(&documents).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![DerefError::ReferenceError {
+                name: String::from("/specs/user.json#/User")
+            }]
+        );
+    }
+}
@@ -10,13 +10,32 @@ pub enum DerefError {
         /// The name of the refrenced element that failed
         name: String,
     },
+    /// A reference leads back to itself without reaching an item.
+    CircularReference {
+        /// The reference that could not be resolved
+        name: String,
+    },
+    /// The fragment of the reference is not a JSON Pointer, `#/components/schemas/User`.
+    PointerError {
+        /// The reference with the bad pointer
+        name: String,
+    },
+    /// The referenced element is not what is expected in the place of the reference,
+    /// like a reference to a response where a schema should be.
+    TypeError {
+        /// The reference to the element
+        name: String,
+        /// What the element should be
+        expected: String,
+    },
+    /// A part of the description this library can not work with.
+    Unsupported {
+        /// What is not supported
+        feature: String,
+    },
 }
 
-// impl ::std::error::Error for DerefError {
-//     fn description(&self) -> &str {
-//         "It was not possible to resolve all references in the file."
-//     }
-// }
+impl core::error::Error for DerefError {}
 
 impl fmt::Display for DerefError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -24,6 +43,19 @@ impl fmt::Display for DerefError {
             DerefError::ReferenceError { ref name } => {
                 write!(f, "The reference {} could not be dereferenced", name)
             }
+            DerefError::CircularReference { ref name } => {
+                write!(f, "The reference {} only leads to references to itself", name)
+            }
+            DerefError::PointerError { ref name } => {
+                write!(f, "The reference {} does not have a valid JSON Pointer", name)
+            }
+            DerefError::TypeError {
+                ref name,
+                ref expected,
+            } => write!(f, "The reference {} does not point to a {}", name, expected),
+            DerefError::Unsupported { ref feature } => {
+                write!(f, "{} are not supported", feature)
+            }
         }
     }
 }
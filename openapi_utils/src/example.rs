@@ -0,0 +1,747 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use indexmap::IndexMap;
+use openapiv3::*;
+
+use crate::reference::SchemaReferenceExt;
+use serde_json::{Map, Number, Value};
+
+/// Optional properties are only generated up to this depth, so recursive schemas end.
+const OPTIONAL_DEPTH: usize = 4;
+/// Values deeper than this are null.
+const MAX_DEPTH: usize = 16;
+
+/// Settings to generate examples from schemas.
+#[derive(Clone, Debug, Default)]
+pub struct ExampleOptions {
+    /// The same seed always generates the same value.
+    pub seed: u64,
+    /// Skips `readOnly` properties which are not required, for examples of requests.
+    pub skip_read_only: bool,
+    /// Skips `writeOnly` properties which are not required, for examples of responses.
+    pub skip_write_only: bool,
+}
+
+pub(crate) fn generate(
+    schema: &Schema,
+    options: &ExampleOptions,
+    components: Option<&Components>,
+) -> Value {
+    let empty = Components::default();
+    let mut generator = Generator {
+        options,
+        components: components.unwrap_or(&empty),
+        rng: SplitMix64(options.seed),
+    };
+    generator.schema(schema, 0)
+}
+
+struct Generator<'a> {
+    options: &'a ExampleOptions,
+    /// Recursive schemas are references to the components.
+    components: &'a Components,
+    rng: SplitMix64,
+}
+
+impl Generator<'_> {
+    fn schema(&mut self, schema: &Schema, depth: usize) -> Value {
+        if depth > MAX_DEPTH {
+            return Value::Null;
+        }
+        let data = &schema.schema_data;
+        if let Some(example) = data.example.as_ref().or(data.default.as_ref()) {
+            return example.clone();
+        }
+
+        match &schema.schema_kind {
+            SchemaKind::Type(the_type) => self.of_type(the_type, depth),
+            SchemaKind::OneOf { one_of } => self.pick(one_of, depth),
+            SchemaKind::AnyOf { any_of } => self.pick(any_of, depth),
+            SchemaKind::AllOf { all_of } => self.merge(all_of, depth),
+            // Anything could be valid, null is the least likely to be excluded.
+            SchemaKind::Not { .. } => Value::Null,
+            SchemaKind::Any(any) => self.any(any, depth),
+        }
+    }
+
+    fn of_type(&mut self, the_type: &Type, depth: usize) -> Value {
+        match the_type {
+            Type::String(string) => {
+                if let Some(value) = self.choose(&string.enumeration) {
+                    return Value::from(value.as_str());
+                }
+                let format = match &string.format {
+                    VariantOrUnknownOrEmpty::Item(StringFormat::Date) => Some("date"),
+                    VariantOrUnknownOrEmpty::Item(StringFormat::DateTime) => Some("date-time"),
+                    VariantOrUnknownOrEmpty::Item(StringFormat::Byte) => Some("byte"),
+                    VariantOrUnknownOrEmpty::Item(_) => None,
+                    VariantOrUnknownOrEmpty::Unknown(format) => Some(format.as_str()),
+                    VariantOrUnknownOrEmpty::Empty => None,
+                };
+                Value::from(self.string(format, string.min_length, string.max_length))
+            }
+            Type::Integer(integer) => {
+                if let Some(value) = self.choose(&integer.enumeration) {
+                    return Value::from(value);
+                }
+                Value::from(self.integer(
+                    integer.minimum,
+                    integer.maximum,
+                    integer.exclusive_minimum,
+                    integer.exclusive_maximum,
+                    integer.multiple_of,
+                ))
+            }
+            Type::Number(number) => {
+                if let Some(value) = self.choose(&number.enumeration) {
+                    return number_value(value);
+                }
+                number_value(self.number(
+                    number.minimum,
+                    number.maximum,
+                    number.exclusive_minimum,
+                    number.exclusive_maximum,
+                    number.multiple_of,
+                ))
+            }
+            Type::Boolean(boolean) => match self.choose(&boolean.enumeration) {
+                Some(value) => Value::Bool(value),
+                None => Value::Bool(self.rng.below(2) == 1),
+            },
+            Type::Object(object) => self.object(
+                &object.properties,
+                &object.required,
+                object.additional_properties.as_ref(),
+                object.min_properties,
+                object.max_properties,
+                depth,
+            ),
+            Type::Array(array) => self.array(
+                array.items.as_ref(),
+                array.min_items,
+                array.max_items,
+                array.unique_items,
+                depth,
+            ),
+        }
+    }
+
+    /// Schemas without a type or which mix a type with compositions, their keywords
+    /// tell what they describe.
+    fn any(&mut self, any: &AnySchema, depth: usize) -> Value {
+        if !any.enumeration.is_empty() {
+            let index = self.rng.below(any.enumeration.len() as u64) as usize;
+            return any.enumeration[index].clone();
+        }
+        // The compositions come first, the properties of the schema complete their objects.
+        let composed = self.composition(any, depth);
+        match (composed, self.any_type(any, depth)) {
+            (Some(Value::Object(mut composed)), Value::Object(object)) => {
+                for (name, value) in object {
+                    composed.entry(name).or_insert(value);
+                }
+                Value::Object(composed)
+            }
+            (Some(composed), _) => composed,
+            (None, value) => value,
+        }
+    }
+
+    /// The value of the allOf, oneOf and anyOf of a schema, None without them.
+    fn composition(&mut self, any: &AnySchema, depth: usize) -> Option<Value> {
+        let mut values = Vec::new();
+        if !any.all_of.is_empty() {
+            values.push(self.merge(&any.all_of, depth));
+        }
+        if !any.one_of.is_empty() {
+            values.push(self.pick(&any.one_of, depth));
+        }
+        if !any.any_of.is_empty() {
+            values.push(self.pick(&any.any_of, depth));
+        }
+        let mut values = values.into_iter();
+        let first = values.next()?;
+        Some(values.fold(first, |merged, value| match (merged, value) {
+            (Value::Object(mut merged), Value::Object(object)) => {
+                merged.extend(object);
+                Value::Object(merged)
+            }
+            (merged, _) => merged,
+        }))
+    }
+
+    /// The value of the keywords of a schema without a type, null when they tell nothing.
+    fn any_type(&mut self, any: &AnySchema, depth: usize) -> Value {
+        let typ = match any.typ.as_deref() {
+            Some(typ) => typ,
+            None if !any.properties.is_empty() => "object",
+            None if any.items.is_some() => "array",
+            None if any.minimum.is_some() || any.maximum.is_some() => "number",
+            None if any.min_length.is_some() || any.max_length.is_some() => "string",
+            None => return Value::Null,
+        };
+        let exclusive_minimum = any.exclusive_minimum.unwrap_or(false);
+        let exclusive_maximum = any.exclusive_maximum.unwrap_or(false);
+        match typ {
+            "string" => {
+                Value::from(self.string(any.format.as_deref(), any.min_length, any.max_length))
+            }
+            "integer" => Value::from(self.integer(
+                any.minimum.map(|minimum| minimum as i64),
+                any.maximum.map(|maximum| maximum as i64),
+                exclusive_minimum,
+                exclusive_maximum,
+                any.multiple_of.map(|multiple_of| multiple_of as i64),
+            )),
+            "number" => number_value(self.number(
+                any.minimum,
+                any.maximum,
+                exclusive_minimum,
+                exclusive_maximum,
+                any.multiple_of,
+            )),
+            "boolean" => Value::Bool(self.rng.below(2) == 1),
+            "object" => self.object(
+                &any.properties,
+                &any.required,
+                any.additional_properties.as_ref(),
+                any.min_properties,
+                any.max_properties,
+                depth,
+            ),
+            "array" => self.array(
+                any.items.as_ref(),
+                any.min_items,
+                any.max_items,
+                any.unique_items.unwrap_or(false),
+                depth,
+            ),
+            _ => Value::Null,
+        }
+    }
+
+    /// oneOf and anyOf, any of the schemas will do.
+    fn pick(&mut self, schemas: &[ReferenceOr<Schema>], depth: usize) -> Value {
+        let components = self.components;
+        let schemas: Vec<&Schema> = schemas
+            .iter()
+            .filter_map(|schema| schema.resolve(components))
+            .collect();
+        if schemas.is_empty() {
+            return Value::Null;
+        }
+        let index = self.rng.below(schemas.len() as u64) as usize;
+        self.schema(schemas[index], depth + 1)
+    }
+
+    /// allOf, the properties of all the schemas are merged.
+    fn merge(&mut self, schemas: &[ReferenceOr<Schema>], depth: usize) -> Value {
+        let components = self.components;
+        let mut merged = Map::new();
+        for schema in schemas.iter().filter_map(|schema| schema.resolve(components)) {
+            match self.schema(schema, depth + 1) {
+                Value::Object(object) => merged.extend(object),
+                value if merged.is_empty() => return value,
+                _ => {}
+            }
+        }
+        Value::Object(merged)
+    }
+
+    fn object(
+        &mut self,
+        properties: &IndexMap<String, ReferenceOr<Box<Schema>>>,
+        required: &[String],
+        additional_properties: Option<&AdditionalProperties>,
+        min_properties: Option<usize>,
+        max_properties: Option<usize>,
+        depth: usize,
+    ) -> Value {
+        let components = self.components;
+        let mut object = Map::new();
+        let max_properties = max_properties.unwrap_or(usize::MAX);
+
+        // Required properties first, so they are kept when the object is limited.
+        let (mandatory, optional): (Vec<_>, Vec<_>) = properties
+            .iter()
+            .filter_map(|(name, property)| property.resolve(components).map(|property| (name, property)))
+            .partition(|(name, _)| required.contains(name));
+        for (name, property) in mandatory {
+            let value = self.schema(property, depth + 1);
+            object.insert(name.clone(), value);
+        }
+        if depth < OPTIONAL_DEPTH {
+            for (name, property) in optional {
+                let data = &property.schema_data;
+                let skipped = (self.options.skip_read_only && data.read_only)
+                    || (self.options.skip_write_only && data.write_only);
+                if skipped || object.len() >= max_properties {
+                    continue;
+                }
+                let value = self.schema(property, depth + 1);
+                object.insert(name.clone(), value);
+            }
+        }
+
+        let min_properties = min_properties.unwrap_or(0);
+        let additional = match additional_properties {
+            Some(AdditionalProperties::Schema(schema)) => schema.resolve(components),
+            _ => None,
+        };
+        let allowed = !matches!(
+            additional_properties,
+            Some(AdditionalProperties::Any(false))
+        );
+        let mut index = 1;
+        while allowed && object.len() < min_properties {
+            let value = match additional {
+                Some(schema) => self.schema(schema, depth + 1),
+                None => Value::from(self.word(4, 8)),
+            };
+            object.insert(format!("property{}", index), value);
+            index += 1;
+        }
+        Value::Object(object)
+    }
+
+    fn array(
+        &mut self,
+        items: Option<&ReferenceOr<Box<Schema>>>,
+        min_items: Option<usize>,
+        max_items: Option<usize>,
+        unique_items: bool,
+        depth: usize,
+    ) -> Value {
+        let components = self.components;
+        let items = match items.and_then(|items| items.resolve(components)) {
+            Some(items) => items,
+            None => return Value::Array(Vec::new()),
+        };
+        let min_items = min_items.unwrap_or(0);
+        let wanted = if depth < OPTIONAL_DEPTH {
+            min_items.max(1)
+        } else {
+            min_items
+        };
+        let count = wanted.min(max_items.unwrap_or(usize::MAX));
+
+        let mut values: Vec<Value> = Vec::with_capacity(count);
+        let mut attempts = 0;
+        while values.len() < count && attempts < count * 10 {
+            attempts += 1;
+            let value = self.schema(items, depth + 1);
+            if unique_items && values.contains(&value) {
+                continue;
+            }
+            values.push(value);
+        }
+        Value::Array(values)
+    }
+
+    fn string(
+        &mut self,
+        format: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+    ) -> String {
+        match format {
+            Some("date") => return self.date(),
+            Some("date-time") => {
+                let date = self.date();
+                let (hour, minute, second) =
+                    (self.rng.below(24), self.rng.below(60), self.rng.below(60));
+                return format!("{}T{:02}:{:02}:{:02}Z", date, hour, minute, second);
+            }
+            Some("uuid") => {
+                let (high, low) = (self.rng.next(), self.rng.next());
+                return format!(
+                    "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+                    high >> 32,
+                    (high >> 16) & 0xffff,
+                    high & 0xfff,
+                    (low >> 48) & 0x3fff | 0x8000,
+                    low & 0xffff_ffff_ffff
+                );
+            }
+            Some("email") => return format!("{}@example.com", self.word(4, 10)),
+            Some("uri") | Some("url") => {
+                return format!("https://example.com/{}", self.word(4, 10))
+            }
+            Some("hostname") => return format!("{}.example.com", self.word(4, 10)),
+            Some("ipv4") => {
+                let octets: Vec<String> = (0..4)
+                    .map(|_| (self.rng.below(254) + 1).to_string())
+                    .collect();
+                return octets.join(".");
+            }
+            Some("ipv6") => return format!("2001:db8::{:x}", self.rng.below(0xffff) + 1),
+            Some("byte") => {
+                let word = self.word(4, 10);
+                return base64(word.as_bytes());
+            }
+            _ => {}
+        }
+        let min_length = min_length.unwrap_or(0);
+        let max_length = max_length.unwrap_or(usize::MAX).max(min_length);
+        let shortest = min_length.max(4.min(max_length));
+        let longest = max_length.min(shortest + 8);
+        self.word(shortest, longest)
+    }
+
+    fn date(&mut self) -> String {
+        let year = 2000 + self.rng.below(30);
+        let month = 1 + self.rng.below(12);
+        let day = 1 + self.rng.below(28);
+        format!("{}-{:02}-{:02}", year, month, day)
+    }
+
+    /// A pronounceable lowercase word with a length between the limits.
+    fn word(&mut self, shortest: usize, longest: usize) -> String {
+        const CONSONANTS: &[u8] = b"bcdfghjklmnprstvz";
+        const VOWELS: &[u8] = b"aeiou";
+        let length = shortest + self.rng.below((longest - shortest) as u64 + 1) as usize;
+        (0..length)
+            .map(|index| {
+                let letters = if index % 2 == 0 { CONSONANTS } else { VOWELS };
+                letters[self.rng.below(letters.len() as u64) as usize] as char
+            })
+            .collect()
+    }
+
+    fn integer(
+        &mut self,
+        minimum: Option<i64>,
+        maximum: Option<i64>,
+        exclusive_minimum: bool,
+        exclusive_maximum: bool,
+        multiple_of: Option<i64>,
+    ) -> i64 {
+        let low = minimum.map(|minimum| i128::from(minimum) + i128::from(exclusive_minimum));
+        let high = maximum.map(|maximum| i128::from(maximum) - i128::from(exclusive_maximum));
+        let (low, high) = match (low, high) {
+            (Some(low), Some(high)) => (low, high),
+            (Some(low), None) => (low, low + 100),
+            (None, Some(high)) => (high - 100, high),
+            (None, None) => (0, 100),
+        };
+        if low >= high {
+            return low as i64;
+        }
+        let value = match multiple_of {
+            Some(step) if step > 0 => {
+                let step = i128::from(step);
+                let first =
+                    low.div_euclid(step) * step + if low.rem_euclid(step) == 0 { 0 } else { step };
+                if first > high {
+                    return first as i64;
+                }
+                let count = (high - first) / step + 1;
+                first + step * self.rng.below_i128(count)
+            }
+            _ => low + self.rng.below_i128(high - low + 1),
+        };
+        value as i64
+    }
+
+    fn number(
+        &mut self,
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+        exclusive_minimum: bool,
+        exclusive_maximum: bool,
+        multiple_of: Option<f64>,
+    ) -> f64 {
+        let (low, high) = match (minimum, maximum) {
+            (Some(low), Some(high)) => (low, high),
+            (Some(low), None) => (low, low + 100.0),
+            (None, Some(high)) => (high - 100.0, high),
+            (None, None) => (0.0, 100.0),
+        };
+        // A single value, or none at all when the limits exclude each other.
+        if low >= high {
+            return low;
+        }
+        if let Some(step) = multiple_of.filter(|step| *step > 0.0) {
+            let mut first = ceil(low / step);
+            if exclusive_minimum && first * step <= low {
+                first += 1.0;
+            }
+            let mut last = floor(high / step);
+            if exclusive_maximum && last * step >= high {
+                last -= 1.0;
+            }
+            if first <= last {
+                let count = (last - first) as i128 + 1;
+                return (first + self.rng.below_i128(count) as f64) * step;
+            }
+        }
+        // Stays away from the limits, they may be exclusive.
+        let fraction = if exclusive_minimum || exclusive_maximum {
+            0.1 + 0.8 * self.rng.fraction()
+        } else {
+            self.rng.fraction()
+        };
+        let value = low + (high - low) * fraction;
+        // Two decimals are easier to read.
+        let rounded = (value * 100.0) as i64 as f64 / 100.0;
+        if rounded > low && rounded < high {
+            rounded
+        } else {
+            value
+        }
+    }
+
+    fn choose<T: Clone>(&mut self, enumeration: &[Option<T>]) -> Option<T> {
+        let values: Vec<&T> = enumeration.iter().flatten().collect();
+        if values.is_empty() {
+            return None;
+        }
+        let index = self.rng.below(values.len() as u64) as usize;
+        Some(values[index].clone())
+    }
+}
+
+fn number_value(value: f64) -> Value {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+fn floor(value: f64) -> f64 {
+    let truncated = value as i64 as f64;
+    if truncated > value {
+        truncated - 1.0
+    } else {
+        truncated
+    }
+}
+
+fn ceil(value: f64) -> f64 {
+    let truncated = value as i64 as f64;
+    if truncated < value {
+        truncated + 1.0
+    } else {
+        truncated
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Small deterministic random number generator, good enough for examples.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 to `bound` excluded.
+    fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next() % bound
+        }
+    }
+
+    fn below_i128(&mut self, bound: i128) -> i128 {
+        if bound <= 0 {
+            0
+        } else {
+            i128::from(self.next()) % bound
+        }
+    }
+
+    /// A number from 0 to 1 excluded.
+    fn fraction(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use serde_json::json;
+
+    fn schema(value: Value) -> Schema {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn example(value: Value, seed: u64) -> Value {
+        let options = ExampleOptions {
+            seed,
+            ..ExampleOptions::default()
+        };
+        generate(&schema(value), &options, None)
+    }
+
+    fn user() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {
+                "id": {"type": "string", "format": "uuid"},
+                "name": {"type": "string", "minLength": 3, "maxLength": 5},
+                "age": {"type": "integer", "minimum": 18, "maximum": 99},
+                "created": {"type": "string", "format": "date-time"}
+            }
+        })
+    }
+
+    #[test]
+    fn the_seed_decides_the_value() {
+        assert_eq!(example(user(), 7), example(user(), 7));
+        assert_ne!(example(user(), 7), example(user(), 8));
+    }
+
+    #[test]
+    fn enums_give_one_of_their_values() {
+        for seed in 0..20 {
+            let value = example(json!({"type": "string", "enum": ["red", "green"]}), seed);
+            assert!(value == "red" || value == "green", "{}", value);
+            let value = example(json!({"enum": [1, "one"]}), seed);
+            assert!(value == 1 || value == "one", "{}", value);
+        }
+    }
+
+    #[test]
+    fn formats_are_followed() {
+        for seed in 0..20 {
+            let uuid = example(json!({"type": "string", "format": "uuid"}), seed);
+            let parts: Vec<usize> = uuid.as_str().unwrap().split('-').map(str::len).collect();
+            assert_eq!(parts, [8, 4, 4, 4, 12], "{}", uuid);
+            let date_time = example(json!({"type": "string", "format": "date-time"}), seed);
+            let date_time = date_time.as_str().unwrap();
+            assert_eq!((date_time.len(), &date_time[10..11], &date_time[19..]), (20, "T", "Z"));
+        }
+    }
+
+    #[test]
+    fn limits_are_followed() {
+        for seed in 0..50 {
+            let value = example(json!({"type": "integer", "minimum": 10, "maximum": 12}), seed);
+            assert!((10..=12).contains(&value.as_i64().unwrap()), "{}", value);
+            let value = example(
+                json!({"type": "integer", "minimum": 10, "maximum": 12, "exclusiveMinimum": true}),
+                seed,
+            );
+            assert!((11..=12).contains(&value.as_i64().unwrap()), "{}", value);
+            let value = example(json!({"type": "number", "minimum": 0.5, "maximum": 0.75}), seed);
+            assert!((0.5..=0.75).contains(&value.as_f64().unwrap()), "{}", value);
+            let value = example(json!({"type": "string", "minLength": 12, "maxLength": 14}), seed);
+            assert!((12..=14).contains(&value.as_str().unwrap().len()), "{}", value);
+            let value = example(json!({"type": "array", "items": {"type": "boolean"}, "minItems": 3}), seed);
+            assert!(value.as_array().unwrap().len() >= 3, "{}", value);
+        }
+    }
+
+    #[test]
+    fn multiples_stay_inside_exclusive_limits() {
+        let limits = json!({
+            "type": "number",
+            "minimum": 0,
+            "maximum": 10,
+            "exclusiveMinimum": true,
+            "exclusiveMaximum": true,
+            "multipleOf": 5
+        });
+        for seed in 0..20 {
+            assert_eq!(example(limits.clone(), seed), 5.0);
+        }
+        let limits = json!({"minimum": 0, "maximum": 1, "exclusiveMaximum": true, "multipleOf": 1});
+        for seed in 0..20 {
+            assert_eq!(example(limits.clone(), seed), 0.0);
+        }
+    }
+
+    #[test]
+    fn required_properties_are_always_there() {
+        for seed in 0..20 {
+            let value = example(user(), seed);
+            assert!(value["id"].is_string() && value["name"].is_string(), "{}", value);
+        }
+        let deep = json!({
+            "type": "object",
+            "required": ["id"],
+            "properties": {"id": {"type": "integer"}, "note": {"type": "string"}}
+        });
+        let options = ExampleOptions::default();
+        let mut generator = Generator {
+            options: &options,
+            components: &Components::default(),
+            rng: SplitMix64(0),
+        };
+        let value = generator.schema(&schema(deep), OPTIONAL_DEPTH);
+        assert!(value.get("id").is_some() && value.get("note").is_none(), "{}", value);
+    }
+
+    #[test]
+    fn all_of_merges_the_objects() {
+        let value = example(
+            json!({"allOf": [
+                {"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}},
+                {"type": "object", "required": ["name"], "properties": {"name": {"type": "string"}}}
+            ]}),
+            1,
+        );
+        assert!(value["id"].is_i64() && value["name"].is_string(), "{}", value);
+    }
+
+    #[test]
+    fn one_of_picks_a_schema() {
+        let mut kinds = vec![];
+        for seed in 0..20 {
+            let value = example(json!({"oneOf": [{"type": "integer"}, {"type": "boolean"}]}), seed);
+            assert!(value.is_i64() || value.is_boolean(), "{}", value);
+            kinds.push(value.is_boolean());
+        }
+        assert!(kinds.contains(&true) && kinds.contains(&false));
+    }
+
+    #[test]
+    fn compositions_next_to_properties_are_kept() {
+        for seed in 0..20 {
+            let value = example(
+                json!({
+                    "type": "object",
+                    "required": ["id"],
+                    "properties": {"id": {"type": "integer"}},
+                    "allOf": [{"required": ["name"], "properties": {"name": {"type": "string"}}}],
+                    "oneOf": [
+                        {"required": ["email"], "properties": {"email": {"type": "string", "format": "email"}}},
+                        {"required": ["phone"], "properties": {"phone": {"type": "string"}}}
+                    ]
+                }),
+                seed,
+            );
+            assert!(value["id"].is_i64() && value["name"].is_string(), "{}", value);
+            assert!(value["email"].is_string() || value["phone"].is_string(), "{}", value);
+        }
+    }
+}
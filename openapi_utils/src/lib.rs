@@ -6,20 +6,26 @@
 extern crate alloc;
 
 mod dereferer;
+mod documents;
 mod error;
+mod example;
+mod openapi31;
 mod operation;
 mod parameter;
 mod reference;
 mod response;
 mod schema;
 mod server;
+mod swagger2;
 mod types;
 
 pub use dereferer::SpecExt;
+pub use documents::Documents;
 pub use error::DerefError;
+pub use example::ExampleOptions;
 pub use operation::OperationExt;
 pub use parameter::{ParameterDataExt, ParameterExt};
-pub use reference::ReferenceOrExt;
+pub use reference::{ParameterSchemaOrContentExt, ReferenceOrExt, SchemaReferenceExt};
 pub use response::ResponseExt;
 pub use schema::SchemaExt;
 pub use server::ServerExt;
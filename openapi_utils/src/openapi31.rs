@@ -0,0 +1,268 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use serde_json::{Map, Value};
+
+/// Objects under these keys are maps of names, their keys are not keywords.
+const NAME_MAPS: &[&str] = &[
+    "$defs",
+    "callbacks",
+    "content",
+    "definitions",
+    "dependentSchemas",
+    "encoding",
+    "examples",
+    "headers",
+    "links",
+    "mapping",
+    "parameters",
+    "pathItems",
+    "paths",
+    "patternProperties",
+    "properties",
+    "requestBodies",
+    "responses",
+    "schemas",
+    "scopes",
+    "securitySchemes",
+    "variables",
+    "webhooks",
+];
+
+/// Values under these keys are data, not part of the description.
+const DATA: &[&str] = &["const", "default", "enum", "example", "value"];
+
+/// JSON Schema 2020-12 keywords which can not be expressed in OpenAPI 3.0 schemas.
+const UNSUPPORTED: &[&str] = &[
+    "$dynamicRef",
+    "contains",
+    "dependentRequired",
+    "dependentSchemas",
+    "else",
+    "if",
+    "maxContains",
+    "minContains",
+    "patternProperties",
+    "prefixItems",
+    "propertyNames",
+    "then",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+];
+
+/// True for documents with `openapi: 3.1.x`.
+pub(crate) fn is_3_1(document: &Value) -> bool {
+    document
+        .get("openapi")
+        .and_then(Value::as_str)
+        .is_some_and(|version| version.starts_with("3.1"))
+}
+
+/// Rewrites an OpenAPI 3.1 document, or a part of one, as OpenAPI 3.0.
+/// The pointers to the keywords which are dropped because 3.0 can't express them
+/// are added to `unsupported`.
+pub(crate) fn normalize(document: &mut Value, uri: &str, unsupported: &mut Vec<String>) {
+    if let Value::Object(object) = document {
+        // Documents with only webhooks have no paths, webhooks are not requests to the API.
+        if object.contains_key("openapi") {
+            if !object.contains_key("paths") {
+                object.insert("paths".to_string(), Value::Object(Map::new()));
+            }
+            if let Some(webhooks) = object.remove("webhooks") {
+                object.insert("x-webhooks".to_string(), webhooks);
+            }
+        }
+    }
+    let mut walker = Walker { uri, unsupported };
+    walker.value(document, "");
+}
+
+struct Walker<'a> {
+    uri: &'a str,
+    unsupported: &'a mut Vec<String>,
+}
+
+impl Walker<'_> {
+    fn value(&mut self, value: &mut Value, pointer: &str) {
+        match value {
+            Value::Object(object) => {
+                schema_keywords(object);
+                for keyword in UNSUPPORTED {
+                    if object.contains_key(*keyword) {
+                        let location = format!("{}#{}/{}", self.uri, pointer, keyword);
+                        self.unsupported.push(location);
+                    }
+                }
+                for (key, value) in object.iter_mut() {
+                    if DATA.contains(&key.as_str()) || key.starts_with("x-") {
+                        continue;
+                    }
+                    let pointer = format!("{}/{}", pointer, escape(key));
+                    match value {
+                        Value::Object(names) if NAME_MAPS.contains(&key.as_str()) => {
+                            for (name, value) in names.iter_mut() {
+                                self.value(value, &format!("{}/{}", pointer, escape(name)));
+                            }
+                        }
+                        value => self.value(value, &pointer),
+                    }
+                }
+            }
+            Value::Array(array) => {
+                for (index, value) in array.iter_mut().enumerate() {
+                    self.value(value, &format!("{}/{}", pointer, index));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Rewrites the keywords of a schema which changed in JSON Schema 2020-12.
+/// Objects which are not schemas never have these keywords with these values.
+fn schema_keywords(object: &mut Map<String, Value>) {
+    // `type: [string, "null"]` is `type: string, nullable: true`.
+    let types: Option<Vec<String>> = match object.get("type") {
+        Some(Value::Array(types)) => Some(
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+        ),
+        Some(Value::String(typ)) if typ == "null" => Some(vec![typ.to_string()]),
+        _ => None,
+    };
+    if let Some(types) = types {
+        let nullable = types.iter().any(|typ| typ == "null");
+        let mut others: Vec<String> = types.into_iter().filter(|typ| typ != "null").collect();
+        object.remove("type");
+        if nullable {
+            object.insert("nullable".to_string(), Value::Bool(true));
+        }
+        match others.len() {
+            0 => {
+                object
+                    .entry("enum")
+                    .or_insert_with(|| Value::Array(vec![Value::Null]));
+            }
+            1 => {
+                object.insert("type".to_string(), Value::String(others.remove(0)));
+            }
+            _ => {
+                let branches: Vec<Value> = others
+                    .into_iter()
+                    .map(|typ| {
+                        let mut branch = Map::new();
+                        branch.insert("type".to_string(), Value::String(typ));
+                        Value::Object(branch)
+                    })
+                    .collect();
+                if object.contains_key("anyOf") {
+                    let mut any_of = Map::new();
+                    any_of.insert("anyOf".to_string(), Value::Array(branches));
+                    let all_of = object.entry("allOf").or_insert_with(|| Value::Array(Vec::new()));
+                    if let Value::Array(all_of) = all_of {
+                        all_of.push(Value::Object(any_of));
+                    }
+                } else {
+                    object.insert("anyOf".to_string(), Value::Array(branches));
+                }
+            }
+        }
+    }
+
+    if let Some(constant) = object.remove("const") {
+        object
+            .entry("enum")
+            .or_insert_with(|| Value::Array(vec![constant]));
+    }
+
+    // Schemas have a list of examples, parameters and media types a map of them.
+    if let Some(Value::Array(_)) = object.get("examples") {
+        if let Some(Value::Array(mut examples)) = object.remove("examples") {
+            if !examples.is_empty() && !object.contains_key("example") {
+                object.insert("example".to_string(), examples.remove(0));
+            }
+        }
+    }
+
+    // The exclusive limits are numbers instead of flags on `minimum` and `maximum`.
+    // Both limits can be present, the stricter one is kept.
+    for (exclusive, limit, lower) in [
+        ("exclusiveMinimum", "minimum", true),
+        ("exclusiveMaximum", "maximum", false),
+    ] {
+        let number = match object.get(exclusive) {
+            Some(Value::Number(number)) => number.clone(),
+            _ => continue,
+        };
+        let exclusive_wins = match object.get(limit).and_then(Value::as_f64) {
+            Some(inclusive) => {
+                let bound = number.as_f64().unwrap_or(inclusive);
+                if lower {
+                    bound >= inclusive
+                } else {
+                    bound <= inclusive
+                }
+            }
+            None => true,
+        };
+        if exclusive_wins {
+            object.insert(limit.to_string(), Value::Number(number));
+            object.insert(exclusive.to_string(), Value::Bool(true));
+        } else {
+            object.remove(exclusive);
+        }
+    }
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema(mut schema: Value) -> Value {
+        schema_keywords(schema.as_object_mut().unwrap());
+        schema
+    }
+
+    #[test]
+    fn numeric_exclusive_limits_become_flags() {
+        let rewritten = schema(json!({"type": "integer", "exclusiveMinimum": 0, "exclusiveMaximum": 10}));
+        assert_eq!(
+            rewritten,
+            json!({
+                "type": "integer",
+                "minimum": 0, "exclusiveMinimum": true,
+                "maximum": 10, "exclusiveMaximum": true
+            })
+        );
+    }
+
+    #[test]
+    fn stricter_inclusive_limits_are_kept() {
+        let rewritten = schema(json!({"minimum": 5, "exclusiveMinimum": 0, "maximum": 8, "exclusiveMaximum": 10}));
+        assert_eq!(rewritten, json!({"minimum": 5, "maximum": 8}));
+    }
+
+    #[test]
+    fn stricter_exclusive_limits_replace_inclusive_ones() {
+        let rewritten = schema(json!({"minimum": 0, "exclusiveMinimum": 5, "maximum": 10, "exclusiveMaximum": 8}));
+        assert_eq!(
+            rewritten,
+            json!({"minimum": 5, "exclusiveMinimum": true, "maximum": 8, "exclusiveMaximum": true})
+        );
+    }
+
+    #[test]
+    fn equal_limits_are_exclusive() {
+        let rewritten = schema(json!({"minimum": 5, "exclusiveMinimum": 5}));
+        assert_eq!(rewritten, json!({"minimum": 5, "exclusiveMinimum": true}));
+    }
+}
@@ -4,7 +4,9 @@ use openapiv3::*;
 /// Extension methods for Operation
 pub trait OperationExt {
     /// For this operation object which contains multiple responses
-    /// returns the response which will be returned in the service
+    /// returns the response which will be returned in the service.
+    /// An exact status code is preferred over a range like `2XX`,
+    /// and the range over the `default` response.
     fn response(&self, status: u16) -> Option<&Response>;
 
     /// Returns all the required parameters of this operation
@@ -17,10 +19,11 @@ pub trait OperationExt {
 impl OperationExt for Operation {
     /// Gets the response for a status code in the operation
     fn response(&self, status: u16) -> Option<&Response> {
-        let status_code = StatusCode::Code(status);
-        self.responses
-            .responses
-            .get(&status_code)
+        let responses = &self.responses.responses;
+        responses
+            .get(&StatusCode::Code(status))
+            .or_else(|| responses.get(&StatusCode::Range(status / 100)))
+            .or(self.responses.default.as_ref())
             .and_then(|ref_or_item| ref_or_item.as_item())
     }
 
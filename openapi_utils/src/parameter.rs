@@ -1,3 +1,4 @@
+use crate::error::DerefError;
 use crate::reference::ParameterSchemaOrContentExt;
 use crate::schema::SchemaExt;
 use alloc::string::String;
@@ -6,19 +7,20 @@ use openapiv3::*;
 
 /// Extension methods for `ParameterData`
 pub trait ParameterDataExt {
-    /// Returns the type of the schema for this parameter, see schema documentation
-    fn get_type(&self) -> &Type;
+    /// Returns the type of the schema for this parameter, see schema documentation.
+    /// Parameters described with `content` return an error.
+    fn try_get_type(&self) -> Result<&Type, DerefError>;
 
     /// Returns true if the schema has a type defined. See schema for documentation.
     fn is_type_defined(&self) -> bool;
 }
 
 impl ParameterDataExt for ParameterData {
-    fn get_type(&self) -> &Type {
-        self.format.item().get_type()
+    fn try_get_type(&self) -> Result<&Type, DerefError> {
+        self.format.try_item()?.try_get_type()
     }
     fn is_type_defined(&self) -> bool {
-        self.format.item().is_type_defined()
+        self.format.try_item().is_ok_and(|schema| schema.is_type_defined())
     }
 }
 
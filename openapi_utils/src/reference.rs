@@ -1,22 +1,34 @@
+use crate::error::DerefError;
+use alloc::boxed::Box;
+use alloc::string::ToString;
 use openapiv3::*;
 
 /// Extension methods for `ParamSchema`
 pub trait ParameterSchemaOrContentExt {
+    /// Returns the schema of the parameter.
+    /// Panics for references and for parameters described with `content`.
     fn item(&self) -> &Schema;
+
+    /// Like `item`, but returns an error instead of panicking.
+    fn try_item(&self) -> Result<&Schema, DerefError>;
 }
 
 impl ParameterSchemaOrContentExt for ParameterSchemaOrContent {
     fn item(&self) -> &Schema {
+        self.try_item().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    fn try_item(&self) -> Result<&Schema, DerefError> {
         match self {
             ParameterSchemaOrContent::Schema(reference) => match reference {
-                ReferenceOr::Reference { .. } => {
-                    unimplemented!("References inside schemas are not supported")
-                }
-                ReferenceOr::Item(item) => item,
+                ReferenceOr::Reference { reference } => Err(DerefError::ReferenceError {
+                    name: reference.to_string(),
+                }),
+                ReferenceOr::Item(item) => Ok(item),
             },
-            ParameterSchemaOrContent::Content(_content) => {
-                unimplemented!("Not quite understand what's a Content in Schema")
-            }
+            ParameterSchemaOrContent::Content(_content) => Err(DerefError::Unsupported {
+                feature: "Parameters described with `content`".to_string(),
+            }),
         }
     }
 }
@@ -28,24 +40,67 @@ impl ParameterSchemaOrContentExt for ParameterSchemaOrContent {
 /// these enums always have an item, never a reference.
 pub trait ReferenceOrExt<T> {
 
-    /// Returns mutable reference to internal Item for a `ReferenceOr`
-    fn to_item_mut(&mut self) -> &mut T;
-}
-
-impl<T> ReferenceOrExt<T> for ReferenceOr<T> {
+    /// Returns mutable reference to internal Item for a `ReferenceOr`.
+    /// Panics for references.
+    ///
     /// # Examples
     ///
     /// ```
+    /// use openapi_utils::ReferenceOrExt;
+    /// use openapiv3::ReferenceOr;
+    ///
     /// let mut item = ReferenceOr::Item(3);
-    /// assert_eq!(to_item_mut(&mut item), &3);
+    /// *item.to_item_mut() += 1;
+    /// assert_eq!(item.as_item(), Some(&4));
     /// ```
+    fn to_item_mut(&mut self) -> &mut T;
+
+    /// Like `to_item_mut`, but returns an error for a reference instead of panicking.
+    fn try_to_item_mut(&mut self) -> Result<&mut T, DerefError>;
+}
+
+impl<T> ReferenceOrExt<T> for ReferenceOr<T> {
     fn to_item_mut(&mut self) -> &mut T {
+        self.try_to_item_mut().unwrap_or_else(|error| panic!("{}", error))
+    }
 
+    fn try_to_item_mut(&mut self) -> Result<&mut T, DerefError> {
         match self {
-            ReferenceOr::Reference { reference } => {
-                unimplemented!("No support to dereference {}.", reference)
-            }
-            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { reference } => Err(DerefError::ReferenceError {
+                name: reference.to_string(),
+            }),
+            ReferenceOr::Item(item) => Ok(item),
         }
     }
 }
+
+/// Follows the references `deref_all` keeps for recursive schemas.
+/// They always point to a schema in the components of the dereferenced spec.
+pub trait SchemaReferenceExt {
+    /// Returns the schema, or the schema in the components it refers to.
+    fn resolve<'a>(&'a self, components: &'a Components) -> Option<&'a Schema>;
+}
+
+impl SchemaReferenceExt for ReferenceOr<Schema> {
+    fn resolve<'a>(&'a self, components: &'a Components) -> Option<&'a Schema> {
+        match self {
+            ReferenceOr::Reference { reference } => component_schema(reference, components),
+            ReferenceOr::Item(item) => Some(item),
+        }
+    }
+}
+
+impl SchemaReferenceExt for ReferenceOr<Box<Schema>> {
+    fn resolve<'a>(&'a self, components: &'a Components) -> Option<&'a Schema> {
+        match self {
+            ReferenceOr::Reference { reference } => component_schema(reference, components),
+            ReferenceOr::Item(item) => Some(item),
+        }
+    }
+}
+
+fn component_schema<'a>(reference: &str, components: &'a Components) -> Option<&'a Schema> {
+    let name = reference.strip_prefix("#/components/schemas/")?;
+    let name = name.replace("~1", "/").replace("~0", "~");
+    components.schemas.get(&name)?.as_item()
+}
@@ -1,28 +1,94 @@
 use openapiv3::*;
+use serde_json::Value;
+
+use crate::error::DerefError;
+use crate::example::{self, ExampleOptions};
+use alloc::string::ToString;
 
 /// Extension methods for Schema
 pub trait SchemaExt {
-    /// Returns the type of the schema
-    /// Panics for oneOf, anyOf, allOf, or no type defined
-    fn get_type(&self) -> &Type;
+    /// Returns the type of the schema.
+    /// Schemas with oneOf, anyOf, allOf, not or without a type have none and return an error.
+    fn try_get_type(&self) -> Result<&Type, DerefError>;
 
     /// Returns true if a specific type is defined, false on oneOf, anyOf, allOf, no type
     fn is_type_defined(&self) -> bool;
+
+    /// Returns a value which agrees with the schema, the same value every time.
+    /// The `example` or `default` of the schema are used when present.
+    /// The schema must be dereferenced, patterns are not taken into account.
+    fn example(&self) -> Value;
+
+    /// Like `example` but with settings, different seeds generate different values.
+    fn example_with(&self, options: &ExampleOptions) -> Value;
+
+    /// Like `example_with`, following the references to the components which
+    /// `deref_all` keeps for recursive schemas.
+    fn example_in(&self, components: &Components, options: &ExampleOptions) -> Value;
 }
 
 impl SchemaExt for Schema {
-    fn get_type(&self) -> &Type {
-        match &self.schema_kind {
-            SchemaKind::Type(schema_type) => schema_type,
-            SchemaKind::OneOf { .. } => unimplemented!("OneOf not supported"),
-            SchemaKind::AnyOf { .. } => unimplemented!("AnyOf not supported"),
-            SchemaKind::AllOf { .. } => unimplemented!("AllOf not supported"),
-            SchemaKind::Not { .. } => unimplemented!("Not not supported"),
-            SchemaKind::Any(_) => unimplemented!("Any not supported"),
-        }
+    fn try_get_type(&self) -> Result<&Type, DerefError> {
+        let feature = match &self.schema_kind {
+            SchemaKind::Type(schema_type) => return Ok(schema_type),
+            SchemaKind::OneOf { .. } => "Types of oneOf schemas",
+            SchemaKind::AnyOf { .. } => "Types of anyOf schemas",
+            SchemaKind::AllOf { .. } => "Types of allOf schemas",
+            SchemaKind::Not { .. } => "Types of not schemas",
+            SchemaKind::Any(_) => "Types of schemas without a type",
+        };
+        Err(DerefError::Unsupported {
+            feature: feature.to_string(),
+        })
     }
 
     fn is_type_defined(&self) -> bool {
         matches!(&self.schema_kind, SchemaKind::Type(_))
     }
+
+    fn example(&self) -> Value {
+        example::generate(self, &ExampleOptions::default(), None)
+    }
+
+    fn example_with(&self, options: &ExampleOptions) -> Value {
+        example::generate(self, options, None)
+    }
+
+    fn example_in(&self, components: &Components, options: &ExampleOptions) -> Value {
+        example::generate(self, options, Some(components))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use serde_json::json;
+
+    fn schema(value: Value) -> Schema {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn typed_schemas_have_a_type() {
+        let string = schema(json!({"type": "string"}));
+        assert!(matches!(string.try_get_type(), Ok(Type::String(_))));
+        assert!(string.is_type_defined());
+    }
+
+    #[test]
+    fn composed_schemas_have_no_type() {
+        let string = json!({"type": "string"});
+        for composed in [
+            json!({"oneOf": vec![string.clone(), json!({"type": "integer"})]}),
+            json!({"anyOf": vec![string.clone()]}),
+            json!({"allOf": vec![string.clone()]}),
+            json!({"not": {"type": "string"}}),
+            json!({"description": "anything"}),
+        ] {
+            let composed = schema(composed);
+            assert!(matches!(composed.try_get_type(), Err(DerefError::Unsupported { .. })));
+            assert!(!composed.is_type_defined());
+        }
+    }
 }
@@ -1,35 +1,62 @@
 use alloc::string::String;
-use http::Uri;
+use alloc::vec;
+use alloc::vec::Vec;
 use openapiv3::*;
 
 /// Extension methods for Server
 pub trait ServerExt {
     /// Returns the `base_path` of the server ensuring the string does not end on /
     fn base_path(&self) -> String;
+    /// Returns every URL the server describes, one for each combination of the `enum`
+    /// values of its variables. Variables without `enum` use their `default`.
+    fn urls(&self) -> Vec<String>;
 }
 
 impl ServerExt for Server {
     /// Returns a string with the base path for the Server
     /// It guarantees it does not end on /
+    /// Variables are replaced by their `default` value.
     fn base_path(&self) -> String {
+        let mut url = self.url.clone();
         if let Some(variables) = &self.variables {
-            match variables.get("basePath") {
-                Some(base_path) => {
-                    let mut base_str = base_path.default.clone();
-                    let last_character = base_str.chars().last().unwrap();
-                    if last_character == '/' {
-                        base_str.pop();
-                    }
-                    base_str
-                }
-                None => String::from(""),
-            }
-        } else {
-            let url_parse = &self.url.parse::<Uri>();
-            match url_parse {
-                Ok(url) => String::from(url.path()),
-                Err(_) => String::from(""),
+            for (name, variable) in variables {
+                url = url.replace(&alloc::format!("{{{}}}", name), &variable.default);
             }
         }
+        String::from(url_path(&url).trim_end_matches('/'))
+    }
+
+    fn urls(&self) -> Vec<String> {
+        let mut urls = vec![self.url.clone()];
+        let variables = match &self.variables {
+            Some(variables) => variables,
+            None => return urls,
+        };
+        for (name, variable) in variables {
+            let placeholder = alloc::format!("{{{}}}", name);
+            let values = if variable.enumeration.is_empty() {
+                vec![variable.default.clone()]
+            } else {
+                variable.enumeration.clone()
+            };
+            let placeholder = placeholder.as_str();
+            urls = urls
+                .iter()
+                .flat_map(|url| values.iter().map(move |value| url.replace(placeholder, value)))
+                .collect();
+        }
+        urls
     }
 }
+
+/// The path of an absolute or relative URL, without its query.
+fn url_path(url: &str) -> &str {
+    let path = match url.find("://") {
+        Some(scheme_end) => {
+            let rest = &url[scheme_end + 3..];
+            rest.find('/').map_or("", |start| &rest[start..])
+        }
+        None => url,
+    };
+    path.split(['?', '#']).next().unwrap_or("")
+}
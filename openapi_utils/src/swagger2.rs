@@ -0,0 +1,743 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use serde_json::{json, Map, Value};
+
+const METHODS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch"];
+
+/// Keys of a parameter or header which stay in it, the rest describe its schema.
+const PARAMETER_KEYS: &[&str] = &["name", "in", "description", "required", "allowEmptyValue"];
+
+/// `#/definitions/User` is `#/components/schemas/User` in OpenAPI 3.
+const MOVED: &[(&str, &str)] = &[
+    ("/definitions/", "/components/schemas/"),
+    ("/parameters/", "/components/parameters/"),
+    ("/responses/", "/components/responses/"),
+];
+
+/// True for documents with `swagger: "2.0"`.
+pub(crate) fn is_swagger_2(document: &Value) -> bool {
+    document
+        .get("swagger")
+        .and_then(Value::as_str)
+        .is_some_and(|version| version.starts_with("2."))
+}
+
+/// Rewrites a Swagger 2.0 document as OpenAPI 3.0.
+/// Documents without the `swagger` field are parts of a description, like a file with
+/// schemas, only their schemas and references are rewritten.
+/// What can't be converted is added to `unsupported`.
+pub(crate) fn convert(document: &mut Value, uri: &str, unsupported: &mut Vec<String>) {
+    if is_swagger_2(document) {
+        if let Value::Object(swagger) = document {
+            let mut converter = Converter::new(swagger, uri, unsupported);
+            *document = Value::Object(converter.openapi(swagger));
+        }
+    } else {
+        if let Value::Object(object) = document {
+            if let Some(definitions) = object.remove("definitions") {
+                object.insert("components".to_string(), json!({ "schemas": definitions }));
+            }
+        }
+        fragment_schemas(document);
+    }
+    rewrite_references(document);
+}
+
+struct Converter<'a> {
+    /// Global parameters, body and form parameters are inlined in the operations.
+    parameters: Map<String, Value>,
+    consumes: Vec<String>,
+    produces: Vec<String>,
+    uri: &'a str,
+    unsupported: &'a mut Vec<String>,
+}
+
+impl<'a> Converter<'a> {
+    fn new(swagger: &Map<String, Value>, uri: &'a str, unsupported: &'a mut Vec<String>) -> Self {
+        let parameters = match swagger.get("parameters") {
+            Some(Value::Object(parameters)) => parameters.clone(),
+            _ => Map::new(),
+        };
+        Converter {
+            parameters,
+            consumes: media_types(swagger.get("consumes")).unwrap_or_else(json_media_type),
+            produces: media_types(swagger.get("produces")).unwrap_or_else(json_media_type),
+            uri,
+            unsupported,
+        }
+    }
+
+    fn openapi(&mut self, swagger: &Map<String, Value>) -> Map<String, Value> {
+        let mut openapi = Map::new();
+        openapi.insert("openapi".to_string(), Value::from("3.0.3"));
+        for (key, value) in swagger {
+            match key.as_str() {
+                "info" | "tags" | "externalDocs" | "security" => {
+                    openapi.insert(key.clone(), value.clone());
+                }
+                _ if key.starts_with("x-") => {
+                    openapi.insert(key.clone(), value.clone());
+                }
+                _ => {}
+            }
+        }
+        openapi.insert("servers".to_string(), servers(swagger));
+
+        let mut paths = Map::new();
+        if let Some(Value::Object(swagger_paths)) = swagger.get("paths") {
+            for (path, item) in swagger_paths {
+                let item = match item {
+                    Value::Object(item) if !path.starts_with("x-") => {
+                        let pointer = format!("/paths/{}", escape(path));
+                        Value::Object(self.path_item(item, &pointer))
+                    }
+                    item => item.clone(),
+                };
+                paths.insert(path.clone(), item);
+            }
+        }
+        openapi.insert("paths".to_string(), Value::Object(paths));
+        openapi.insert("components".to_string(), Value::Object(self.components(swagger)));
+        openapi
+    }
+
+    fn components(&mut self, swagger: &Map<String, Value>) -> Map<String, Value> {
+        let mut components = Map::new();
+        if let Some(Value::Object(definitions)) = swagger.get("definitions") {
+            let schemas = definitions
+                .iter()
+                .map(|(name, schema)| (name.clone(), schema_value(schema)))
+                .collect();
+            components.insert("schemas".to_string(), Value::Object(schemas));
+        }
+
+        let mut parameters = Map::new();
+        let mut request_bodies = Map::new();
+        for (name, parameter) in self.parameters.clone() {
+            let pointer = format!("/parameters/{}", escape(&name));
+            match parameter.get("in").and_then(Value::as_str) {
+                Some("body") => {
+                    let consumes = self.consumes.clone();
+                    request_bodies.insert(name, request_body(&parameter, &consumes));
+                }
+                // Form parameters are only meaningful inside an operation.
+                Some("formData") => {}
+                _ => {
+                    parameters.insert(name, self.parameter(&parameter, &pointer));
+                }
+            }
+        }
+        components.insert("parameters".to_string(), Value::Object(parameters));
+        components.insert("requestBodies".to_string(), Value::Object(request_bodies));
+
+        if let Some(Value::Object(responses)) = swagger.get("responses") {
+            let produces = self.produces.clone();
+            let responses = responses
+                .iter()
+                .map(|(name, response)| (name.clone(), response_value(response, &produces)))
+                .collect();
+            components.insert("responses".to_string(), Value::Object(responses));
+        }
+        if let Some(Value::Object(schemes)) = swagger.get("securityDefinitions") {
+            let schemes = schemes
+                .iter()
+                .map(|(name, scheme)| (name.clone(), security_scheme(scheme)))
+                .collect();
+            components.insert("securitySchemes".to_string(), Value::Object(schemes));
+        }
+        components
+    }
+
+    fn path_item(&mut self, item: &Map<String, Value>, pointer: &str) -> Map<String, Value> {
+        let mut result = Map::new();
+        // Body and form parameters of the path belong to the request body of each operation.
+        let mut shared = Vec::new();
+        let mut parameters = Vec::new();
+        if let Some(Value::Array(path_parameters)) = item.get("parameters") {
+            for (index, parameter) in path_parameters.iter().enumerate() {
+                let parameter = self.resolve(parameter);
+                match parameter.get("in").and_then(Value::as_str) {
+                    Some("body") | Some("formData") => shared.push(parameter),
+                    _ => {
+                        let pointer = format!("{}/parameters/{}", pointer, index);
+                        parameters.push(self.parameter(&parameter, &pointer));
+                    }
+                }
+            }
+        }
+        if !parameters.is_empty() {
+            result.insert("parameters".to_string(), Value::Array(parameters));
+        }
+
+        for (key, value) in item {
+            match (key.as_str(), value) {
+                ("parameters", _) => {}
+                (method, Value::Object(operation)) if METHODS.contains(&method) => {
+                    let pointer = format!("{}/{}", pointer, method);
+                    let operation = self.operation(operation, &shared, &pointer);
+                    result.insert(key.clone(), Value::Object(operation));
+                }
+                _ => {
+                    result.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        result
+    }
+
+    fn operation(
+        &mut self,
+        operation: &Map<String, Value>,
+        shared: &[Value],
+        pointer: &str,
+    ) -> Map<String, Value> {
+        let consumes = media_types(operation.get("consumes")).unwrap_or_else(|| self.consumes.clone());
+        let produces = media_types(operation.get("produces")).unwrap_or_else(|| self.produces.clone());
+        let mut result = Map::new();
+        for (key, value) in operation {
+            match key.as_str() {
+                "consumes" | "produces" | "parameters" | "responses" | "schemes" => {}
+                _ => {
+                    result.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        let own: Vec<Value> = match operation.get("parameters") {
+            Some(Value::Array(parameters)) => {
+                parameters.iter().map(|parameter| self.resolve(parameter)).collect()
+            }
+            _ => Vec::new(),
+        };
+        let mut parameters = Vec::new();
+        let mut body = None;
+        let mut form_properties = Map::new();
+        let mut form_required = Vec::new();
+        let mut files = false;
+        for (index, parameter) in own.iter().chain(shared).enumerate() {
+            let name = parameter.get("name").and_then(Value::as_str).unwrap_or_default();
+            match parameter.get("in").and_then(Value::as_str) {
+                Some("body") => body = Some(request_body(parameter, &consumes)),
+                Some("formData") => {
+                    if form_properties.contains_key(name) {
+                        continue;
+                    }
+                    let mut property = match parameter {
+                        Value::Object(parameter) => schema_of_parameter(parameter),
+                        _ => Map::new(),
+                    };
+                    if let Some(description) = parameter.get("description") {
+                        property.insert("description".to_string(), description.clone());
+                    }
+                    files |= property.get("format").and_then(Value::as_str) == Some("binary");
+                    if parameter.get("required") == Some(&Value::Bool(true)) {
+                        form_required.push(Value::from(name));
+                    }
+                    form_properties.insert(name.to_string(), Value::Object(property));
+                }
+                _ => {
+                    let pointer = format!("{}/parameters/{}", pointer, index);
+                    parameters.push(self.parameter(parameter, &pointer));
+                }
+            }
+        }
+        if !parameters.is_empty() {
+            result.insert("parameters".to_string(), Value::Array(parameters));
+        }
+
+        if !form_properties.is_empty() {
+            let media_type = if files || consumes.iter().any(|media| media == "multipart/form-data") {
+                "multipart/form-data"
+            } else {
+                "application/x-www-form-urlencoded"
+            };
+            let mut schema = json!({ "type": "object", "properties": form_properties });
+            if !form_required.is_empty() {
+                schema["required"] = Value::Array(form_required);
+            }
+            body = Some(json!({ "content": { media_type: { "schema": schema } } }));
+        }
+        if let Some(body) = body {
+            result.insert("requestBody".to_string(), body);
+        }
+
+        let mut responses = Map::new();
+        if let Some(Value::Object(swagger_responses)) = operation.get("responses") {
+            for (code, response) in swagger_responses {
+                responses.insert(code.clone(), response_value(response, &produces));
+            }
+        }
+        result.insert("responses".to_string(), Value::Object(responses));
+        result
+    }
+
+    /// Body and form parameters defined globally are copied in the operation, they
+    /// have no equivalent in the components of OpenAPI 3.
+    fn resolve(&self, parameter: &Value) -> Value {
+        let name = parameter
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix("#/parameters/"));
+        match name.and_then(|name| self.parameters.get(&unescape(name))) {
+            Some(global) => match global.get("in").and_then(Value::as_str) {
+                Some("body") | Some("formData") => global.clone(),
+                _ => parameter.clone(),
+            },
+            None => parameter.clone(),
+        }
+    }
+
+    /// Query, path, header parameters keep their place, the keywords about their
+    /// values move to a schema.
+    fn parameter(&mut self, parameter: &Value, pointer: &str) -> Value {
+        let parameter = match parameter {
+            Value::Object(parameter) if !parameter.contains_key("$ref") => parameter,
+            parameter => return parameter.clone(),
+        };
+        let mut result = Map::new();
+        for (key, value) in parameter {
+            if PARAMETER_KEYS.contains(&key.as_str()) || key.starts_with("x-") {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+        let schema = schema_of_parameter(parameter);
+        let is_array = schema.get("type").and_then(Value::as_str) == Some("array");
+        let location = parameter.get("in").and_then(Value::as_str).unwrap_or_default();
+        let format = parameter
+            .get("collectionFormat")
+            .and_then(Value::as_str)
+            .unwrap_or("csv");
+        if is_array {
+            let style = match (location, format) {
+                ("query", "multi") => Some(("form", true)),
+                ("query", "ssv") => Some(("spaceDelimited", false)),
+                ("query", "pipes") => Some(("pipeDelimited", false)),
+                ("query", _) => Some(("form", false)),
+                _ => None,
+            };
+            if let Some((style, explode)) = style {
+                result.insert("style".to_string(), Value::from(style));
+                result.insert("explode".to_string(), Value::Bool(explode));
+            }
+            if !matches!(format, "csv" | "multi" | "ssv" | "pipes") || (location != "query" && format != "csv") {
+                self.unsupported.push(format!("{}#{}/collectionFormat", self.uri, pointer));
+            }
+        }
+        result.insert("schema".to_string(), Value::Object(schema));
+        Value::Object(result)
+    }
+}
+
+fn servers(swagger: &Map<String, Value>) -> Value {
+    let base_path = swagger
+        .get("basePath")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .trim_end_matches('/');
+    let host = match swagger.get("host").and_then(Value::as_str) {
+        Some(host) => host,
+        None if base_path.is_empty() => return json!([{ "url": "/" }]),
+        None => return json!([{ "url": base_path }]),
+    };
+    let schemes = media_types(swagger.get("schemes")).unwrap_or_else(|| vec!["http".to_string()]);
+    let servers: Vec<Value> = schemes
+        .iter()
+        .map(|scheme| json!({ "url": format!("{}://{}{}", scheme, host, base_path) }))
+        .collect();
+    Value::Array(servers)
+}
+
+fn request_body(parameter: &Value, consumes: &[String]) -> Value {
+    let schema = parameter.get("schema").map(schema_value).unwrap_or_else(|| json!({}));
+    let content: Map<String, Value> = consumes
+        .iter()
+        .map(|media_type| (media_type.clone(), json!({ "schema": schema })))
+        .collect();
+    let mut body = json!({ "content": content });
+    for key in ["description", "required"] {
+        if let Some(value) = parameter.get(key) {
+            body[key] = value.clone();
+        }
+    }
+    body
+}
+
+fn response_value(response: &Value, produces: &[String]) -> Value {
+    let response = match response {
+        Value::Object(response) if !response.contains_key("$ref") => response,
+        response => return response.clone(),
+    };
+    let mut result = Map::new();
+    let description = response.get("description").cloned().unwrap_or_else(|| Value::from(""));
+    result.insert("description".to_string(), description);
+    for (key, value) in response {
+        if key.starts_with("x-") {
+            result.insert(key.clone(), value.clone());
+        }
+    }
+    if let Some(Value::Object(headers)) = response.get("headers") {
+        let headers: Map<String, Value> = headers
+            .iter()
+            .map(|(name, header)| {
+                let mut converted = Map::new();
+                if let Some(description) = header.get("description") {
+                    converted.insert("description".to_string(), description.clone());
+                }
+                if let Value::Object(header) = header {
+                    converted.insert("schema".to_string(), Value::Object(schema_of_parameter(header)));
+                }
+                (name.clone(), Value::Object(converted))
+            })
+            .collect();
+        result.insert("headers".to_string(), Value::Object(headers));
+    }
+    if let Some(schema) = response.get("schema") {
+        let examples = response.get("examples");
+        let content: Map<String, Value> = produces
+            .iter()
+            .map(|media_type| {
+                let mut media = json!({ "schema": schema_value(schema) });
+                if let Some(example) = examples.and_then(|examples| examples.get(media_type)) {
+                    media["example"] = example.clone();
+                }
+                (media_type.clone(), media)
+            })
+            .collect();
+        result.insert("content".to_string(), Value::Object(content));
+    }
+    Value::Object(result)
+}
+
+fn security_scheme(scheme: &Value) -> Value {
+    let mut result = match scheme.get("type").and_then(Value::as_str) {
+        Some("basic") => json!({ "type": "http", "scheme": "basic" }),
+        Some("oauth2") => {
+            let flow = match scheme.get("flow").and_then(Value::as_str) {
+                Some("application") => "clientCredentials",
+                Some("accessCode") => "authorizationCode",
+                Some("password") => "password",
+                _ => "implicit",
+            };
+            let mut converted = Map::new();
+            for key in ["authorizationUrl", "tokenUrl"] {
+                if let Some(url) = scheme.get(key) {
+                    converted.insert(key.to_string(), url.clone());
+                }
+            }
+            let scopes = scheme.get("scopes").cloned().unwrap_or_else(|| json!({}));
+            converted.insert("scopes".to_string(), scopes);
+            json!({ "type": "oauth2", "flows": { flow: converted } })
+        }
+        _ => {
+            let mut api_key = json!({ "type": "apiKey" });
+            for key in ["name", "in"] {
+                if let Some(value) = scheme.get(key) {
+                    api_key[key] = value.clone();
+                }
+            }
+            api_key
+        }
+    };
+    if let Some(description) = scheme.get("description") {
+        result["description"] = description.clone();
+    }
+    result
+}
+
+/// The keywords of a parameter, header or items object which describe its values.
+fn schema_of_parameter(parameter: &Map<String, Value>) -> Map<String, Value> {
+    let mut schema = Map::new();
+    for (key, value) in parameter {
+        if PARAMETER_KEYS.contains(&key.as_str()) || key == "collectionFormat" || key.starts_with("x-") {
+            continue;
+        }
+        let value = match (key.as_str(), value) {
+            ("items", Value::Object(items)) => Value::Object(schema_of_parameter(items)),
+            _ => value.clone(),
+        };
+        schema.insert(key.clone(), value);
+    }
+    if schema.get("type").and_then(Value::as_str) == Some("file") {
+        file_type(&mut schema);
+    }
+    schema
+}
+
+fn schema_value(schema: &Value) -> Value {
+    let mut schema = schema.clone();
+    convert_schema(&mut schema);
+    schema
+}
+
+/// The keywords of Swagger schemas which changed in OpenAPI 3.
+fn convert_schema(schema: &mut Value) {
+    let object = match schema {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    if let Some(nullable) = object.remove("x-nullable") {
+        object.insert("nullable".to_string(), nullable);
+    }
+    if let Some(Value::String(property)) = object.get("discriminator") {
+        let discriminator = json!({ "propertyName": property });
+        object.insert("discriminator".to_string(), discriminator);
+    }
+    if object.get("type").and_then(Value::as_str) == Some("file") {
+        file_type(object);
+    }
+
+    for (key, value) in object.iter_mut() {
+        match key.as_str() {
+            "properties" => {
+                if let Value::Object(properties) = value {
+                    properties.values_mut().for_each(convert_schema);
+                }
+            }
+            "allOf" => {
+                if let Value::Array(schemas) = value {
+                    schemas.iter_mut().for_each(convert_schema);
+                }
+            }
+            "items" | "additionalProperties" => convert_schema(value),
+            _ => {}
+        }
+    }
+}
+
+/// Parts of a description without a known structure, every object with the keywords
+/// which changed is a schema.
+fn fragment_schemas(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let is_schema = object.contains_key("x-nullable")
+                || object.get("discriminator").is_some_and(Value::is_string)
+                || object.get("type").and_then(Value::as_str) == Some("file");
+            if is_schema {
+                convert_schema(value);
+            }
+            if let Value::Object(object) = value {
+                for (key, value) in object.iter_mut() {
+                    if !matches!(key.as_str(), "example" | "default" | "enum") {
+                        fragment_schemas(value);
+                    }
+                }
+            }
+        }
+        Value::Array(array) => array.iter_mut().for_each(fragment_schemas),
+        _ => {}
+    }
+}
+
+fn file_type(schema: &mut Map<String, Value>) {
+    schema.insert("type".to_string(), Value::from("string"));
+    schema.insert("format".to_string(), Value::from("binary"));
+}
+
+/// Points the references to the places the elements have in OpenAPI 3.
+fn rewrite_references(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get_mut("$ref") {
+                if let Some(index) = reference.find('#') {
+                    let (document, pointer) = reference.split_at(index + 1);
+                    for (swagger, openapi) in MOVED {
+                        if let Some(rest) = pointer.strip_prefix(swagger) {
+                            *reference = format!("{}{}{}", document, openapi, rest);
+                            break;
+                        }
+                    }
+                }
+            }
+            object.values_mut().for_each(rewrite_references);
+        }
+        Value::Array(array) => array.iter_mut().for_each(rewrite_references),
+        _ => {}
+    }
+}
+
+fn media_types(value: Option<&Value>) -> Option<Vec<String>> {
+    match value {
+        Some(Value::Array(types)) if !types.is_empty() => Some(
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn json_media_type() -> Vec<String> {
+    vec!["application/json".to_string()]
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape(key: &str) -> String {
+    key.replace("~1", "/").replace("~0", "~")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapiv3::OpenAPI;
+
+    fn converted(mut document: Value) -> (Value, Vec<String>) {
+        let mut unsupported = Vec::new();
+        convert(&mut document, "/specs/api.json", &mut unsupported);
+        (document, unsupported)
+    }
+
+    fn swagger(paths: Value) -> Value {
+        json!({
+            "swagger": "2.0",
+            "info": { "title": "Test", "version": "1.0" },
+            "paths": paths
+        })
+    }
+
+    #[test]
+    fn definitions_are_schemas_of_the_components() {
+        let mut document = swagger(json!({
+            "/users": { "get": { "responses": { "200": {
+                "description": "The users",
+                "schema": { "type": "array", "items": { "$ref": "#/definitions/User" } }
+            } } } }
+        }));
+        document["definitions"] = json!({
+            "User": { "type": "object", "x-nullable": true, "properties": { "name": { "type": "string" } } }
+        });
+        let (openapi, unsupported) = converted(document);
+
+        assert!(unsupported.is_empty());
+        assert_eq!(openapi["openapi"], "3.0.3");
+        assert_eq!(openapi["components"]["schemas"]["User"]["nullable"], true);
+        let content = &openapi["paths"]["/users"]["get"]["responses"]["200"]["content"];
+        assert_eq!(
+            content["application/json"]["schema"]["items"]["$ref"],
+            "#/components/schemas/User"
+        );
+        assert!(serde_json::from_value::<OpenAPI>(openapi).is_ok());
+    }
+
+    #[test]
+    fn body_parameters_are_request_bodies() {
+        let (openapi, _) = converted(swagger(json!({
+            "/users": { "post": {
+                "consumes": ["application/json", "application/xml"],
+                "parameters": [{
+                    "name": "user", "in": "body", "required": true,
+                    "schema": { "$ref": "#/definitions/User" }
+                }],
+                "responses": { "201": { "description": "Created" } }
+            } }
+        })));
+
+        let operation = &openapi["paths"]["/users"]["post"];
+        assert!(operation.get("parameters").is_none());
+        let body = &operation["requestBody"];
+        assert_eq!(body["required"], true);
+        for media_type in ["application/json", "application/xml"] {
+            assert_eq!(body["content"][media_type]["schema"]["$ref"], "#/components/schemas/User");
+        }
+    }
+
+    #[test]
+    fn form_parameters_are_request_bodies() {
+        let (openapi, _) = converted(swagger(json!({
+            "/login": { "post": {
+                "parameters": [
+                    { "name": "user", "in": "formData", "type": "string", "required": true },
+                    { "name": "password", "in": "formData", "type": "string" }
+                ],
+                "responses": { "200": { "description": "Logged in" } }
+            } },
+            "/avatar": { "post": {
+                "parameters": [{ "name": "image", "in": "formData", "type": "file" }],
+                "responses": { "200": { "description": "Uploaded" } }
+            } }
+        })));
+
+        let login = &openapi["paths"]["/login"]["post"]["requestBody"]["content"];
+        let schema = &login["application/x-www-form-urlencoded"]["schema"];
+        assert_eq!(schema["properties"]["password"]["type"], "string");
+        assert_eq!(schema["required"], json!(["user"]));
+
+        let avatar = &openapi["paths"]["/avatar"]["post"]["requestBody"]["content"];
+        let image = &avatar["multipart/form-data"]["schema"]["properties"]["image"];
+        assert_eq!(image, &json!({ "type": "string", "format": "binary" }));
+    }
+
+    fn array_parameter(name: &str, collection_format: &str) -> Value {
+        json!({
+            "name": name,
+            "in": "query",
+            "type": "array",
+            "items": { "type": "string" },
+            "collectionFormat": collection_format
+        })
+    }
+
+    #[test]
+    fn collection_formats_are_styles() {
+        let (openapi, unsupported) = converted(swagger(json!({
+            "/items": { "get": {
+                "parameters": [
+                    array_parameter("multi", "multi"),
+                    array_parameter("ssv", "ssv"),
+                    { "name": "csv", "in": "query", "type": "array", "items": { "type": "integer" } },
+                    array_parameter("tsv", "tsv")
+                ],
+                "responses": { "200": { "description": "The items" } }
+            } }
+        })));
+
+        let parameters = &openapi["paths"]["/items"]["get"]["parameters"];
+        assert_eq!((&parameters[0]["style"], &parameters[0]["explode"]), (&json!("form"), &json!(true)));
+        assert_eq!(parameters[1]["style"], "spaceDelimited");
+        assert_eq!((&parameters[2]["style"], &parameters[2]["explode"]), (&json!("form"), &json!(false)));
+        assert_eq!(parameters[2]["schema"]["items"]["type"], "integer");
+        assert!(parameters[2].get("collectionFormat").is_none());
+        assert_eq!(
+            unsupported,
+            vec!["/specs/api.json#/paths/~1items/get/parameters/3/collectionFormat".to_string()]
+        );
+    }
+
+    #[test]
+    fn host_and_base_path_are_servers() {
+        let mut document = swagger(json!({}));
+        document["host"] = json!("api.example.com");
+        document["basePath"] = json!("/v1/");
+        document["schemes"] = json!(["https", "http"]);
+        let (openapi, _) = converted(document);
+        assert_eq!(
+            openapi["servers"],
+            json!([{ "url": "https://api.example.com/v1" }, { "url": "http://api.example.com/v1" }])
+        );
+
+        let mut document = swagger(json!({}));
+        document["basePath"] = json!("/v1");
+        let (openapi, _) = converted(document);
+        assert_eq!(openapi["servers"], json!([{ "url": "/v1" }]));
+    }
+
+    #[test]
+    fn fragments_only_have_their_schemas_converted() {
+        let (fragment, _) = converted(json!({
+            "definitions": {
+                "Page": { "type": "object", "properties": { "user": { "$ref": "#/definitions/User" } } },
+                "User": { "type": "object", "discriminator": "kind" }
+            }
+        }));
+        let schemas = &fragment["components"]["schemas"];
+        assert_eq!(schemas["Page"]["properties"]["user"]["$ref"], "#/components/schemas/User");
+        assert_eq!(schemas["User"]["discriminator"], json!({ "propertyName": "kind" }));
+        assert!(fragment.get("openapi").is_none());
+    }
+}
@@ -50,7 +50,7 @@ impl IntegerTypeExt for IntegerType {
                     minimum
                 }
             }
-            None => i64::min_value(),
+            None => i64::MIN,
         };
 
         let the_max = match self.maximum {
@@ -61,7 +61,7 @@ impl IntegerTypeExt for IntegerType {
                     maximum
                 }
             }
-            None => i64::max_value(),
+            None => i64::MAX,
         };
         (the_min, the_max)
     }
@@ -85,7 +85,7 @@ impl NumberTypeExt for NumberType {
                     minimum
                 }
             }
-            None => core::f64::MIN,
+            None => f64::MIN,
         };
 
         let the_max = match self.maximum {
@@ -96,7 +96,7 @@ impl NumberTypeExt for NumberType {
                     maximum
                 }
             }
-            None => core::f64::MAX,
+            None => f64::MAX,
         };
         (the_min, the_max)
     }
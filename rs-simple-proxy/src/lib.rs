@@ -8,16 +8,27 @@ pub mod middlewares;
 pub mod proxy;
 
 use futures::future::Future;
+use futures::Stream;
 use hyper::server::conn::AddrStream;
 use hyper::service::make_service_fn;
 use hyper::Server;
+use native_tls::TlsAcceptor;
 use std::fmt;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use http::uri::Authority;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::timer::Timeout;
+use tokio_tls::TlsStream;
 
 use crate::proxy::middleware::Middleware;
 use crate::proxy::service::ProxyService;
 
+/// Connections in their TLS handshake at the same time, and how long they can take.
+const MAX_TLS_HANDSHAKES: usize = 64;
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 type Middlewares = Arc<Mutex<Vec<Box<Middleware + Send + Sync>>>>;
 
 #[derive(Debug, Clone, Copy)]
@@ -55,6 +66,9 @@ pub struct SimpleProxy {
     backend: Authority,
     environment: Environment,
     middlewares: Middlewares,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    tls: Option<TlsAcceptor>,
 }
 
 impl SimpleProxy {
@@ -64,9 +78,28 @@ impl SimpleProxy {
             backend,
             environment,
             middlewares: Arc::new(Mutex::new(vec![])),
+            timeout: None,
+            max_body_size: None,
+            tls: None,
         }
     }
 
+    /// Serves HTTPS instead of HTTP, with the certificate of `acceptor`.
+    pub fn set_tls(&mut self, acceptor: TlsAcceptor) {
+        self.tls = Some(acceptor);
+    }
+
+    /// Answers `504 Gateway Timeout` when the backend takes longer than `timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Answers `413 Payload Too Large` to requests with bodies larger than `max_body_size`
+    /// bytes, when a middleware reads them. Larger backend responses become a `502 Bad Gateway`.
+    pub fn set_max_body_size(&mut self, max_body_size: usize) {
+        self.max_body_size = Some(max_body_size);
+    }
+
     pub fn run(&self) {
         let addr = ([0, 0, 0, 0], self.port).into();
         let backend = self.backend.clone();
@@ -78,15 +111,61 @@ impl SimpleProxy {
         info!("Running proxy in {} mode on: {}", self.environment, &addr);
 
         let middlewares = Arc::clone(&self.middlewares);
-        let make_svc = make_service_fn(move |socket: &AddrStream| {
-            let remote_addr = socket.remote_addr();
-
+        let timeout = self.timeout;
+        let max_body_size = self.max_body_size;
+        let service = move |remote_addr: SocketAddr| {
             debug!("Handling connection for IP: {}", &remote_addr);
 
             ProxyService::new(middlewares.clone(), remote_addr, backend.clone())
+                .with_timeout(timeout)
+                .with_max_body_size(max_body_size)
+        };
+
+        let acceptor = match &self.tls {
+            Some(acceptor) => tokio_tls::TlsAcceptor::from(acceptor.clone()),
+            None => {
+                let make_svc = make_service_fn(move |socket: &AddrStream| service(socket.remote_addr()));
+                let server = Server::bind(&addr)
+                    .serve(make_svc)
+                    .map_err(|e| eprintln!("server error: {}", e));
+                hyper::rt::run(server);
+                return;
+            }
+        };
+
+        let listener = TcpListener::bind(&addr).expect("The port of the proxy is not available.");
+        // A connection which fails or takes too long in its handshake is dropped, the
+        // others go on.
+        let incoming = listener
+            .incoming()
+            .then(|socket| match socket {
+                Ok(socket) => Ok(Some(socket)),
+                Err(error) => {
+                    warn!("Could not accept a connection: {}", error);
+                    Ok::<_, std::io::Error>(None)
+                }
+            })
+            .filter_map(|socket| socket)
+            .map(move |socket| {
+                Timeout::new(acceptor.accept(socket), TLS_HANDSHAKE_TIMEOUT).then(|stream| match stream {
+                    Ok(stream) => Ok(Some(stream)),
+                    Err(error) => {
+                        debug!("TLS handshake failed: {:?}", error);
+                        Ok(None)
+                    }
+                })
+            })
+            .buffer_unordered(MAX_TLS_HANDSHAKES)
+            .filter_map(|stream| stream);
+        let make_svc = make_service_fn(move |stream: &TlsStream<TcpStream>| {
+            let remote_addr = stream
+                .get_ref()
+                .get_ref()
+                .peer_addr()
+                .unwrap_or_else(|_| ([0, 0, 0, 0], 0).into());
+            service(remote_addr)
         });
-
-        let server = Server::bind(&addr)
+        let server = Server::builder(incoming)
             .serve(make_svc)
             .map_err(|e| eprintln!("server error: {}", e));
 
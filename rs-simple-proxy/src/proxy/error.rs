@@ -6,6 +6,7 @@ pub struct MiddlewareError {
     pub description: String,
     pub body: String,
     pub status: StatusCode,
+    pub content_type: String,
 }
 
 impl From<MiddlewareError> for Response<Body> {
@@ -32,12 +33,19 @@ impl MiddlewareError {
             description,
             status,
             body,
+            content_type: String::from("application/json"),
         }
     }
 
+    /// Changes the Content-Type of the response, `application/json` by default.
+    pub fn with_content_type(mut self, content_type: &str) -> MiddlewareError {
+        self.content_type = content_type.to_string();
+        self
+    }
+
     pub fn to_json_response(&self) -> Response<Body> {
         Response::builder()
-            .header("Content-Type", "application/json")
+            .header("Content-Type", self.content_type.as_str())
             .status(self.status)
             .body(Body::from(format!("{}", self.body)))
             .unwrap()
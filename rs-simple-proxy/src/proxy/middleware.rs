@@ -58,6 +58,17 @@ pub trait Middleware {
         Self::state(req_id, state)
     }
 
+    /// True when the middleware reads the body of the request, which is then buffered
+    /// and available as a `BufferedBody`. Bodies nobody reads are streamed to the backend.
+    fn reads_request_body(&mut self, _req: &Request<Body>, _ctx: &ServiceContext, _state: &State) -> bool {
+        false
+    }
+
+    /// True when the middleware reads the body of the backend response, see `reads_request_body`.
+    fn reads_response_body(&mut self, _res: &Response<Body>, _ctx: &ServiceContext, _state: &State) -> bool {
+        false
+    }
+
     fn before_request(
         &mut self,
         _req: &mut Request<Body>,
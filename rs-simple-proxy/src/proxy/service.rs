@@ -1,15 +1,19 @@
+use bytes::Bytes;
 use futures::future;
-use futures::future::IntoFuture;
+use futures::future::{Either, IntoFuture};
+use futures::Stream;
 
 use hyper::client::connect::HttpConnector;
 use hyper::rt::Future;
 use hyper::service::Service;
-use hyper::{Body, Client, Request, Response};
+use hyper::{Body, Client, Request, Response, StatusCode};
 use http::uri::Authority;
+use tokio::timer::Timeout;
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use rand::prelude::*;
 use rand::rngs::SmallRng;
@@ -28,6 +32,8 @@ pub struct ProxyService {
     remote_addr: SocketAddr,
     backend: Authority,
     rng: SmallRng,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
 }
 
 #[derive(Clone, Copy)]
@@ -54,114 +60,252 @@ impl Service for ProxyService {
             .unwrap();
         parts.uri = uri;
 
-        let mut req = Request::from_parts(parts, body);
-
-        debug!("request reached the proxy {:?}", req);
-
         // Create references for future callbacks
         // references are moved in each chained future (map,then..)
+        let client = self.client.clone();
+        let mws_read = Arc::clone(&self.middlewares);
+        let mws_before = Arc::clone(&self.middlewares);
         let mws_failure = Arc::clone(&self.middlewares);
         let mws_success = Arc::clone(&self.middlewares);
         let mws_after = Arc::clone(&self.middlewares);
+        let state_read = Arc::clone(&self.state);
+        let state_before = Arc::clone(&self.state);
         let state_failure = Arc::clone(&self.state);
         let state_success = Arc::clone(&self.state);
         let state_after = Arc::clone(&self.state);
 
         let req_id = self.rng.next_u64();
+        let timeout = self.timeout;
+        let max_body_size = self.max_body_size;
 
         let context = ServiceContext {
             req_id,
             remote_addr: self.remote_addr,
         };
 
-        let mut before_res: Option<Response<Body>> = None;
-        for mw in self.middlewares.lock().unwrap().iter_mut() {
-            // Run all middlewares->before_request
-            if let Some(res) = match mw.before_request(&mut req, &context, &self.state) {
-                Err(err) => Some(Response::from(err)),
-                Ok(RespondWith(response)) => Some(response),
-                Ok(Next) => None,
-            } {
-                // Stop when an early response is wanted
-                before_res = Some(res);
-                break;
-            }
-        }
+        let req = Request::from_parts(parts, body);
+        let reads_body = self
+            .middlewares
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .any(|mw| mw.reads_request_body(&req, &context, &self.state));
+        let res = read_request(req, reads_body, max_body_size).and_then(move |req| {
+            let mut req = match req {
+                Ok(req) => req,
+                Err(res) => {
+                    let res = early_response(&mws_before, &context, res, &state_before);
+                    return Either::A(future::ok(res));
+                }
+            };
 
-        if let Some(res) = before_res {
-            return Box::new(future::ok(self.early_response(&context, res, &self.state)));
-        }
+            debug!("request reached the proxy {:?}", req);
 
-        let res = self
-            .client
-            .request(req)
-            .map_err(move |err| {
-                for mw in mws_failure.lock().unwrap().iter_mut() {
-                    // TODO: think about graceful handling
-                    if let Err(err) = mw.request_failure(&err, &context, &state_failure) {
-                        error!("Request_failure errored: {:?}", &err);
-                    }
+            let mut before_res: Option<Response<Body>> = None;
+            for mw in mws_before.lock().unwrap().iter_mut() {
+                // Run all middlewares->before_request
+                if let Some(res) = match mw.before_request(&mut req, &context, &state_before) {
+                    Err(err) => Some(Response::from(err)),
+                    Ok(RespondWith(response)) => Some(response),
+                    Ok(Next) => None,
+                } {
+                    // Stop when an early response is wanted
+                    before_res = Some(res);
+                    break;
                 }
-                err
-            })
-            .map(move |mut res| {
-                for mw in mws_success.lock().unwrap().iter_mut() {
-                    match mw.request_success(&mut res, &context, &state_success) {
-                        Err(err) => res = Response::from(err),
-                        Ok(RespondWith(response)) => res = response,
-                        Ok(Next) => (),
+            }
+
+            if let Some(res) = before_res {
+                let res = early_response(&mws_before, &context, res, &state_before);
+                return Either::A(future::ok(res));
+            }
+
+            let res = client.request(req).and_then(move |res| {
+                let reads_body = mws_read
+                    .lock()
+                    .unwrap()
+                    .iter_mut()
+                    .any(|mw| mw.reads_response_body(&res, &context, &state_read));
+                read_response(res, reads_body, max_body_size)
+            });
+            let res = match timeout {
+                Some(timeout) => Either::A(Timeout::new(res, timeout).or_else(|err| match err.into_inner() {
+                    Some(err) => Err(err),
+                    None => {
+                        error!("The backend did not answer in time");
+                        Ok(gateway_timeout())
                     }
-                }
-                res
-            })
-            .then(move |res| match res {
-                // Allows middlewares to catch errors after requests
-                Err(err) => {
-                    let mut res = Err(err);
-                    for mw in mws_after.lock().unwrap().iter_mut() {
-                        match mw.after_request(None, &context, &state_after) {
-                            Err(err) => res = Ok(Response::from(err)),
-                            Ok(RespondWith(response)) => res = Ok(response),
-                            Ok(Next) => (),
+                })),
+                None => Either::B(res),
+            };
+            let res = res
+                .map_err(move |err| {
+                    for mw in mws_failure.lock().unwrap().iter_mut() {
+                        // TODO: think about graceful handling
+                        if let Err(err) = mw.request_failure(&err, &context, &state_failure) {
+                            error!("Request_failure errored: {:?}", &err);
                         }
                     }
-                    res
-                }
-                // Allows middlewares to change the response after requests
-                Ok(mut res) => {
-                    for mw in mws_after.lock().unwrap().iter_mut() {
-                        match mw.after_request(Some(&mut res), &context, &state_after) {
+                    err
+                })
+                .map(move |mut res| {
+                    for mw in mws_success.lock().unwrap().iter_mut() {
+                        match mw.request_success(&mut res, &context, &state_success) {
                             Err(err) => res = Response::from(err),
                             Ok(RespondWith(response)) => res = response,
                             Ok(Next) => (),
                         }
                     }
-                    Ok(res)
-                }
-            });
+                    res
+                })
+                .then(move |res| match res {
+                    // Allows middlewares to catch errors after requests
+                    Err(err) => {
+                        let mut res = Err(err);
+                        for mw in mws_after.lock().unwrap().iter_mut() {
+                            match mw.after_request(None, &context, &state_after) {
+                                Err(err) => res = Ok(Response::from(err)),
+                                Ok(RespondWith(response)) => res = Ok(response),
+                                Ok(Next) => (),
+                            }
+                        }
+                        res
+                    }
+                    // Allows middlewares to change the response after requests
+                    Ok(mut res) => {
+                        for mw in mws_after.lock().unwrap().iter_mut() {
+                            match mw.after_request(Some(&mut res), &context, &state_after) {
+                                Err(err) => res = Response::from(err),
+                                Ok(RespondWith(response)) => res = response,
+                                Ok(Next) => (),
+                            }
+                        }
+                        Ok(res)
+                    }
+                });
+
+            Either::B(res)
+        });
 
         Box::new(res)
     }
 }
 
-impl ProxyService {
-    fn early_response(
-        &self,
-        context: &ServiceContext,
-        mut res: Response<Body>,
-        state: &State,
-    ) -> Response<Body> {
-        for mw in self.middlewares.lock().unwrap().iter_mut() {
-            match mw.after_request(Some(&mut res), context, state) {
-                Err(err) => res = Response::from(err),
-                Ok(RespondWith(response)) => res = response,
-                Ok(Next) => (),
+/// Contents of a request or response body, buffered by the proxy.
+/// It is available in the extensions of the request and the backend response
+/// when a middleware reads the body, so they can read it without consuming it.
+#[derive(Clone, Debug)]
+pub struct BufferedBody(pub Bytes);
+
+/// Marks the `502 Bad Gateway` which replaces a backend response too large to be read,
+/// middlewares should not check it like a response of the backend.
+#[derive(Clone, Copy, Debug)]
+pub struct BodyTooLarge;
+
+/// Buffers the body of the request when a middleware reads it. Bodies larger than
+/// `max_size` are answered with `413 Payload Too Large`, which is the error.
+fn read_request(
+    req: Request<Body>,
+    reads_body: bool,
+    max_size: Option<usize>,
+) -> impl Future<Item = Result<Request<Body>, Response<Body>>, Error = hyper::Error> {
+    if !reads_body {
+        return Either::A(future::ok(Ok(req)));
+    }
+    let (parts, body) = req.into_parts();
+    Either::B(buffer_body(body, max_size).map(move |bytes| match bytes {
+        Some(bytes) => {
+            let mut req = Request::from_parts(parts, Body::from(bytes.clone()));
+            req.extensions_mut().insert(BufferedBody(bytes));
+            Ok(req)
+        }
+        None => Err(payload_too_large()),
+    }))
+}
+
+/// Buffers the body of the response when a middleware reads it. Responses larger than
+/// `max_size` are replaced by a `502 Bad Gateway`, as they can't be checked.
+fn read_response(
+    res: Response<Body>,
+    reads_body: bool,
+    max_size: Option<usize>,
+) -> impl Future<Item = Response<Body>, Error = hyper::Error> {
+    if !reads_body {
+        return Either::A(future::ok(res));
+    }
+    let (parts, body) = res.into_parts();
+    Either::B(buffer_body(body, max_size).map(move |bytes| match bytes {
+        Some(bytes) => {
+            let mut res = Response::from_parts(parts, Body::from(bytes.clone()));
+            res.extensions_mut().insert(BufferedBody(bytes));
+            res
+        }
+        None => {
+            error!("The response of the backend is larger than the maximum body size");
+            response_too_large()
+        }
+    }))
+}
+
+/// The whole body, None as soon as it is larger than `max_size`.
+fn buffer_body(body: Body, max_size: Option<usize>) -> impl Future<Item = Option<Bytes>, Error = hyper::Error> {
+    // A None error stops reading a body which is too large.
+    body.map_err(Some)
+        .fold(Vec::new(), move |mut buffer, chunk| {
+            if max_size.is_some_and(|max_size| buffer.len() + chunk.len() > max_size) {
+                return Err(None);
             }
+            buffer.extend_from_slice(&chunk);
+            Ok(buffer)
+        })
+        .then(|result| match result {
+            Ok(buffer) => Ok(Some(Bytes::from(buffer))),
+            Err(None) => Ok(None),
+            Err(Some(err)) => Err(err),
+        })
+}
+
+fn payload_too_large() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .body(Body::from("The body of the request is too large."))
+        .unwrap()
+}
+
+fn response_too_large() -> Response<Body> {
+    let mut res = Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(Body::from("The response of the backend is too large."))
+        .unwrap();
+    res.extensions_mut().insert(BodyTooLarge);
+    res
+}
+
+fn gateway_timeout() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::GATEWAY_TIMEOUT)
+        .body(Body::from("The backend did not answer in time."))
+        .unwrap()
+}
+
+fn early_response(
+    middlewares: &Middlewares,
+    context: &ServiceContext,
+    mut res: Response<Body>,
+    state: &State,
+) -> Response<Body> {
+    for mw in middlewares.lock().unwrap().iter_mut() {
+        match mw.after_request(Some(&mut res), context, state) {
+            Err(err) => res = Response::from(err),
+            Ok(RespondWith(response)) => res = response,
+            Ok(Next) => (),
         }
-        debug!("Early response is {:?}", &res);
-        res
     }
+    debug!("Early response is {:?}", &res);
+    res
+}
 
+impl ProxyService {
     // Needed to avoid a single connection creating too much data in state
     // Since we need to identify each request in state (HashMap tuple identifier), it grows
     // for each request from the same connection
@@ -181,8 +325,22 @@ impl ProxyService {
             remote_addr,
             backend,
             middlewares,
+            timeout: None,
+            max_body_size: None,
         }
     }
+
+    /// Requests to the backend which take longer than `timeout` are answered with a 504.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Bodies the middlewares read, see `BufferedBody`, can't be larger than `max_body_size`.
+    pub fn with_max_body_size(mut self, max_body_size: Option<usize>) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
 }
 
 impl IntoFuture for ProxyService {
@@ -194,3 +352,69 @@ impl IntoFuture for ProxyService {
         future::ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A body sent in several chunks, like the ones hyper reads from a connection.
+    fn chunked(chunks: &[&'static str]) -> Body {
+        Body::wrap_stream(futures::stream::iter_ok::<_, hyper::Error>(chunks.to_vec()))
+    }
+
+    fn contents(body: Body) -> Vec<u8> {
+        body.concat2().wait().unwrap().to_vec()
+    }
+
+    #[test]
+    fn bodies_are_buffered_up_to_the_maximum_size() {
+        let body = buffer_body(chunked(&["{\"id\":", "1}"]), Some(8)).wait().unwrap();
+        assert_eq!(body, Some(Bytes::from("{\"id\":1}")));
+        let body = buffer_body(chunked(&["{\"id\":", "10}"]), Some(8)).wait().unwrap();
+        assert_eq!(body, None);
+        let body = buffer_body(chunked(&["{\"id\":", "10}"]), None).wait().unwrap();
+        assert_eq!(body, Some(Bytes::from("{\"id\":10}")));
+    }
+
+    #[test]
+    fn read_requests_keep_their_body_and_expose_it() {
+        let req = Request::new(chunked(&["hello ", "world"]));
+        let req = read_request(req, true, Some(11)).wait().unwrap().unwrap();
+        assert_eq!(req.extensions().get::<BufferedBody>().unwrap().0, Bytes::from("hello world"));
+        assert_eq!(contents(req.into_body()), b"hello world");
+    }
+
+    #[test]
+    fn unread_requests_are_streamed() {
+        let req = Request::new(chunked(&["hello ", "world"]));
+        let req = read_request(req, false, Some(1)).wait().unwrap().unwrap();
+        assert!(req.extensions().get::<BufferedBody>().is_none());
+        assert_eq!(contents(req.into_body()), b"hello world");
+    }
+
+    #[test]
+    fn requests_too_large_are_answered_with_413() {
+        let req = Request::new(chunked(&["hello ", "world"]));
+        let res = read_request(req, true, Some(10)).wait().unwrap().unwrap_err();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn read_responses_keep_their_body_and_expose_it() {
+        let res = Response::new(chunked(&["hello ", "world"]));
+        let res = read_response(res, true, Some(11)).wait().unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.extensions().get::<BufferedBody>().unwrap().0, Bytes::from("hello world"));
+        assert!(res.extensions().get::<BodyTooLarge>().is_none());
+        assert_eq!(contents(res.into_body()), b"hello world");
+    }
+
+    #[test]
+    fn responses_too_large_are_replaced_with_502() {
+        let res = Response::new(chunked(&["hello ", "world"]));
+        let res = read_response(res, true, Some(10)).wait().unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        assert!(res.extensions().get::<BodyTooLarge>().is_some());
+        assert!(res.extensions().get::<BufferedBody>().is_none());
+    }
+}
@@ -0,0 +1,678 @@
+use http::uri::Authority;
+use http::StatusCode;
+use native_tls::{Identity, TlsAcceptor};
+use oas_middleware::{Api, OperationOptions, Options};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::Config;
+
+const DEFAULT_BACKEND: &str = "localhost:3000";
+const DEFAULT_PORT: u16 = 5000;
+const DEFAULT_INPUT: &str = "/tmp/openapi.yaml";
+const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Environment variables with this prefix and `__` between the keys replace values of the
+/// configuration file, `OAS_VALIDATION__ERROR_STATUS=422` is `error_status` in `validation`.
+const ENV_PREFIX: &str = "OAS_";
+const ENV_SEPARATOR: &str = "__";
+
+/// The configuration file of the proxy, in YAML or TOML.
+///
+/// ```yaml
+/// backend: localhost:3000
+/// port: 5000
+/// apis:
+///   - spec: users.yaml
+///     backend: localhost:3001
+///     prefix: /users-api
+///     block_responses: true
+///   - spec: https://pets.example.com/openapi.yaml
+///     backend: localhost:3002
+/// validation:
+///   strict: true
+///   error_status: 422
+///   match_host: true
+///   reload_path: /admin/reload
+///   reload_token: secret
+///   ignored_paths: [/metrics, /internal/*]
+///   operations:
+///     uploadAvatar: {validate: false}
+///     listUsers: {observe: true}
+/// logging:
+///   level: info,oas_middleware=debug
+/// timeouts:
+///   backend: 30
+/// limits:
+///   body_size: 1048576
+/// tls:
+///   certificate: proxy.crt
+///   key: proxy.key
+/// middlewares: [health, logger, oas]
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigFile {
+    /// Where requests are proxied to when their API has no backend.
+    pub backend: Option<String>,
+    pub port: Option<u16>,
+    /// The OpenAPI file when there is a single API.
+    pub input: Option<PathBuf>,
+    /// The APIs behind the proxy, every request goes to the one with a matching path.
+    pub apis: Vec<ApiConfig>,
+    pub validation: ValidationConfig,
+    pub logging: LoggingConfig,
+    pub timeouts: TimeoutsConfig,
+    pub limits: LimitsConfig,
+    pub tls: Option<TlsConfig>,
+    /// The order requests go through the middlewares, `[health, oas]` by default.
+    pub middlewares: Option<Vec<MiddlewareName>>,
+    /// Problems which don't stop the proxy, logged once the logger is ready.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    /// The OpenAPI file, relative to the configuration file.
+    pub spec: PathBuf,
+    /// Where the requests to this API go, the backend of the proxy by default.
+    pub backend: Option<String>,
+    /// Path the requests to this API start with, removed before proxying.
+    #[serde(default)]
+    pub prefix: String,
+    /// Replace the responses of this API which break the contract with a 502.
+    pub block_responses: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct ValidationConfig {
+    pub strict: bool,
+    pub error_status: Option<u16>,
+    pub observe: bool,
+    /// Replace the responses which break the contract with a 502 instead of logging them.
+    pub block_responses: bool,
+    pub mock: bool,
+    pub watch: bool,
+    /// `POST` requests to this path reload the specs, it needs `reload_token`.
+    pub reload_path: Option<String>,
+    pub reload_token: Option<String>,
+    /// Match requests to the servers of the specs by their Host header.
+    pub match_host: bool,
+    /// Proxy requests to paths which are not described instead of answering 404.
+    pub proxy_undocumented: bool,
+    pub ignored_paths: Vec<String>,
+    /// Settings of single operations, by operationId.
+    pub operations: BTreeMap<String, OperationConfig>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct OperationConfig {
+    pub strict: Option<bool>,
+    pub observe: Option<bool>,
+    pub block_responses: Option<bool>,
+    pub mock: Option<bool>,
+    pub validate: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct LoggingConfig {
+    /// Filter with the syntax of `RUST_LOG`, which replaces it when it is set.
+    pub level: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct TimeoutsConfig {
+    /// Seconds to wait for the backend before answering with a 504.
+    pub backend: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct LimitsConfig {
+    /// Bytes of the largest body which is checked, larger requests get a 413. 10 MiB by default.
+    pub body_size: Option<usize>,
+}
+
+/// The proxy serves HTTPS with this certificate.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the certificate, then the intermediate certificates, relative to the
+    /// configuration file.
+    pub certificate: PathBuf,
+    /// PEM file with the PKCS #8 private key of the certificate.
+    pub key: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MiddlewareName {
+    Health,
+    Logger,
+    Oas,
+}
+
+/// Everything the proxy needs to start, from the flags, the environment and the
+/// configuration file, in that order of preference.
+#[derive(Debug)]
+pub struct Settings {
+    pub backend: Authority,
+    pub port: u16,
+    pub apis: Vec<Api>,
+    pub options: Options,
+    pub log_filter: Option<String>,
+    pub timeout: Option<Duration>,
+    pub max_body_size: usize,
+    pub tls: Option<Tls>,
+    pub middlewares: Vec<MiddlewareName>,
+    pub warnings: Vec<String>,
+}
+
+/// The certificate the proxy serves HTTPS with, ready to accept connections.
+pub struct Tls {
+    pub certificate: PathBuf,
+    pub acceptor: TlsAcceptor,
+}
+
+impl fmt::Debug for Tls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tls").field("certificate", &self.certificate).finish()
+    }
+}
+
+impl ConfigFile {
+    /// Reads the file, YAML unless it ends on `.toml`, and applies the environment variables.
+    pub fn read(filename: Option<&Path>) -> Result<Self, String> {
+        Self::read_with(filename, std::env::vars())
+    }
+
+    /// Reads the file and applies the `OAS_SECTION__KEY` settings among `variables`.
+    fn read_with(filename: Option<&Path>, variables: impl Iterator<Item = (String, String)>) -> Result<Self, String> {
+        let mut value = match filename {
+            Some(filename) => {
+                let data = std::fs::read_to_string(filename).map_err(|error| {
+                    format!("The configuration file `{}` could not be read: {}.", filename.display(), error)
+                })?;
+                let is_toml = filename.extension().is_some_and(|extension| extension == "toml");
+                let value = if is_toml {
+                    toml::from_str(&data).map_err(|error| error.to_string())
+                } else {
+                    serde_yaml::from_str(&data).map_err(|error| error.to_string())
+                };
+                value.map_err(|error| {
+                    format!("The configuration file `{}` is not valid: {}.", filename.display(), error)
+                })?
+            }
+            None => Value::Mapping(Mapping::new()),
+        };
+        if value.is_null() {
+            value = Value::Mapping(Mapping::new());
+        }
+        let mut variables: Vec<(String, Vec<String>, String)> = variables
+            .filter_map(|(name, setting)| env_keys(&name).map(|keys| (name, keys, setting)))
+            .collect();
+        let mut warnings = Vec::new();
+        loop {
+            let mut merged = value.clone();
+            for (_, keys, setting) in &variables {
+                apply_env(&mut merged, keys, setting);
+            }
+            let error = match serde_path_to_error::deserialize::<_, ConfigFile>(merged) {
+                Ok(mut config) => {
+                    config.warnings = warnings;
+                    return Ok(config);
+                }
+                Err(error) => error,
+            };
+            // Other programs can use variables which look like ours, the ones which are not
+            // settings are left out.
+            let path = error.path().to_string();
+            let unknown = error.inner().to_string().starts_with("unknown field");
+            let position = variables.iter().position(|(_, keys, _)| {
+                let keys = keys.join(".");
+                keys == path || keys.starts_with(&format!("{}.", path))
+            });
+            match position {
+                Some(position) if unknown => {
+                    let (name, _, _) = variables.remove(position);
+                    warnings.push(format!("The environment variable {} is not a setting, ignoring it.", name));
+                }
+                _ => {
+                    let source = filename.map_or_else(
+                        || String::from("The environment"),
+                        |filename| format!("The configuration file `{}`", filename.display()),
+                    );
+                    return Err(format!("{} is not valid at `{}`: {}.", source, path, error.inner()));
+                }
+            }
+        }
+    }
+
+    /// Checks the settings and joins them with the flags. Every problem is reported.
+    pub fn settings(self, config: &Config) -> Result<Settings, String> {
+        let mut errors = Vec::new();
+        let directory = config
+            .config
+            .as_ref()
+            .and_then(|filename| filename.parent())
+            .unwrap_or_else(|| Path::new(""));
+
+        let backend = match (&config.backend, &self.backend) {
+            (Some(backend), _) => backend.clone(),
+            (None, Some(backend)) => parse_backend(backend, "backend", &mut errors),
+            (None, None) => DEFAULT_BACKEND.parse().expect("The default backend is valid."),
+        };
+        let port = config.port.or(self.port).unwrap_or(DEFAULT_PORT);
+
+        if !self.apis.is_empty() && self.input.is_some() {
+            errors.push(String::from("Use either `input` or `apis`, not both."));
+        }
+        let apis = if self.apis.is_empty() {
+            let input = match (&config.input, &self.input) {
+                (Some(input), _) => input.clone(),
+                (None, Some(input)) => relative_to(directory, input),
+                (None, None) => PathBuf::from(DEFAULT_INPUT),
+            };
+            vec![Api::new(input)]
+        } else {
+            self.apis
+                .iter()
+                .map(|api_config| {
+                    let mut api = Api::new(relative_to(directory, &api_config.spec))
+                        .with_prefix(&api_config.prefix);
+                    if let Some(block_responses) = api_config.block_responses {
+                        api = api.with_block_responses(block_responses);
+                    }
+                    if let Some(backend) = &api_config.backend {
+                        let place = format!("backend of {}", api_config.spec.display());
+                        api = api.with_backend(parse_backend(backend, &place, &mut errors));
+                    }
+                    api
+                })
+                .collect()
+        };
+
+        let validation = self.validation;
+        let error_status = match (config.error_status, validation.error_status) {
+            (Some(status), _) => status,
+            (None, Some(status)) => match StatusCode::from_u16(status) {
+                Ok(status) if status.is_client_error() || status.is_server_error() => status,
+                _ => {
+                    errors.push(format!("`error_status` is {}, it must be between 400 and 599.", status));
+                    StatusCode::BAD_REQUEST
+                }
+            },
+            (None, None) => StatusCode::BAD_REQUEST,
+        };
+        for path in &validation.ignored_paths {
+            if !path.starts_with('/') {
+                errors.push(format!("The ignored path `{}` must start with /.", path));
+            }
+        }
+        let reload_path = config.reload_path.clone().or(validation.reload_path);
+        let reload_token = config.reload_token.clone().or(validation.reload_token);
+        match (&reload_path, &reload_token) {
+            (Some(path), _) if !path.starts_with('/') => {
+                errors.push(format!("The reload path `{}` must start with /.", path));
+            }
+            (Some(_), None) => {
+                errors.push(String::from("The reload path needs a reload token."));
+            }
+            (None, Some(_)) => {
+                errors.push(String::from("The reload token needs a reload path."));
+            }
+            _ => {}
+        }
+        let operations = validation
+            .operations
+            .into_iter()
+            .map(|(operation_id, operation)| {
+                let options = OperationOptions {
+                    strict_query: operation.strict,
+                    observe: operation.observe,
+                    block_responses: operation.block_responses,
+                    mock: operation.mock,
+                    validate: operation.validate,
+                };
+                (operation_id, options)
+            })
+            .collect();
+        let options = Options {
+            strict_query: config.strict || validation.strict,
+            error_status,
+            observe: config.observe || validation.observe,
+            block_responses: config.block_responses || validation.block_responses,
+            mock: config.mock || validation.mock,
+            watch: config.watch || validation.watch,
+            reload_path,
+            reload_token,
+            validate: true,
+            match_host: config.match_host || validation.match_host,
+            proxy_undocumented: config.proxy_undocumented || validation.proxy_undocumented,
+            ignored_paths: validation.ignored_paths,
+            operations,
+        };
+
+        if let Some(level) = &self.logging.level {
+            check_log_filter(level, &mut errors);
+        }
+
+        let timeout = match self.timeouts.backend {
+            Some(seconds) if seconds.is_finite() && seconds > 0.0 => Some(Duration::from_secs_f64(seconds)),
+            Some(seconds) => {
+                errors.push(format!("The backend timeout is {}, it must be a positive number of seconds.", seconds));
+                None
+            }
+            None => None,
+        };
+
+        let max_body_size = match self.limits.body_size {
+            Some(0) => {
+                errors.push(String::from("The body size limit must be a positive number of bytes."));
+                DEFAULT_MAX_BODY_SIZE
+            }
+            Some(body_size) => body_size,
+            None => DEFAULT_MAX_BODY_SIZE,
+        };
+
+        let tls = match &self.tls {
+            Some(tls) => read_tls(&relative_to(directory, &tls.certificate), &relative_to(directory, &tls.key))
+                .map_err(|error| errors.push(error))
+                .ok(),
+            None => None,
+        };
+
+        let middlewares = self
+            .middlewares
+            .unwrap_or_else(|| vec![MiddlewareName::Health, MiddlewareName::Oas]);
+        for (index, middleware) in middlewares.iter().enumerate() {
+            if middlewares[..index].contains(middleware) {
+                errors.push(format!("The middleware {} is listed more than once.", format!("{:?}", middleware).to_lowercase()));
+            }
+        }
+        if !middlewares.contains(&MiddlewareName::Oas) {
+            errors.push(String::from("The middlewares must include oas."));
+        }
+
+        if !errors.is_empty() {
+            return Err(format!("The configuration is not valid:\n  - {}", errors.join("\n  - ")));
+        }
+        Ok(Settings {
+            backend,
+            port,
+            apis,
+            options,
+            log_filter: self.logging.level,
+            timeout,
+            max_body_size,
+            tls,
+            middlewares,
+            warnings: self.warnings,
+        })
+    }
+}
+
+/// The keys of an `OAS_SECTION__KEY` variable. Keys are lower case, but for the
+/// operationIds in `OAS_VALIDATION__OPERATIONS__listUsers__OBSERVE`.
+fn env_keys(name: &str) -> Option<Vec<String>> {
+    let keys = match name.strip_prefix(ENV_PREFIX) {
+        Some(keys) if keys.contains(ENV_SEPARATOR) => keys,
+        _ => return None,
+    };
+    let keys: Vec<&str> = keys.split(ENV_SEPARATOR).collect();
+    let keys = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let is_operation_id = index > 0 && keys[index - 1].eq_ignore_ascii_case("operations");
+            if is_operation_id {
+                key.to_string()
+            } else {
+                key.to_lowercase()
+            }
+        })
+        .collect();
+    Some(keys)
+}
+
+/// Sets the value of the keys of an environment variable.
+fn apply_env(value: &mut Value, keys: &[String], setting: &str) {
+    let mut current = value;
+    for (index, key) in keys.iter().enumerate() {
+        if !current.is_mapping() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        let mapping = match current {
+            Value::Mapping(mapping) => mapping,
+            _ => return,
+        };
+        let key = Value::String(key.clone());
+        if index == keys.len() - 1 {
+            // `true`, `422` or `[/metrics]` keep their type.
+            let parsed = serde_yaml::from_str(setting).unwrap_or_else(|_| Value::String(setting.to_string()));
+            mapping.insert(key, parsed);
+            return;
+        }
+        if !mapping.contains_key(&key) {
+            mapping.insert(key.clone(), Value::Mapping(Mapping::new()));
+        }
+        current = mapping.get_mut(&key).expect("The key was just inserted.");
+    }
+}
+
+fn parse_backend(backend: &str, place: &str, errors: &mut Vec<String>) -> Authority {
+    backend.parse().unwrap_or_else(|error| {
+        errors.push(format!("The {} `{}` is not valid: {}.", place, backend, error));
+        DEFAULT_BACKEND.parse().expect("The default backend is valid.")
+    })
+}
+
+/// Specs in the configuration file are relative to it, URLs are kept.
+fn relative_to(directory: &Path, spec: &Path) -> PathBuf {
+    let location = spec.to_string_lossy();
+    if location.starts_with("http://") || location.starts_with("https://") {
+        spec.to_path_buf()
+    } else {
+        directory.join(spec)
+    }
+}
+
+/// The certificate and key of the proxy, loaded to check they are usable.
+fn read_tls(certificate: &Path, key: &Path) -> Result<Tls, String> {
+    let read = |filename: &Path, what: &str| {
+        std::fs::read(filename).map_err(|error| {
+            format!("The TLS {} `{}` could not be read: {}.", what, filename.display(), error)
+        })
+    };
+    let (certificate_pem, key_pem) = (read(certificate, "certificate")?, read(key, "key")?);
+    let acceptor = Identity::from_pkcs8(&certificate_pem, &key_pem)
+        .and_then(TlsAcceptor::new)
+        .map_err(|error| {
+            format!(
+                "The TLS certificate `{}` and key `{}` can't be used: {}.",
+                certificate.display(),
+                key.display(),
+                error
+            )
+        })?;
+    Ok(Tls {
+        certificate: certificate.to_path_buf(),
+        acceptor,
+    })
+}
+
+/// `info,oas_middleware=debug`, each level must be known.
+fn check_log_filter(filter: &str, errors: &mut Vec<String>) {
+    for directive in filter.split(',').filter(|directive| !directive.is_empty()) {
+        let level = directive.rsplit('=').next().unwrap_or(directive);
+        let is_level = level.parse::<log::LevelFilter>().is_ok();
+        // A directive without `=` can be a module name, which logs everything in it.
+        if !is_level && directive.contains('=') {
+            errors.push(format!("The log level `{}` in `{}` is not known.", level, filter));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    /// Writes a configuration file named `name` in a directory of its own.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("oas-proxy-config-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&directory).unwrap();
+        let filename = directory.join(name);
+        std::fs::write(&filename, contents).unwrap();
+        filename
+    }
+
+    fn read(filename: &Path, variables: &[(&str, &str)]) -> Result<ConfigFile, String> {
+        let variables = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()));
+        ConfigFile::read_with(Some(filename), variables.collect::<Vec<_>>().into_iter())
+    }
+
+    fn without_flags(file: ConfigFile) -> Result<Settings, String> {
+        file.settings(&Config::from_iter(vec!["oasproxy"]))
+    }
+
+    #[test]
+    fn yaml_and_toml_files_are_read() {
+        let yaml = config_file("proxy.yaml", "port: 5001\nvalidation:\n  strict: true\n  ignored_paths: [/metrics]\n");
+        let toml = config_file("proxy.toml", "port = 5001\n[validation]\nstrict = true\nignored_paths = [\"/metrics\"]\n");
+        for filename in [yaml, toml] {
+            let file = read(&filename, &[]).unwrap();
+            assert_eq!(file.port, Some(5001));
+            assert!(file.validation.strict);
+            assert_eq!(file.validation.ignored_paths, ["/metrics"]);
+        }
+        let yaml_in_toml = config_file("yaml.toml", "port: 5001\n");
+        assert!(read(&yaml_in_toml, &[]).unwrap_err().contains("is not valid"));
+    }
+
+    #[test]
+    fn environment_variables_replace_the_file() {
+        let filename = config_file("env.yaml", "validation:\n  error_status: 400\n  observe: false\n");
+        let file = read(
+            &filename,
+            &[
+                ("OAS_VALIDATION__ERROR_STATUS", "422"),
+                ("OAS_VALIDATION__IGNORED_PATHS", "[/metrics, /health]"),
+                ("OAS_VALIDATION__OPERATIONS__listUsers__OBSERVE", "true"),
+                ("OAS_TIMEOUTS__BACKEND", "2.5"),
+                ("PATH", "/usr/bin"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(file.validation.error_status, Some(422));
+        assert!(!file.validation.observe);
+        assert_eq!(file.validation.ignored_paths, ["/metrics", "/health"]);
+        assert_eq!(file.validation.operations["listUsers"].observe, Some(true));
+        assert_eq!(file.timeouts.backend, Some(2.5));
+        assert!(file.warnings.is_empty());
+    }
+
+    #[test]
+    fn unknown_environment_variables_are_warnings() {
+        let filename = config_file("unknown-env.yaml", "port: 5001\n");
+        let file = read(
+            &filename,
+            &[("OAS_VALIDATION__STRICTNESS", "true"), ("OAS_CACHE__DIR", "/tmp"), ("OAS_LIMITS__BODY_SIZE", "10")],
+        )
+        .unwrap();
+        assert_eq!(file.limits.body_size, Some(10));
+        assert_eq!(
+            file.warnings,
+            [
+                "The environment variable OAS_VALIDATION__STRICTNESS is not a setting, ignoring it.",
+                "The environment variable OAS_CACHE__DIR is not a setting, ignoring it."
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_of_the_file_are_errors() {
+        let filename = config_file("unknown.yaml", "validation:\n  strictness: true\n");
+        let error = read(&filename, &[]).unwrap_err();
+        assert!(error.contains("is not valid at `validation.strictness`"), "{}", error);
+        assert!(error.contains("unknown field `strictness`"), "{}", error);
+
+        let filename = config_file("wrong-type.yaml", "port: 5001\n");
+        let error = read(&filename, &[("OAS_TIMEOUTS__BACKEND", "soon")]).unwrap_err();
+        assert!(error.contains("at `timeouts.backend`"), "{}", error);
+    }
+
+    #[test]
+    fn limits_and_timeouts_have_defaults() {
+        let settings = without_flags(ConfigFile::default()).unwrap();
+        assert_eq!(settings.max_body_size, DEFAULT_MAX_BODY_SIZE);
+        assert_eq!(settings.timeout, None);
+        assert!(settings.tls.is_none());
+
+        let mut file = ConfigFile::default();
+        file.limits.body_size = Some(1024);
+        file.timeouts.backend = Some(2.5);
+        let settings = without_flags(file).unwrap();
+        assert_eq!(settings.max_body_size, 1024);
+        assert_eq!(settings.timeout, Some(Duration::from_millis(2500)));
+    }
+
+    #[test]
+    fn responses_are_blocked_by_the_apis_and_operations_which_ask_for_it() {
+        let filename = config_file(
+            "block.yaml",
+            "apis:\n  - spec: users.yaml\n    block_responses: true\n  - spec: pets.yaml\n\
+             validation:\n  operations:\n    listUsers: {block_responses: false}\n",
+        );
+        let settings = without_flags(read(&filename, &[]).unwrap()).unwrap();
+        assert!(!settings.options.block_responses);
+        assert_eq!(settings.apis[0].block_responses, Some(true));
+        assert_eq!(settings.apis[1].block_responses, None);
+        assert_eq!(settings.options.operations["listUsers"].block_responses, Some(false));
+
+        let settings = ConfigFile::default().settings(&Config::from_iter(vec!["oasproxy", "--block-responses"]));
+        assert!(settings.unwrap().options.block_responses);
+    }
+
+    #[test]
+    fn every_invalid_setting_is_reported() {
+        let mut file = ConfigFile::default();
+        file.limits.body_size = Some(0);
+        file.timeouts.backend = Some(-1.0);
+        file.validation.reload_path = Some(String::from("reload"));
+        let error = without_flags(file).unwrap_err();
+        assert!(error.contains("The body size limit must be a positive number of bytes."), "{}", error);
+        assert!(error.contains("The backend timeout is -1"), "{}", error);
+        assert!(error.contains("The reload path `reload` must start with /."), "{}", error);
+    }
+
+    #[test]
+    fn tls_files_must_be_usable() {
+        let certificate = config_file("tls.yaml", "tls:\n  certificate: proxy.crt\n  key: proxy.key\n");
+        let directory = certificate.parent().unwrap();
+        let file = read(&certificate, &[]).unwrap();
+        let error = file.settings(&Config::from_iter(vec!["oasproxy", "-c", certificate.to_str().unwrap()]));
+        let missing = directory.join("proxy.crt");
+        assert!(
+            error.unwrap_err().contains(&format!("The TLS certificate `{}` could not be read", missing.display()))
+        );
+
+        std::fs::write(directory.join("proxy.crt"), "not a certificate").unwrap();
+        std::fs::write(directory.join("proxy.key"), "not a key").unwrap();
+        let file = read(&certificate, &[]).unwrap();
+        let error = file.settings(&Config::from_iter(vec!["oasproxy", "-c", certificate.to_str().unwrap()]));
+        assert!(error.unwrap_err().contains("can't be used"));
+    }
+}
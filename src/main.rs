@@ -3,12 +3,17 @@ extern crate http;
 extern crate serde;
 extern crate simple_proxy;
 
-use simple_proxy::middlewares::{Health};
+mod config;
+
+use simple_proxy::middlewares::{Health, Logger};
 use simple_proxy::{Environment, SimpleProxy};
 use oas_middleware::OASMiddleware;
+use config::{ConfigFile, MiddlewareName};
 
+use log::{debug, info, warn};
 use std::path::PathBuf;
 use http::uri::Authority;
+use http::StatusCode;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -16,43 +21,137 @@ use structopt::StructOpt;
 /// OAS Proxy provides a proxy to validate requests and responses
 /// based on the description of an OpenAPI file.
 struct Config {
-    #[structopt(
-        short,
-        env = "OAS_BACKEND",
-        default_value = "localhost:3000",
-        parse(try_from_str)
-    )]
-    /// The URI where requests will be proxied to.
-    backend: Authority,
-
-    #[structopt(short, env = "OAS_PORT", default_value = "5000")]
-    /// The port where the proxy is running.
-    port: u16,
-
-    #[structopt(
-        short,
-        env = "OAS_FILENAME",
-        default_value = "/tmp/openapi.yaml",
-        parse(from_os_str)
-    )]
-    /// The path to the openapi file describing the API.
-    input: PathBuf,
+    #[structopt(short, env = "OAS_BACKEND", parse(try_from_str))]
+    /// The URI where requests will be proxied to, localhost:3000 by default.
+    backend: Option<Authority>,
+
+    #[structopt(short, env = "OAS_PORT")]
+    /// The port where the proxy is running, 5000 by default.
+    port: Option<u16>,
+
+    #[structopt(short, env = "OAS_FILENAME", parse(from_os_str))]
+    /// The path to the openapi file describing the API, /tmp/openapi.yaml by default.
+    input: Option<PathBuf>,
+
+    #[structopt(short, long, env = "OAS_CONFIG", parse(from_os_str))]
+    /// A YAML or TOML configuration file, with the APIs behind the proxy, validation,
+    /// logging, timeout and TLS settings. Variables like OAS_VALIDATION__OBSERVE=true replace
+    /// its values and flags replace both.
+    config: Option<PathBuf>,
+
+    #[structopt(long)]
+    /// Rejects requests with query parameters not described in the openapi file.
+    strict: bool,
+
+    #[structopt(long, parse(try_from_str = parse_error_status))]
+    /// The status code of the responses to requests which do not agree with the openapi
+    /// file, 400 by default. Paths and methods it does not describe get 404 and 405.
+    error_status: Option<StatusCode>,
+
+    #[structopt(long)]
+    /// Proxies requests and responses which do not agree with the openapi file.
+    /// Violations are logged and listed in the OAS-Violations response header.
+    observe: bool,
+
+    #[structopt(long)]
+    /// Replaces responses which do not agree with the openapi file with a 502 Bad Gateway.
+    /// Otherwise they are proxied, logged and listed in the OAS-Violations response header.
+    block_responses: bool,
+
+    #[structopt(long)]
+    /// Answers requests with the examples of the openapi file instead of proxying them.
+    /// The status code can be chosen with the `Prefer: code=404` header.
+    mock: bool,
+
+    #[structopt(long)]
+    /// Reloads the openapi file when it changes or the proxy gets a SIGHUP.
+    /// Files which fail to load are logged and the proxy keeps the previous contract.
+    watch: bool,
+
+    #[structopt(long)]
+    /// Reloads the openapi file on `POST` requests to this path, like /admin/reload.
+    /// It needs --reload-token.
+    reload_path: Option<String>,
+
+    #[structopt(long, env = "OAS_RELOAD_TOKEN", hide_env_values = true)]
+    /// Requests to the reload path must have the header `Authorization: Bearer <token>`.
+    reload_token: Option<String>,
+
+    #[structopt(long)]
+    /// Requests only match the servers of the openapi file with their Host header, so
+    /// one file can describe several deployments. Relative server URLs match any host.
+    match_host: bool,
+
+    #[structopt(long)]
+    /// Proxies requests to paths not described in the openapi file without checks,
+    /// instead of answering them with 404. Requests which start with the prefix of no
+    /// API in the configuration file still get 404.
+    proxy_undocumented: bool,
+}
+
+fn parse_error_status(status: &str) -> Result<StatusCode, String> {
+    match status.parse::<StatusCode>() {
+        Ok(status) if status.is_client_error() || status.is_server_error() => Ok(status),
+        _ => Err(String::from("valid values: 400 to 599")),
+    }
 }
 
 fn main() {
-    env_logger::init();
     let config = Config::from_args();
-    println!("{:?}", config);
+    let settings = ConfigFile::read(config.config.as_deref())
+        .and_then(|file| file.settings(&config));
+    let settings = match settings {
+        Ok(settings) => settings,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // RUST_LOG is applied last so it can change the levels of the configuration file.
+    let mut logger = env_logger::Builder::new();
+    if let Some(filter) = &settings.log_filter {
+        logger.parse_filters(filter);
+    }
+    if let Ok(filter) = std::env::var("RUST_LOG") {
+        logger.parse_filters(&filter);
+    }
+    logger.init();
+    for warning in &settings.warnings {
+        warn!("{}", warning);
+    }
+    debug!("{:?}", settings);
 
-    let mut proxy = SimpleProxy::new(config.port, config.backend, Environment::Development);
-    let health = Health::new("/health", "OK !");
-//    let logger = Logger::new();
-    let oas_validator = OASMiddleware::new(&config.input);
+    let mut proxy = SimpleProxy::new(settings.port, settings.backend, Environment::Development);
+    if let Some(timeout) = settings.timeout {
+        proxy.set_timeout(timeout);
+    }
+    proxy.set_max_body_size(settings.max_body_size);
+    if let Some(tls) = settings.tls {
+        info!("Serving HTTPS with the certificate {}", tls.certificate.display());
+        proxy.set_tls(tls.acceptor);
+    }
+    let oas_validator = match OASMiddleware::with_apis(settings.apis, settings.options) {
+        Ok(oas_validator) => oas_validator,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
     // Order matters
-    proxy.add_middleware(Box::new(health));
-    proxy.add_middleware(Box::new(oas_validator));
-   // proxy.add_middleware(Box::new(logger));
+    let mut oas_validator = Some(oas_validator);
+    for middleware in settings.middlewares {
+        match middleware {
+            MiddlewareName::Health => proxy.add_middleware(Box::new(Health::new("/health", "OK !"))),
+            MiddlewareName::Logger => proxy.add_middleware(Box::new(Logger)),
+            MiddlewareName::Oas => {
+                if let Some(oas_validator) = oas_validator.take() {
+                    proxy.add_middleware(Box::new(oas_validator));
+                }
+            }
+        }
+    }
 
     // Start proxy
     proxy.run();